@@ -0,0 +1,35 @@
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use log::*;
+
+use device_config::store::ConfigStore;
+use sensor_http::api::{Api, SharedDevice};
+use sensor_http::server;
+use sensor_http::status::Status;
+
+use crate::config_storage::NvsStorage;
+
+///Starts a thread serving the bench API and dashboard on port 80 of the station address.  The main
+///loop keeps `status` current; config changes are stored and apply at the next restart.
+pub fn api_start(
+    status: Arc<Mutex<Status>>,
+    store: Arc<Mutex<ConfigStore<NvsStorage>>>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind("0.0.0.0:80")?;
+
+    std::thread::Builder::new()
+        .stack_size(8192)
+        .spawn(move || {
+            let mut api = Api::new(SharedDevice::new(status, store));
+
+            server::serve(
+                &listener,
+                &mut api,
+                |_| false,
+                |error| warn!("API request failed: {:?}", error),
+            );
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys::EspError;
+
+use device_config::storage::Storage;
+
+const NAMESPACE: &str = "config";
+
+///`Storage` over an NVS namespace in the `nvs` partition
+pub struct NvsStorage {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl NvsStorage {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<NvsStorage, EspError> {
+        Ok(NvsStorage {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+}
+
+impl Storage for NvsStorage {
+    type Error = EspError;
+
+    fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let Some(len) = self.nvs.blob_len(key)? else {
+            return Ok(None);
+        };
+
+        let mut data = vec![0; len];
+
+        Ok(self.nvs.get_raw(key, &mut data)?.map(<[u8]>::to_vec))
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Self::Error> {
+        self.nvs.set_raw(key, data)?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error> {
+        self.nvs.remove(key)?;
+
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
 #![feature(never_type)]
 
+mod bench_api;
+mod config_storage;
 mod wifi_manager;
-mod wifi_config;
 mod mqtt_manager;
+mod nvs_spill;
+mod provisioning;
 
 use std::borrow::BorrowMut;
 use std::ptr::null;
-use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use anyhow::Result;
 
@@ -38,7 +43,10 @@ use esp_idf_hal::{
 use embedded_hal_bus::i2c::AtomicDevice;
 
 use esp_idf_svc::{
-    eventloop::EspSystemEventLoop, nvs::EspDefaultNvsPartition, wifi::BlockingWifi, wifi::EspWifi,
+    eventloop::EspSystemEventLoop,
+    nvs::{EspCustomNvsPartition, EspDefaultNvsPartition},
+    wifi::BlockingWifi,
+    wifi::EspWifi,
 };
 
 use esp_idf_sys as _;
@@ -49,82 +57,120 @@ use ina237::types::Mode;
 use log::*;
 // use shared_bus::{BusManagerSimple, I2cProxy};
 
-use sht4x::{Precision, Sht4x};
+use sht40::sht40::{self as sht40_driver, Address, Sht40};
+use sht40::heater::{HeaterDuration, HeaterPower};
+use sht40::saturation::SaturationConfig;
+use sht40::sensor::Sht40Sensor;
 
 use ina237::ina237::Ina237;
+use ina237::sensor::Ina237Sensor;
 
 use nau7802::nau7802::Nau7802;
+use nau7802::sensor::Nau7802Sensor;
 
-// use embedded_sdmmc::*;
+use sensor_board_bsp::v0::{Board, SensorI2c};
 
-fn i2c_scan(i2c_bus_device: &mut AtomicDevice<I2cDriver>) {
-    let address_range = 0x00..=0x7F;
-    let empty_body: [u8; 0] = [];
+use sensor_core::bus_health::{BusHealth, HealthConfig};
+use sensor_core::queue::{OverflowPolicy, ReadingQueue};
+use sensor_core::scan::{self, Inventory};
+use sensor_core::scheduler::{Schedule, Scheduler};
+use sensor_core::sensor::Sensor;
 
-    let i2c_bus = i2c_bus_device.borrow_mut();
+use telemetry::snapshot::Snapshot;
 
-    for address in address_range {
-        let result = i2c_bus.write(address, &empty_body);
+use mqtt_manager::MqttEvent;
 
-        if result.is_ok() {
-            info!("Found {:#02x}", address);
-        } else {
-            match result.err() {
-                Some(AtomicError::Busy) => warn!("Unable to scan {:#02x} - Busy", address),
-                Some(AtomicError::Other(e)) => warn!("Unable to scan {:#02x} - {}", address, e),
-                _ => warn!("Unable to scan {:#02x} - Unknown Reason", address),
-            }
-        }
-    }
-}
+use device_config::config::{DeviceConfig, LoadCell, Network};
+use device_config::store::{ConfigStore, Source};
+use nau7802::sensor::LoadCellCalibration;
 
-fn sht_init<'a>(
-    i2c_bus: AtomicDevice<'a, I2cDriver<'a>>,
-) -> Sht4x<AtomicDevice<'a, I2cDriver<'a>>, Delay> {
-    // // For SHT40-AD1B, use address 0x44
-    let sht40 = Sht4x::new(i2c_bus);
+use sensor_mqtt::availability::{Action, Availability, Backoff, ConnectionEvent};
+use sensor_mqtt::commands::{Command, CommandError, Commands};
+use sensor_mqtt::discovery::{self, Discovery};
+use sensor_mqtt::publisher::{PublishConfig, Publisher};
+use sensor_mqtt::topics::Topics;
 
-    return sht40;
-}
+use sensor_http::status::Status;
 
-fn sht_read(sht_driver: &mut Sht4x<AtomicDevice<I2cDriver>, Delay>, delay: &mut Delay) -> (f32, f32) {
-    let measurement = sht_driver.measure(Precision::High, delay).unwrap();
+// use embedded_sdmmc::*;
 
-    return (measurement.temperature_celsius().to_num::<f32>(), measurement.humidity_percent().to_num::<f32>());
 
-    // info!(
-    //     "Temp: {:.2}\tHumidity: {:.2}",
-    //     measurement.temperature_celsius(),
-    //     measurement.humidity_percent(),
-    // );
-}
+// Readings held while offline: a couple of seconds of everything in RAM, then 64 batches of 32 in
+// the 64K spill partition, about half of it so NVS keeps room to compact.  Downsampling stretches
+// both over longer outages.
+const QUEUE_CAPACITY: usize = 128;
+const SPILL_CAPACITY: u32 = 64 * 32;
 
-fn ina_read(ina_driver: &mut Ina237<AtomicDevice<I2cDriver>>, delay: &mut Delay) -> (i32, i32, i32, i32) {
-    let m = ina_driver.read().unwrap_or_else(|error| {
-        panic!("Error reading INA")
-    });
+type SensorBus = BusHealth<SensorI2c, fn(&mut SensorI2c) -> bool, Delay>;
 
-    return (m.voltage_mV(), m.shunt_uV(), m.current_uA(), m.temp_mC());
+fn i2c_scan(i2c_bus_device: &mut AtomicDevice<SensorBus>, delay: &mut Delay) -> Inventory {
+    // The INA237 is tried before the SHT4x, which share addresses
+    let inventory = scan::scan(
+        i2c_bus_device,
+        delay,
+        &[nau7802::sensor::identify, ina237::sensor::identify, sht40::sensor::identify],
+    );
 
-    // info!("INA A: {} mV {} uV {} uA {} mC", m.voltage_mV(), m.shunt_uV(), m.current_uA(), m.temp_mC());
+    for found in inventory.iter() {
+        info!("Found {:#02x}: {:?}", found.address, found.device);
+    }
+
+    if inventory.overflow > 0 {
+        warn!("{} more devices not listed", inventory.overflow);
+    }
+
+    inventory
 }
 
-fn nau_read(nau_driver: &mut Nau7802<AtomicDevice<I2cDriver>,Delay>, delay: &mut Delay) -> (i32) {
-    let is_ready = nau_driver.is_data_ready().unwrap();
-    let ctrl2 = nau_driver.ctrl2().unwrap();
+fn sht_init<'a>(
+    i2c_bus: AtomicDevice<'a, SensorBus>,
+    address: Address,
+) -> Sht40<AtomicDevice<'a, SensorBus>> {
+    let mut sht40 = Sht40::with_address(i2c_bus, address);
+
+    sht40.set_saturation_config(SaturationConfig {
+        recovery_heater: Some((HeaterPower::Mw200, HeaterDuration::Ms1000)),
+        ..SaturationConfig::default()
+    });
 
-    let reading_a = if is_ready {
-        nau_driver.read_adc().unwrap()
-    } else {
+    return sht40;
+}
 
-    info!("NAU: data ready: {} {:04x}", is_ready, Into::<u8>::into(ctrl2));
+/// Development builds can be provisioned from `WIFI_SSID`, `WIFI_PSK`, `MQTT_URL`, `MQTT_USER`,
+/// `MQTT_PASSWORD` and `DEVICE_ID` set when building, so no credentials live in the source.
+/// Returns true if anything was set.
+fn seed_from_env(config: &mut DeviceConfig) -> bool {
+    let mut seeded = false;
+
+    if let (Some(ssid), psk) = (option_env!("WIFI_SSID"), option_env!("WIFI_PSK")) {
+        config.network.networks.push(Network {
+            ssid: ssid.to_string(),
+            psk: psk.unwrap_or_default().to_string(),
+        });
+        seeded = true;
+    }
 
-        0
-    };
+    for (value, field) in [
+        (option_env!("MQTT_URL"), &mut config.mqtt.url),
+        (option_env!("MQTT_USER"), &mut config.mqtt.user),
+        (option_env!("MQTT_PASSWORD"), &mut config.mqtt.password),
+        (option_env!("DEVICE_ID"), &mut config.device_id),
+    ] {
+        if let Some(value) = value {
+            *field = value.to_string();
+            seeded = true;
+        }
+    }
 
-    delay.delay_ms(10);
+    seeded
+}
 
-    return (reading_a)
+/// Checks a command's optional `sensor` argument names this sensor
+fn select(sensor: &Option<String>, candidate: &dyn Sensor) -> Result<(), CommandError> {
+    match sensor {
+        Some(sensor) if *sensor != candidate.channel().to_string() => Err(CommandError::UnknownSensor),
+        _ => Ok(()),
+    }
 }
 
 fn sd_test() {}
@@ -140,16 +186,59 @@ fn main() -> ! {
     // Bind the log crate to the ESP Logging facilities
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    let peripherals = Peripherals::take().unwrap();
+    let board = Board::take().unwrap();
 
     let sys_loop = EspSystemEventLoop::take().unwrap();
     let nvs = EspDefaultNvsPartition::take().unwrap();
 
-    let _wifi = wifi_manager::wifi_create(&sys_loop, &nvs).unwrap();
+    let mut config_store = ConfigStore::new(config_storage::NvsStorage::new(nvs.clone()).unwrap());
+
+    let (mut config, source) = config_store.load_or_default();
+
+    match source {
+        Ok(Source::Migrated { from, saved: false }) => {
+            warn!("Config migrated from version {} but not saved back, will migrate again", from)
+        }
+        Ok(source) => info!("Config: {:?}", source),
+        Err(error) => warn!("Config unreadable, using defaults: {:?}", error),
+    }
+
+    if !config.is_provisioned() && seed_from_env(&mut config) {
+        info!("Config seeded from build environment");
+
+        if let Err(error) = config_store.save(&config) {
+            warn!("Config save failed: {:?}", error);
+        }
+    }
+
+    if !config.is_provisioned() {
+        info!("Not provisioned, starting setup portal");
 
-    let mqtt_client = mqtt_manager::mqtt_create().unwrap();
+        provisioning::run(board.modem, &sys_loop, &nvs, config_store, config);
+    }
 
-    mqtt_manager::mqtt_post(&mqtt_client, "sensor/esp123/status", "alive").unwrap();
+    if provisioning::take_request(&nvs) {
+        info!("Stored networks couldn't be joined, starting setup portal");
+
+        provisioning::run(board.modem, &sys_loop, &nvs, config_store, config);
+    }
+
+    let wifi_link = wifi_manager::wifi_start(board.modem, &sys_loop, &nvs, config.network.networks.clone()).unwrap();
+
+    let config_store = Arc::new(Mutex::new(config_store));
+
+    let spill_partition = EspCustomNvsPartition::take(nvs_spill::PARTITION).unwrap();
+    let spill = nvs_spill::NvsSpill::new(spill_partition, SPILL_CAPACITY).unwrap();
+
+    let topics = Topics::new(&config.mqtt.prefix, &config.device_id);
+
+    let mut availability = Availability::new(&topics, Backoff::default());
+
+    let (mqtt_events_tx, mqtt_events) = mpsc::channel();
+
+    let mqtt_client = mqtt_manager::mqtt_create(&config, &availability.last_will(), mqtt_events_tx.clone()).unwrap();
+
+    let mut publisher = Publisher::new(mqtt_client, topics, PublishConfig::default());
 
     // let mut clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
@@ -178,133 +267,352 @@ fn main() -> ! {
 
     let mut delay = Delay::new_default();
 
-    let mut wifi = wifi_init(peripherals.modem, sys_loop);
-    wifi_scan(&mut wifi);
-
-    let scl = peripherals.pins.gpio5;
-    let sda = peripherals.pins.gpio4;
-
-    let i2c_config = I2cConfig::new().baudrate(100.kHz().into());
-    let i2c_bus_raw = I2cDriver::new(peripherals.i2c0, sda, scl, &i2c_config).unwrap();
-
-    let i2c_bus_cell = AtomicCell::new(i2c_bus_raw);
+    // A device that keeps failing gets SCL clocked by hand and the driver re-created
+    let i2c_bus_cell = AtomicCell::new(BusHealth::new(
+        board.i2c,
+        SensorI2c::recover as fn(&mut SensorI2c) -> bool,
+        Delay::new_default(),
+        HealthConfig::default(),
+    ));
 
     info!("I2c Bus Configured");
 
-    // {
-    //     let mut i2c_scan_bus = AtomicDevice::new(&i2c_bus_cell);
+    {
+        let mut i2c_scan_bus = AtomicDevice::new(&i2c_bus_cell);
 
-    //     i2c_scan(&mut i2c_scan_bus);
-    // }
+        i2c_scan(&mut i2c_scan_bus, &mut delay);
+    }
 
     {
         let i2c_nau_bus = AtomicDevice::new( &i2c_bus_cell);
-        let mut nau_driver = Nau7802::new(i2c_nau_bus);
 
-        nau_driver.initialize(&mut delay).unwrap();
-        
-        info!("NAU Revision: {:#04x}", nau_driver.revision_id().unwrap());
+        info!("NAU Calibrate begin");
+
+        let mut nau_driver = Nau7802::builder(i2c_nau_bus)
+            .ldo(nau7802::registers::LdoVoltage::v3_0)
+            .gain(nau7802::registers::Gains::x16)
+            .channel(nau7802::nau7802::AdcChannel::A)
+            .build(&mut delay)
+            .unwrap();
+
+        info!("NAU Calibrate end");
+
+        info!("NAU Device: {:?}", nau_driver.device_info().unwrap());
 
         info!("NAU CTRL1: {:#04x} CTRL2: {:#04x}", Into::<u8>::into(nau_driver.ctrl1().unwrap()), Into::<u8>::into(nau_driver.ctrl2().unwrap()));
 
-        nau_driver.set_ldo_voltage(nau7802::registers::LdoVoltage::v3_0).unwrap();
-        nau_driver.enable_ldo().unwrap();
+        let mut i2c_probe_bus = AtomicDevice::new(&i2c_bus_cell);
 
-        // if nau_driver.select_channel(nau7802::nau7802::AdcChannel::B, &mut delay).unwrap() {
-        //     info!("NAU Channel Changed to B");
-        //     delay.delay_ms(20);
-        // }
+        let mut sht_addresses = sht40_driver::probe(&mut i2c_probe_bus, &mut delay)
+            .into_iter()
+            .filter_map(|(address, serial_number)| {
+                let serial_number = serial_number?;
 
-        // info!("NAU CTRL1: {:#04x} CTRL2: {:#04x}", Into::<u8>::into(nau_driver.ctrl1().unwrap()), Into::<u8>::into(nau_driver.ctrl2().unwrap()));
+                info!("SHT40 at {:#02x}: Sensor Device Id: {:#02x}", u8::from(address), serial_number);
 
-        // info!("NAU Calibrate begin");
+                Some(address)
+            });
 
-        // nau_driver.calibrate(&mut delay).unwrap();
+        // Inside the enclosure is the first sensor found, outside the second if fitted
+        let inside_address = sht_addresses.next().unwrap_or(Address::Ad1b);
+        let outside_address = sht_addresses.next();
 
-        // info!("NAU Calibrate end");
+        let mut sht_inside = Sht40Sensor::new(sht_init(AtomicDevice::new(&i2c_bus_cell), inside_address), "inside");
+        let mut sht_outside = outside_address
+            .map(|address| Sht40Sensor::new(sht_init(AtomicDevice::new(&i2c_bus_cell), address), "outside"));
 
-        info!("NAU CTRL1: {:#04x} CTRL2: {:#04x}", Into::<u8>::into(nau_driver.ctrl1().unwrap()), Into::<u8>::into(nau_driver.ctrl2().unwrap()));
+        let uptime = Instant::now();
+
+        let i2c_ina_bus = AtomicDevice::new(&i2c_bus_cell);
 
-        if nau_driver.select_channel(nau7802::nau7802::AdcChannel::A, &mut delay).unwrap() {
-            info!("NAU Channel Changed to A");
-            delay.delay_ms(20);
+        let mut ina_config_registers = ina237::types::ConfigurationRegisterValues::new();
+
+        ina_config_registers.adc_range = AdcRange::LOW;
+        ina_config_registers.mode = Mode::ContinuousTempShuntBusVoltage;
+        ina_config_registers.adc_averaging = AdcAveraging::Avg64;
+
+        let ina_configuration_a = ina237::types::Configuration::new(0x46, config.calibration.ina237_shunt_cal);
+
+        let mut ina_a = Ina237::new(i2c_ina_bus, ina_configuration_a);
+
+        if let Err(error) = ina_a.initialize(ina_config_registers) {
+            warn!("INA237 A: initialize failed: {:?}", error);
         }
 
-        nau_driver.set_gain(nau7802::registers::Gains::x16).unwrap();
+        info!("INA237 A: Configuration {:#04x?}", ina_a.configuration().ok());
 
-        info!("NAU CTRL1: {:#04x} CTRL2: {:#04x}", Into::<u8>::into(nau_driver.ctrl1().unwrap()), Into::<u8>::into(nau_driver.ctrl2().unwrap()));
+        info!("INA237 A: ADC Configuration {:#04x?}", ina_a.adc_configuration().ok());
 
-        info!("NAU Calibrate begin");
+        info!("INA 237 A: {:#04x?}", ina_a.manufacturer_id().ok());
 
-        nau_driver.calibrate(&mut delay).unwrap();
+        info!("INA 237 A: Shunt Cal: {:?}", ina_a.shunt_cal().ok());
 
-        info!("NAU Calibrate end");
+        let mut ina_a = Ina237Sensor::new(ina_a, "a");
+        let mut nau_a = Nau7802Sensor::new(nau_driver, "a");
 
-        info!("NAU CTRL1: {:#04x} CTRL2: {:#04x}", Into::<u8>::into(nau_driver.ctrl1().unwrap()), Into::<u8>::into(nau_driver.ctrl2().unwrap()));
+        nau_a.set_calibration(config.calibration.load_cell.map(|load_cell| LoadCellCalibration {
+            zero_offset: load_cell.zero_offset,
+            counts_per_gram: load_cell.counts_per_gram,
+        }));
 
-        let i2c_sht_bus = AtomicDevice::new(&i2c_bus_cell);
+        let now_ms = || uptime.elapsed().as_millis() as u64;
 
-        let mut sht40 = Sht4x::new(i2c_sht_bus);
+        // Power at 10 Hz, the load cell at its 10 SPS conversion rate, humidity once a minute
+        let mut scheduler = Scheduler::<4>::new(now_ms());
 
-        let device_id = sht40.serial_number(&mut delay).unwrap();
+        let sampling = config.sampling;
 
-        info!("SHT40 Sensor Device Id: {:#02x}", device_id);
+        scheduler.add(Schedule::new(sampling.power_interval_ms as u64, 5));
+        scheduler.add(Schedule::new(sampling.load_interval_ms as u64, 0));
+        scheduler.add(Schedule::new(sampling.climate_interval_ms as u64, 9));
 
-        let i2c_ina_bus = AtomicDevice::new(&i2c_bus_cell);
+        if sht_outside.is_some() {
+            scheduler.add(Schedule::new(sampling.climate_interval_ms as u64, 9));
+        }
 
-        let mut ina_config_registers = ina237::types::ConfigurationRegisterValues::new();
+        // Same order as the scheduler
+        let mut inventory = vec![
+            (ina_a.channel(), ina_a.quantities()),
+            (nau_a.channel(), nau_a.quantities()),
+            (sht_inside.channel(), sht_inside.quantities()),
+        ];
 
-        ina_config_registers.adc_range = AdcRange::LOW;
-        ina_config_registers.mode = Mode::ContinuousTempShuntBusVoltage;
-        ina_config_registers.adc_averaging = AdcAveraging::Avg64;
+        if let Some(sht_outside) = sht_outside.as_ref() {
+            inventory.push((sht_outside.channel(), sht_outside.quantities()));
+        }
 
-        let ina_configuration_a = ina237::types::Configuration::new(0x46, 4000);
+        let status = Arc::new(Mutex::new(Status::new(inventory.iter().copied())));
 
-        let mut ina_a = Ina237::new(i2c_ina_bus, ina_configuration_a);
+        if let Err(error) = bench_api::api_start(status.clone(), config_store.clone()) {
+            warn!("Bench API not started: {:?}", error);
+        }
 
-        ina_a.initialize(ina_config_registers);
+        let discovery = Discovery::new(
+            discovery::DEFAULT_DISCOVERY_PREFIX,
+            &config.device_id,
+            publisher.topics().clone(),
+        );
 
-        info!("INA237 A: Configuration {:#04x}", ina_a.configuration());
+        let commands = Commands::new(publisher.topics());
 
-        info!("INA237 A: ADC Configuration {:#04x}", ina_a.adc_configuration());
+        let channels: Vec<_> = inventory.iter().map(|(channel, _)| *channel).collect();
 
-        info!("INA 237 A: {:#04x}", ina_a.manufacturer_id(),);
+        let mut queue = ReadingQueue::<QUEUE_CAPACITY, _>::with_spill(OverflowPolicy::Downsample, spill, &channels);
 
-        info!("INA 237 A: Shunt Cal: {}", ina_a.shunt_cal());
+        let mut relays = board.relays;
 
-        FreeRtos::delay_ms(200u32);
+        let mut readings = Vec::new();
 
         loop {
-            let sht_reading = sht_read(&mut sht40, &mut delay);
+            while let Ok(event) = mqtt_events.try_recv() {
+                let event = match event {
+                    MqttEvent::Connection(event) => event,
+                    MqttEvent::Received { topic, payload } => {
+                        let mut reboot = false;
+
+                        let mut handler = |command: &Command| match command {
+                            Command::Tare { sensor } => {
+                                select(sensor, &nau_a)?;
+
+                                let offset = nau_a.tare().map_err(|_| CommandError::Failed)?;
+
+                                info!("Tared at {}", offset);
+
+                                let load_cell = nau_a.calibration().map(|calibration| LoadCell {
+                                    zero_offset: calibration.zero_offset,
+                                    counts_per_gram: calibration.counts_per_gram,
+                                });
+
+                                config.calibration.load_cell = load_cell;
+
+                                // Only the calibration changes, keeping anything the bench API stored since boot
+                                let mut store = config_store.lock().unwrap();
+                                let (mut stored, _) = store.load().map_err(|_| CommandError::Failed)?;
+
+                                stored.calibration.load_cell = load_cell;
+
+                                store.save(&stored).map_err(|_| CommandError::Failed)
+                            }
+                            Command::Calibrate { sensor } => {
+                                select(sensor, &nau_a)?;
+
+                                nau_a.driver().calibrate(&mut delay).map_err(|_| CommandError::Failed)
+                            }
+                            Command::SetInterval { sensor, interval_ms } => {
+                                let index = inventory
+                                    .iter()
+                                    .position(|(channel, _)| channel.to_string() == *sensor)
+                                    .ok_or(CommandError::UnknownSensor)?;
+
+                                let schedule = scheduler.schedule(index).ok_or(CommandError::UnknownSensor)?;
+
+                                scheduler.set_schedule(index, Schedule::new(*interval_ms, schedule.latency_ms));
+
+                                Ok(())
+                            }
+                            Command::Relay { relay, on } => relays
+                                .set(*relay, *on)
+                                .map_err(|_| CommandError::Failed)
+                                .and_then(|valid| valid.then_some(()).ok_or(CommandError::BadArgument("relay"))),
+                            Command::Heater { sensor, power_mw, duration_ms } => {
+                                // No sensor named means the inside one; the outside one only by name
+                                let sht = match sht_outside.as_mut() {
+                                    Some(sht_outside) if sensor.is_some() && select(sensor, sht_outside).is_ok() => {
+                                        sht_outside
+                                    }
+                                    _ => {
+                                        select(sensor, &sht_inside)?;
+                                        &mut sht_inside
+                                    }
+                                };
+
+                                let power = match power_mw {
+                                    200 => HeaterPower::Mw200,
+                                    110 => HeaterPower::Mw110,
+                                    _ => HeaterPower::Mw20,
+                                };
+
+                                let duration = match duration_ms {
+                                    1000 => HeaterDuration::Ms1000,
+                                    _ => HeaterDuration::Ms100,
+                                };
+
+                                let measurement = sht
+                                    .driver()
+                                    .heat(power, duration, now_ms(), &mut delay)
+                                    .map_err(|_| CommandError::Failed)?;
+
+                                info!("Heater pulse done: {:?}", measurement);
+
+                                Ok(())
+                            }
+                            Command::Reboot => {
+                                reboot = true;
+
+                                Ok(())
+                            }
+                        };
+
+                        match commands.dispatch(publisher.client(), &mut handler, &topic, &payload) {
+                            Ok(Some(handled)) => info!("Command {}: {:?}", handled.request.name, handled.outcome),
+                            Ok(None) => {}
+                            Err(error) => warn!("Command result publish failed: {:?}", error),
+                        }
+
+                        if reboot {
+                            info!("Rebooting");
+
+                            let _ = availability.go_offline(publisher.client());
+
+                            FreeRtos::delay_ms(500);
+
+                            esp_idf_hal::reset::restart();
+                        }
+
+                        continue;
+                    }
+                };
+
+                if availability.handle(event, now_ms()) == Action::Announce {
+                    info!("MQTT online, connection {}", availability.connects());
+
+                    let announced = availability.announce(publisher.client(), |client| {
+                        commands.subscribe(client)?;
+                        discovery.publish(client, inventory.iter().copied())
+                    });
+
+                    if let Err(error) = announced {
+                        warn!("Announce failed: {:?}", error);
+                    }
+                }
+            }
 
-            FreeRtos::delay_ms(200u32);
+            if availability.poll(now_ms()) == Action::Reconnect {
+                info!("MQTT reconnecting");
+
+                // Dropping the old client ends its event thread
+                match mqtt_manager::mqtt_create(&config, &availability.last_will(), mqtt_events_tx.clone()) {
+                    Ok(client) => *publisher.client() = client,
+                    Err(error) => {
+                        warn!("MQTT connect failed: {:?}", error);
+                        availability.handle(ConnectionEvent::Disconnected, now_ms());
+                    }
+                }
+            }
 
-            let ina_reading = ina_read(&mut ina_a, &mut delay);
+            let mut sensors: Vec<&mut dyn Sensor> = vec![&mut ina_a, &mut nau_a, &mut sht_inside];
 
-            FreeRtos::delay_ms(200u32);
+            if let Some(sht_outside) = sht_outside.as_mut() {
+                sensors.push(sht_outside);
+            }
 
-            let nau_reading = nau_read(&mut nau_driver, &mut delay);
+            let wait_ms = scheduler.run_due(
+                &now_ms,
+                &mut sensors,
+                &mut delay,
+                &mut |reading| {
+                    status.lock().unwrap().record(&reading);
+                    readings.push(reading);
+                },
+                &mut |channel, error| {
+                    warn!("Reading {} failed: {:?}", channel, error);
+                    status.lock().unwrap().failed(channel, error);
+                },
+            );
+
+            // The backlog goes to the telemetry topic, each reading with its own timestamp
+            if availability.is_online() && !queue.is_empty() {
+                queue.drain(|reading| {
+                    let backlog = Snapshot::new(&config.device_id, reading.timestamp_ms, std::slice::from_ref(reading));
+
+                    match publisher.publish_backlog(&backlog) {
+                        Ok(()) => true,
+                        Err(error) => {
+                            warn!("Backlog publish failed: {:?}", error);
+                            false
+                        }
+                    }
+                });
+            }
 
-            info!("Reading: {} mV, {} uA, {} C, {} %RH, {} ({:04x}) A", 
-            ina_reading.0, ina_reading.3,
-            sht_reading.0, sht_reading.1,
-            nau_reading, nau_reading);
-        }
-    }
+            if !readings.is_empty() {
+                let snapshot = Snapshot::new(&config.device_id, now_ms(), &readings);
+
+                info!("Snapshot: {}", String::from_utf8_lossy(&snapshot.encode()));
+
+                // Live readings wait behind any backlog, so the retained values stay the latest
+                let published = availability.is_online()
+                    && queue.is_empty()
+                    && match publisher.publish_snapshot(&snapshot) {
+                        Ok(()) => true,
+                        Err(error) => {
+                            warn!("Publish failed: {:?}", error);
+                            false
+                        }
+                    };
+
+                for reading in readings.drain(..) {
+                    if !published {
+                        queue.push(reading);
+                    }
+                }
+            }
 
-    let spi = peripherals.spi2;
+            if !availability.is_online() && !queue.is_empty() {
+                let wifi = if wifi_link.lock().unwrap().is_some() { "up" } else { "down" };
 
-    let sck = peripherals.pins.gpio15;
-    let cipo = peripherals.pins.gpio17;
-    let copi = peripherals.pins.gpio16;
-    let sd_cs = peripherals.pins.gpio14;
-    let mut sd_det = PinDriver::input(peripherals.pins.gpio11).unwrap();
+                info!("Offline (Wi-Fi {}), {} readings queued, {} dropped", wifi, queue.len(), queue.dropped());
+            }
 
-    sd_det.set_pull(Pull::Up).unwrap();
+            let wait_ms = availability.wait_ms(now_ms()).map_or(wait_ms, |retry_ms| wait_ms.min(retry_ms));
 
-    let has_sd = sd_det.get_level();
+            FreeRtos::delay_ms(wait_ms as u32);
+        }
+    }
+
+    let sd_card = board.sd_card;
 
-    if has_sd == esp_idf_hal::gpio::Level::Low {
+    if sd_card.is_card_present() {
         info!("SD Card Detected");
     } else {
         info!("No SD Card Detected");
@@ -316,24 +624,17 @@ fn main() -> ! {
 
     // info!("Initializing SD Card");
 
-    // let spi_driver = SpiDriver::new(spi,
-    // sck,
-    // copi,
-    // Some(cipo),
-    // &driver_config
-    // ).unwrap();
-
     // info!("Preparing SD Card");
 
     // let sdcard_config = config::Config::new().baudrate(26.MHz().into());
 
     // let sd_device = SpiDeviceDriver::new(
-    //     &spi_driver,
+    //     &sd_card.spi,
     //     None::<AnyOutputPin>,
     //     &sdcard_config
     // ).unwrap();
 
-    // let sdcard = embedded_sdmmc::SdCard::new(sd_device, PinDriver::output(sd_cs).unwrap(), FreeRtos);
+    // let sdcard = embedded_sdmmc::SdCard::new(sd_device, sd_card.cs, FreeRtos);
 
     // info!("SD Card Type: {}", sdcard.get_card_type().unwrap() as u8);
 
@@ -1,29 +1,106 @@
+use std::sync::mpsc::Sender;
+
 use esp_idf_svc::{mqtt::client::*, sys::EspError};
+use log::*;
+
+use device_config::config::DeviceConfig;
+use sensor_mqtt::availability::{ConnectionEvent, LastWill};
+use sensor_mqtt::client::{self, MqttClient};
+
+pub enum MqttEvent {
+    Connection(ConnectionEvent),
+    Received { topic: String, payload: Vec<u8> },
+}
+
+///`MqttClient` over the ESP-IDF client
+pub struct EspClient(pub EspMqttClient<'static>);
+
+impl MqttClient for EspClient {
+    type Error = EspError;
+
+    fn publish(
+        &mut self,
+        topic: &str,
+        qos: client::QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<(), Self::Error> {
+        self.0.publish(topic, esp_qos(qos), retain, payload)?;
 
-const MQTT_URL: str = "mqtt://littlerascal.local:1883";
-const MQTT_USER: str = "status_light";
-const MQTT_PASSWORD: str = "";
-const MQTT_CLIENT_ID: str = "esp32-123";
+        Ok(())
+    }
 
-pub fn mqtt_create() -> Result<(EspMqttClient<'static>, EspMqttConnection), EspError> {
-    
+    fn subscribe(&mut self, topic: &str, qos: client::QoS) -> Result<(), Self::Error> {
+        self.0.subscribe(topic, esp_qos(qos))?;
 
-    let (mqtt_client, mqtt_conn) = EspMqttClient::new(
-        &MQTT_URL,
+        Ok(())
+    }
+}
+
+fn esp_qos(qos: client::QoS) -> QoS {
+    match qos {
+        client::QoS::AtMostOnce => QoS::AtMostOnce,
+        client::QoS::AtLeastOnce => QoS::AtLeastOnce,
+    }
+}
+
+///Connects with `last_will` and starts a thread draining connection events, which the client
+///needs to make progress.  Connects, disconnects and received messages are forwarded to `events`;
+///the thread ends when the client is dropped.  ESP-IDF's own reconnect is off, so a dropped
+///connection stays down until the caller recreates the client.
+pub fn mqtt_create(
+    config: &DeviceConfig,
+    last_will: &LastWill,
+    events: Sender<MqttEvent>,
+) -> Result<EspClient, EspError> {
+    let (mqtt_client, mut mqtt_conn) = EspMqttClient::new(
+        &config.mqtt.url,
         &MqttClientConfiguration {
-            username: Some(&MQTT_USERNAME),
-            password: Some(&MQTT_PASSWORD),
-            client_id: Some(&MQTT_CLIENT_ID),
+            username: Some(&config.mqtt.user).filter(|user| !user.is_empty()).map(String::as_str),
+            password: Some(&config.mqtt.password).filter(|password| !password.is_empty()).map(String::as_str),
+            client_id: Some(config.client_id()),
+            lwt: Some(LwtConfiguration {
+                topic: last_will.topic,
+                payload: last_will.payload,
+                qos: esp_qos(last_will.qos),
+                retain: last_will.retain,
+            }),
+            disable_auto_reconnect: true,
             ..Default::default()
         },
     )?;
 
-    Ok((mqtt_client, mqtt_conn))
-}
+    std::thread::Builder::new()
+        .stack_size(6000)
+        .spawn(move || {
+            while let Ok(event) = mqtt_conn.next() {
+                let event = match event.payload() {
+                    EventPayload::Connected(_) => MqttEvent::Connection(ConnectionEvent::Connected),
+                    EventPayload::Disconnected => MqttEvent::Connection(ConnectionEvent::Disconnected),
+                    // Commands are small, so only whole messages are handled
+                    EventPayload::Received {
+                        topic: Some(topic),
+                        data,
+                        details: Details::Complete,
+                        ..
+                    } => MqttEvent::Received {
+                        topic: topic.to_string(),
+                        payload: data.to_vec(),
+                    },
+                    payload => {
+                        info!("MQTT event: {:?}", payload);
+                        continue;
+                    }
+                };
 
-pub(crate) fn mqtt_post(mqtt_tuple: &(EspMqttClient<'_>, EspMqttConnection), topic: &str, value: &str) -> Result<(), EspError> {
-    let (mqtt_client, mqtt_connection) = mqtt_tuple;
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
 
-    
-}
+            info!("MQTT connection closed");
+        })
+        .unwrap();
 
+    Ok(EspClient(mqtt_client))
+}
@@ -0,0 +1,173 @@
+use esp_idf_svc::nvs::{EspCustomNvsPartition, EspNvs, NvsCustom};
+use esp_idf_svc::sys::EspError;
+use log::*;
+
+use sensor_core::queue::{Record, Spill, RECORD_LEN};
+
+///Partition in partitions.csv, kept apart from `nvs` so a full spill can't starve config saves
+pub const PARTITION: &str = "spill";
+
+const NAMESPACE: &str = "spill";
+
+///Records per blob.  512 bytes, so a batch costs one blob write rather than 32.
+const BATCH: usize = 32;
+
+///Readings spilled to their own NVS partition in batches of `BATCH` records, one blob per batch
+///keyed by sequence number.  Records wait in RAM until a batch fills, and head and tail are only
+///stored when a batch is written or freed.
+///
+///Timestamps are ms since boot, so the backlog is discarded at startup rather than replayed with
+///times from a previous boot.
+pub struct NvsSpill {
+    nvs: EspNvs<NvsCustom>,
+    ///Sequence numbers of the oldest and the next stored batch
+    head: u32,
+    tail: u32,
+    ///The batch at `head` once read back, and how many of its records have been popped
+    reading: Vec<Record>,
+    popped: usize,
+    ///Records for the next batch
+    writing: Vec<Record>,
+    ///Most batches stored
+    capacity: u32,
+}
+
+impl NvsSpill {
+    ///Holds up to `capacity` readings, rounded down to whole batches
+    pub fn new(partition: EspCustomNvsPartition, capacity: u32) -> Result<NvsSpill, EspError> {
+        let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+        let head = nvs.get_u32("head")?.unwrap_or(0);
+        let tail = nvs.get_u32("tail")?.unwrap_or(head);
+
+        let mut spill = NvsSpill {
+            nvs,
+            head,
+            tail,
+            reading: Vec::new(),
+            popped: 0,
+            writing: Vec::with_capacity(BATCH),
+            capacity: capacity / BATCH as u32,
+        };
+
+        if spill.head != spill.tail {
+            info!("Discarding {} spilled batches from before restart", spill.batches());
+        }
+
+        while spill.head != spill.tail {
+            spill.free_head();
+        }
+
+        Ok(spill)
+    }
+
+    ///NVS keys are at most 15 characters; `b` plus 8 hex digits fits
+    fn key(sequence: u32) -> String {
+        format!("b{:08x}", sequence)
+    }
+
+    fn batches(&self) -> u32 {
+        self.tail.wrapping_sub(self.head)
+    }
+
+    fn save(&mut self) {
+        if let Err(error) = self
+            .nvs
+            .set_u32("head", self.head)
+            .and_then(|_| self.nvs.set_u32("tail", self.tail))
+        {
+            warn!("Spill index write failed: {:?}", error);
+        }
+    }
+
+    fn write_batch(&mut self) -> bool {
+        let data: Vec<u8> = self.writing.iter().flatten().copied().collect();
+
+        if let Err(error) = self.nvs.set_raw(&Self::key(self.tail), &data) {
+            warn!("Spill write failed: {:?}", error);
+            return false;
+        }
+
+        self.tail = self.tail.wrapping_add(1);
+        self.writing.clear();
+        self.save();
+
+        true
+    }
+
+    ///Reads the batch at `head` back if it isn't already
+    fn read_head(&mut self) {
+        if !self.reading.is_empty() {
+            return;
+        }
+
+        let mut data = [0; BATCH * RECORD_LEN];
+
+        let len = match self.nvs.get_raw(&Self::key(self.head), &mut data) {
+            Ok(Some(stored)) => stored.len() / RECORD_LEN,
+            _ => 0,
+        };
+
+        self.reading = data[..len * RECORD_LEN]
+            .chunks_exact(RECORD_LEN)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+
+        // A missing or short blob decodes as unknown channels, which the queue drops and counts
+        self.reading.resize(BATCH, [0xFF; RECORD_LEN]);
+    }
+
+    fn free_head(&mut self) {
+        let _ = self.nvs.remove(&Self::key(self.head));
+
+        self.head = self.head.wrapping_add(1);
+        self.reading.clear();
+        self.popped = 0;
+        self.save();
+    }
+}
+
+impl Spill for NvsSpill {
+    fn push(&mut self, record: &Record) -> bool {
+        if self.writing.len() == BATCH - 1 && self.batches() >= self.capacity {
+            return false;
+        }
+
+        self.writing.push(*record);
+
+        if self.writing.len() == BATCH && !self.write_batch() {
+            self.writing.pop();
+            return false;
+        }
+
+        true
+    }
+
+    fn front(&mut self) -> Option<Record> {
+        if self.head != self.tail {
+            self.read_head();
+
+            return Some(self.reading[self.popped]);
+        }
+
+        self.writing.first().copied()
+    }
+
+    fn pop(&mut self) {
+        if self.head != self.tail {
+            self.read_head();
+
+            self.popped += 1;
+
+            if self.popped == BATCH {
+                self.free_head();
+            }
+        } else if !self.writing.is_empty() {
+            self.writing.remove(0);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.batches() as usize * BATCH - self.popped + self.writing.len()
+    }
+}
@@ -0,0 +1,160 @@
+use std::net::{TcpListener, UdpSocket};
+use std::thread;
+
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::hal::reset;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs};
+use esp_idf_svc::sys::EspError;
+use esp_idf_svc::wifi::{
+    AccessPointConfiguration, AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi,
+};
+use log::*;
+
+use device_config::config::DeviceConfig;
+use device_config::store::ConfigStore;
+use sensor_http::dns;
+use sensor_http::portal::{Portal, ScannedNetwork};
+use sensor_http::server;
+
+use crate::config_storage::NvsStorage;
+
+///The setup network is this plus the device id, so boards on one bench can be told apart
+const AP_SSID_PREFIX: &str = "sensor-setup-";
+
+///ESP-IDF's default SoftAP address
+const AP_ADDRESS: [u8; 4] = [192, 168, 71, 1];
+
+///Where a request to rerun the portal is kept across the restart
+const REQUEST_NAMESPACE: &str = "setup";
+const REQUEST_KEY: &str = "portal";
+
+///A board that is already provisioned goes back to its stored config after this long in the
+///portal, so a network that was only down for a while is tried again
+const PROVISIONED_TIMEOUT_MS: u32 = 10 * 60 * 1000;
+
+///Asks for the portal at the next boot, e.g. when none of the stored networks can be joined
+pub fn request(nvs: &EspDefaultNvsPartition) -> Result<(), EspError> {
+    EspNvs::new(nvs.clone(), REQUEST_NAMESPACE, true)?.set_u8(REQUEST_KEY, 1)
+}
+
+///Returns and clears a request made by `request`
+pub fn take_request(nvs: &EspDefaultNvsPartition) -> bool {
+    let Ok(mut flag) = EspNvs::new(nvs.clone(), REQUEST_NAMESPACE, true) else {
+        return false;
+    };
+
+    let requested = matches!(flag.get_u8(REQUEST_KEY), Ok(Some(_)));
+
+    if requested {
+        let _ = flag.remove(REQUEST_KEY);
+    }
+
+    requested
+}
+
+///Runs the setup portal until a config is saved, then restarts into it.  The board hosts an open
+///network with DNS pointing every name at itself, so joining it brings up the setup form, filled
+///in from `config`.
+pub fn run(
+    modem: Modem,
+    sys_loop: &EspSystemEventLoop,
+    nvs: &EspDefaultNvsPartition,
+    store: ConfigStore<NvsStorage>,
+    config: DeviceConfig,
+) -> ! {
+    let mut esp_wifi = EspWifi::new(modem, sys_loop.clone(), Some(nvs.clone())).unwrap();
+    let mut wifi = BlockingWifi::wrap(&mut esp_wifi, sys_loop.clone()).unwrap();
+
+    let ssid: String = format!("{}{}", AP_SSID_PREFIX, config.device_id)
+        .chars()
+        .take(32)
+        .collect();
+
+    // Mixed, so the station side can scan while the access point is up
+    wifi.set_configuration(&Configuration::Mixed(
+        ClientConfiguration::default(),
+        AccessPointConfiguration {
+            ssid: ssid.as_str().try_into().unwrap(),
+            auth_method: AuthMethod::None,
+            channel: 1,
+            ..Default::default()
+        },
+    ))
+    .unwrap();
+
+    wifi.start().unwrap();
+
+    let networks = match wifi.scan() {
+        Ok(found) => found
+            .into_iter()
+            .map(|ap| ScannedNetwork {
+                ssid: ap.ssid.to_string(),
+                rssi: ap.signal_strength,
+            })
+            .collect(),
+        Err(error) => {
+            warn!("Scan failed: {:?}", error);
+            Vec::new()
+        }
+    };
+
+    info!("Setup network {} up, {} networks found", ssid, networks.len());
+
+    let provisioned = config.is_provisioned();
+
+    let mut portal = Portal::new(store, config);
+    portal.set_networks(networks);
+
+    thread::Builder::new()
+        .stack_size(4096)
+        .spawn(dns_responder)
+        .unwrap();
+
+    if provisioned {
+        thread::Builder::new()
+            .stack_size(2048)
+            .spawn(|| {
+                FreeRtos::delay_ms(PROVISIONED_TIMEOUT_MS);
+
+                info!("Setup not completed, restarting with the stored config");
+
+                reset::restart();
+            })
+            .unwrap();
+    }
+
+    let listener = TcpListener::bind("0.0.0.0:80").unwrap();
+
+    server::serve(
+        &listener,
+        &mut portal,
+        |portal| portal.is_saved(),
+        |error| warn!("Setup request failed: {:?}", error),
+    );
+
+    info!("Provisioned, restarting");
+
+    // Give the browser time to read the response
+    FreeRtos::delay_ms(1000);
+
+    reset::restart();
+}
+
+fn dns_responder() {
+    let socket = UdpSocket::bind(("0.0.0.0", dns::PORT)).unwrap();
+
+    let mut buffer = [0; 512];
+
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, peer)) => {
+                if let Some(response) = dns::answer(&buffer[..len], AP_ADDRESS) {
+                    let _ = socket.send_to(&response, peer);
+                }
+            }
+            Err(error) => warn!("DNS receive failed: {:?}", error),
+        }
+    }
+}
@@ -1,10 +0,0 @@
-pub struct WifiConfiguration {
-    pub ssid: str,
-    pub psk: str,
-}
-
-pub const WIFI_CONFIGURATION : WifiConfiguration =
-    WifiConfiguration {
-        ssid: "Oblivion",
-        psk: "t4unjath0mson",
-    };
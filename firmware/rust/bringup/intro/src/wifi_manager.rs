@@ -1,32 +1,176 @@
-use anyhow::Error;
-use embedded_svc;
-use esp_idf_svc::{self, wifi::{BlockingWifi, EspWifi}};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use embedded_svc::{wifi::ClientConfiguration, wifi::Configuration};
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::hal::reset;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{self, esp, EspError};
+use esp_idf_svc::wifi::{AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi, WifiEvent};
+use log::*;
 
-pub fn wifi_create(
-    sys_loop: &EspSystemEventLoop,
-    nvs: &EspDefaultNvsPartition,
-) -> Result<EspWifi<'static>, EspError> {
-    let peripherals = Peripherals::take()?;
+use device_config::config::Network;
+use sensor_wifi::manager::{Manager, ManagerConfig, Update};
+use sensor_wifi::wifi::{AccessPoint, Wifi};
+
+use crate::provisioning;
+
+///Consecutive failed rounds of joining before the setup portal is brought back, about 10 minutes
+///with the default backoff
+const PORTAL_AFTER_FAILURES: u32 = 10;
+
+///The access point the station is connected to, with the RSSI of the last check.  None while
+///disconnected.
+pub type Link = Arc<Mutex<Option<AccessPoint>>>;
+
+///`Wifi` over the ESP-IDF station
+pub struct EspStation(pub BlockingWifi<EspWifi<'static>>);
+
+impl Wifi for EspStation {
+    type Error = EspError;
+
+    fn scan(&mut self) -> Result<Vec<AccessPoint>, Self::Error> {
+        Ok(self
+            .0
+            .scan()?
+            .into_iter()
+            .map(|ap| AccessPoint {
+                ssid: ap.ssid.to_string(),
+                bssid: ap.bssid,
+                channel: ap.channel,
+                rssi: ap.signal_strength,
+            })
+            .collect())
+    }
 
-    let mut esp_wifi = EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs.clone()))?;
-    let mut wifi = BlockingWifi::wrap(&mut esp_wifi, sys_loop.clone())?;
+    fn connect(&mut self, ap: &AccessPoint, psk: &str) -> Result<(), Self::Error> {
+        let invalid = |_| EspError::from_infallible::<{ sys::ESP_ERR_INVALID_ARG }>();
+
+        self.0.set_configuration(&Configuration::Client(ClientConfiguration {
+            ssid: ap.ssid.as_str().try_into().map_err(invalid)?,
+            bssid: Some(ap.bssid),
+            channel: Some(ap.channel),
+            password: psk.try_into().map_err(invalid)?,
+            auth_method: if psk.is_empty() { AuthMethod::None } else { AuthMethod::WPA2Personal },
+            ..Default::default()
+        }))?;
+
+        self.0.connect()?;
+        self.0.wait_netif_up()
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        self.0.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.0.is_connected().unwrap_or(false)
+    }
+
+    fn rssi(&mut self) -> Option<i8> {
+        let mut record = sys::wifi_ap_record_t::default();
+
+        // Only reads the station's current AP record
+        esp!(unsafe { sys::esp_wifi_sta_get_ap_info(&mut record) }).ok()?;
+
+        Some(record.rssi)
+    }
+}
 
-    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
-        ssid: wifi_config::WIFI_CONFIGURATION.SSID.try_into().unwrap(),
-        password: wifi_config::WIFI_CONFIGURATION.psk.try_into().unwrap(),
-        ..Default::default()
-    }))?;
+///Starts the station and a thread keeping it connected to the best of `networks` (most preferred
+///first), roaming between access points and reconnecting when the link drops.  If none can be
+///joined for `PORTAL_AFTER_FAILURES` rounds, e.g. a mistyped PSK, the board restarts into the
+///setup portal.
+pub fn wifi_start(
+    modem: Modem,
+    sys_loop: &EspSystemEventLoop,
+    nvs: &EspDefaultNvsPartition,
+    networks: Vec<Network>,
+) -> Result<Link, EspError> {
+    let mut wifi = BlockingWifi::wrap(
+        EspWifi::new(modem, sys_loop.clone(), Some(nvs.clone()))?,
+        sys_loop.clone(),
+    )?;
 
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))?;
     wifi.start()?;
     info!("Wifi started");
 
-    wifi.connect()?;
-    info!("Wifi connected");
+    let (disconnects_tx, disconnects) = mpsc::channel();
+
+    let subscription = sys_loop.subscribe::<WifiEvent, _>(move |event| {
+        if matches!(event, WifiEvent::StaDisconnected { .. }) {
+            let _ = disconnects_tx.send(());
+        }
+    })?;
+
+    let link = Link::default();
+    let thread_link = link.clone();
+    let nvs = nvs.clone();
+
+    std::thread::Builder::new()
+        .stack_size(8192)
+        .spawn(move || {
+            // Unsubscribes when dropped
+            let _subscription = subscription;
+
+            let mut station = EspStation(wifi);
+            let mut manager = Manager::new(networks, ManagerConfig::default());
+
+            let started = Instant::now();
+            let now_ms = || started.elapsed().as_millis() as u64;
+
+            loop {
+                let update = manager.poll(&mut station, now_ms());
+
+                if let Update::Failed { failures, .. } = update {
+                    if failures >= PORTAL_AFTER_FAILURES {
+                        warn!("Wifi: giving up after {} tries, restarting into setup", failures);
+
+                        match provisioning::request(&nvs) {
+                            Ok(()) => reset::restart(),
+                            Err(error) => warn!("Setup request failed: {:?}", error),
+                        }
+                    }
+                }
+
+                report(&manager, update, &thread_link);
+
+                let wait = Duration::from_millis(manager.wait_ms(now_ms()).max(10));
+
+                if disconnects.recv_timeout(wait).is_ok() {
+                    let update = manager.disconnected(&mut station, now_ms());
+                    report(&manager, update, &thread_link);
+                }
+            }
+        })
+        .unwrap();
+
+    Ok(link)
+}
+
+fn report(manager: &Manager, update: Update, link: &Link) {
+    *link.lock().unwrap() = manager.connection().cloned();
 
-    wifi.wait_netif_up()?;
-    info!("Wifi netif up");
+    match update {
+        Update::None => {}
+        Update::Connected { network, roamed } => {
+            let ap = manager.connection().unwrap();
 
-    Ok(esp_wifi)
+            info!(
+                "Wifi {} {} ({:02x?}, channel {}), {} dBm",
+                if roamed { "roamed on" } else { "connected to" },
+                manager.networks()[network].ssid,
+                ap.bssid,
+                ap.channel,
+                ap.rssi
+            );
+        }
+        Update::Failed { failures, retry_in_ms } => {
+            warn!("Wifi: no known network joined after {} tries, retrying in {} ms", failures, retry_in_ms)
+        }
+        Update::Lost => warn!("Wifi link lost, reconnecting"),
+        Update::Rssi(rssi) => info!("Wifi RSSI {} dBm", rssi),
+    }
 }
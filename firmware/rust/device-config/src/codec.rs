@@ -0,0 +1,399 @@
+//! Binary layout of a stored `DeviceConfig`:
+//!
+//! `"DC"`, version (u16), payload length (u16), payload, CRC-16/CCITT of the payload (u16)
+//!
+//! Integers are little endian and strings are a u8 length followed by UTF-8.  Older versions are
+//! migrated to the current one as they are decoded, filling in defaults for fields they lack.
+//! Tests exercise that path with a version 0 that only they can decode.
+//!
+//! | Version | Payload                                                                      |
+//! |---------|------------------------------------------------------------------------------|
+//! | 1       | device id, networks (u8 count, ssid, psk), mqtt url, user, password, client id, mqtt prefix, sampling intervals (3 x u32), shunt cal (u16), load cell (u8 present, i32 offset, f32 counts per gram) |
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::config::{
+    CalibrationConfig, DeviceConfig, LoadCell, MqttConfig, Network, NetworkConfig, SamplingConfig, MAX_NETWORKS,
+};
+
+pub const MAGIC: [u8; 2] = *b"DC";
+
+pub const CURRENT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CodecError {
+    ///Wrong magic, short data or a CRC mismatch
+    Corrupt,
+    ///Written by newer firmware
+    UnsupportedVersion(u16),
+    ///A string is longer than 255 bytes, so can't be encoded
+    TooLong,
+    ///More than `MAX_NETWORKS` networks
+    TooManyNetworks,
+}
+
+pub fn encode(config: &DeviceConfig) -> Result<Vec<u8>, CodecError> {
+    let mut payload = Writer(Vec::new());
+
+    payload.string(&config.device_id)?;
+
+    let networks = &config.network.networks;
+
+    if networks.len() > MAX_NETWORKS {
+        return Err(CodecError::TooManyNetworks);
+    }
+
+    payload.u8(networks.len() as u8);
+
+    for network in networks {
+        payload.string(&network.ssid)?;
+        payload.string(&network.psk)?;
+    }
+
+    payload.string(&config.mqtt.url)?;
+    payload.string(&config.mqtt.user)?;
+    payload.string(&config.mqtt.password)?;
+    payload.string(&config.mqtt.client_id)?;
+    payload.string(&config.mqtt.prefix)?;
+    payload.u32(config.sampling.power_interval_ms);
+    payload.u32(config.sampling.load_interval_ms);
+    payload.u32(config.sampling.climate_interval_ms);
+    payload.u16(config.calibration.ina237_shunt_cal);
+
+    match config.calibration.load_cell {
+        Some(load_cell) => {
+            payload.u8(1);
+            payload.u32(load_cell.zero_offset as u32);
+            payload.u32(load_cell.counts_per_gram.to_bits());
+        }
+        None => {
+            payload.u8(0);
+            payload.u32(0);
+            payload.u32(0);
+        }
+    }
+
+    Ok(frame(CURRENT_VERSION, &payload.0))
+}
+
+///Decodes any supported version.  Returns the config and the version it was stored as.
+pub fn decode(data: &[u8]) -> Result<(DeviceConfig, u16), CodecError> {
+    let (version, payload) = unframe(data)?;
+
+    let config = match version {
+        #[cfg(test)]
+        0 => tests::decode_v0(&mut Reader(payload)),
+        1 => decode_v1(&mut Reader(payload)),
+        _ => return Err(CodecError::UnsupportedVersion(version)),
+    }
+    .ok_or(CodecError::Corrupt)?;
+
+    Ok((config, version))
+}
+
+pub fn frame(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len() + 2);
+
+    data.extend_from_slice(&MAGIC);
+    data.extend_from_slice(&version.to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&crc16(payload).to_le_bytes());
+
+    data
+}
+
+fn unframe(data: &[u8]) -> Result<(u16, &[u8]), CodecError> {
+    if data.len() < HEADER_LEN + 2 || data[0..2] != MAGIC {
+        return Err(CodecError::Corrupt);
+    }
+
+    let version = u16::from_le_bytes([data[2], data[3]]);
+    let len = u16::from_le_bytes([data[4], data[5]]) as usize;
+
+    let payload = data.get(HEADER_LEN..HEADER_LEN + len).ok_or(CodecError::Corrupt)?;
+    let crc = data
+        .get(HEADER_LEN + len..HEADER_LEN + len + 2)
+        .ok_or(CodecError::Corrupt)?;
+
+    if crc16(payload).to_le_bytes() != crc {
+        return Err(CodecError::Corrupt);
+    }
+
+    Ok((version, payload))
+}
+
+fn decode_v1(reader: &mut Reader) -> Option<DeviceConfig> {
+    let device_id = reader.string()?;
+
+    let count = reader.u8()? as usize;
+    let mut networks = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        networks.push(Network {
+            ssid: reader.string()?,
+            psk: reader.string()?,
+        });
+    }
+
+    let mqtt = MqttConfig {
+        url: reader.string()?,
+        user: reader.string()?,
+        password: reader.string()?,
+        client_id: reader.string()?,
+        prefix: reader.string()?,
+    };
+
+    let sampling = SamplingConfig {
+        power_interval_ms: reader.u32()?,
+        load_interval_ms: reader.u32()?,
+        climate_interval_ms: reader.u32()?,
+    };
+
+    let ina237_shunt_cal = reader.u16()?;
+    let has_load_cell = reader.u8()? != 0;
+    let zero_offset = reader.u32()? as i32;
+    let counts_per_gram = f32::from_bits(reader.u32()?);
+
+    let calibration = CalibrationConfig {
+        ina237_shunt_cal,
+        load_cell: has_load_cell.then_some(LoadCell {
+            zero_offset,
+            counts_per_gram,
+        }),
+    };
+
+    Some(DeviceConfig {
+        device_id,
+        network: NetworkConfig { networks },
+        mqtt,
+        sampling,
+        calibration,
+    })
+}
+
+///CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) -> Result<(), CodecError> {
+        let len = u8::try_from(value.len()).map_err(|_| CodecError::TooLong)?;
+
+        self.0.push(len);
+        self.0.extend_from_slice(value.as_bytes());
+
+        Ok(())
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u8()? as usize;
+
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///A made-up older layout, the first fields of version 1, so migration stays tested before
+    ///there is a real one
+    pub(super) fn decode_v0(reader: &mut Reader) -> Option<DeviceConfig> {
+        let device_id = reader.string()?;
+
+        let count = reader.u8()? as usize;
+        let mut networks = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            networks.push(Network {
+                ssid: reader.string()?,
+                psk: reader.string()?,
+            });
+        }
+
+        let mqtt = MqttConfig {
+            url: reader.string()?,
+            user: reader.string()?,
+            password: reader.string()?,
+            client_id: reader.string()?,
+            ..MqttConfig::default()
+        };
+
+        Some(DeviceConfig {
+            device_id,
+            network: NetworkConfig { networks },
+            mqtt,
+            ..DeviceConfig::default()
+        })
+    }
+
+    fn config() -> DeviceConfig {
+        DeviceConfig {
+            device_id: String::from("greenhouse-1"),
+            network: NetworkConfig {
+                networks: vec![
+                    Network {
+                        ssid: String::from("Shed"),
+                        psk: String::from("hunter22"),
+                    },
+                    Network {
+                        ssid: String::from("House"),
+                        psk: String::new(),
+                    },
+                ],
+            },
+            mqtt: MqttConfig {
+                url: String::from("mqtt://broker.local:1883"),
+                user: String::from("sensor"),
+                password: String::from("secret"),
+                client_id: String::new(),
+                prefix: String::from("home"),
+            },
+            sampling: SamplingConfig {
+                power_interval_ms: 250,
+                load_interval_ms: 1_000,
+                climate_interval_ms: 30_000,
+            },
+            calibration: CalibrationConfig {
+                ina237_shunt_cal: 3_900,
+                load_cell: Some(LoadCell {
+                    zero_offset: -1_234,
+                    counts_per_gram: 420.5,
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(0x29B1, crc16(b"123456789"));
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let config = config();
+
+        let data = encode(&config).unwrap();
+
+        assert_eq!(b"DC\x01\x00", &data[0..4]);
+        assert_eq!(Ok((config, CURRENT_VERSION)), decode(&data));
+    }
+
+    #[test]
+    fn older_versions_migrate_with_defaults() {
+        let mut payload = Vec::new();
+
+        payload.extend_from_slice(b"\x05esp12");
+        payload.extend_from_slice(b"\x01\x04Shed\x08hunter22");
+        payload.extend_from_slice(b"\x18mqtt://broker.local:1883\x06sensor\x00\x00");
+
+        let (migrated, version) = decode(&frame(0, &payload)).unwrap();
+
+        assert_eq!(0, version);
+        assert_eq!("esp12", migrated.device_id);
+        assert_eq!("Shed", migrated.network.networks[0].ssid);
+        assert_eq!("mqtt://broker.local:1883", migrated.mqtt.url);
+        assert_eq!("sensor", migrated.mqtt.prefix);
+        assert_eq!(SamplingConfig::default(), migrated.sampling);
+        assert_eq!(CalibrationConfig::default(), migrated.calibration);
+    }
+
+    #[test]
+    fn damage_is_detected() {
+        let mut data = encode(&config()).unwrap();
+
+        let last = data.len() - 3;
+        data[last] ^= 0x01;
+
+        assert_eq!(Err(CodecError::Corrupt), decode(&data));
+        assert_eq!(Err(CodecError::Corrupt), decode(&data[..5]));
+        assert_eq!(Err(CodecError::Corrupt), decode(b"XX\x01\x00\x00\x00\xFF\xFF"));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        assert_eq!(Err(CodecError::UnsupportedVersion(2)), decode(&frame(2, &[])));
+    }
+
+    #[test]
+    fn long_strings_are_rejected() {
+        let mut config = config();
+
+        config.mqtt.url = "x".repeat(256);
+
+        assert_eq!(Err(CodecError::TooLong), encode(&config));
+    }
+
+    #[test]
+    fn too_many_networks_are_rejected() {
+        let mut config = config();
+
+        config.network.networks = vec![config.network.networks[0].clone(); MAX_NETWORKS];
+
+        assert!(encode(&config).is_ok());
+
+        config.network.networks.push(config.network.networks[0].clone());
+
+        assert_eq!(Err(CodecError::TooManyNetworks), encode(&config));
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+///Most networks kept in `NetworkConfig`
+pub const MAX_NETWORKS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    pub ssid: String,
+    pub psk: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct NetworkConfig {
+    ///Known networks, most preferred first
+    pub networks: Vec<Network>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttConfig {
+    ///Broker, e.g. `mqtt://broker.local:1883`.  Empty until provisioned.
+    pub url: String,
+    pub user: String,
+    pub password: String,
+    ///Empty to use the device id
+    pub client_id: String,
+    ///Topics go under `<prefix>/<device id>`
+    pub prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            user: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            prefix: String::from("sensor"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplingConfig {
+    ///INA237
+    pub power_interval_ms: u32,
+    ///NAU7802
+    pub load_interval_ms: u32,
+    ///SHT40
+    pub climate_interval_ms: u32,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            power_interval_ms: 100,
+            load_interval_ms: 100,
+            climate_interval_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoadCell {
+    ///Counts with nothing on the load cell
+    pub zero_offset: i32,
+    pub counts_per_gram: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CalibrationConfig {
+    ///INA237 SHUNT_CAL register value
+    pub ina237_shunt_cal: u16,
+    ///None until the load cell has been calibrated
+    pub load_cell: Option<LoadCell>,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            ina237_shunt_cal: 4000,
+            load_cell: None,
+        }
+    }
+}
+
+///Everything about a board that can change without reflashing
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceConfig {
+    ///Names the board in topics and discovery
+    pub device_id: String,
+    pub network: NetworkConfig,
+    pub mqtt: MqttConfig,
+    pub sampling: SamplingConfig,
+    pub calibration: CalibrationConfig,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            device_id: String::from("esp32-sensor"),
+            network: NetworkConfig::default(),
+            mqtt: MqttConfig::default(),
+            sampling: SamplingConfig::default(),
+            calibration: CalibrationConfig::default(),
+        }
+    }
+}
+
+impl DeviceConfig {
+    ///MQTT client id, defaulting to the device id
+    pub fn client_id(&self) -> &str {
+        if self.mqtt.client_id.is_empty() {
+            &self.device_id
+        } else {
+            &self.mqtt.client_id
+        }
+    }
+
+    ///Has at least one network and a broker
+    pub fn is_provisioned(&self) -> bool {
+        !self.network.networks.is_empty() && !self.mqtt.url.is_empty()
+    }
+}
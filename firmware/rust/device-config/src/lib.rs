@@ -0,0 +1,9 @@
+#![deny(unsafe_code)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod codec;
+pub mod config;
+pub mod storage;
+pub mod store;
@@ -0,0 +1,50 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+///Key/value blob storage, e.g. an NVS namespace on the device
+pub trait Storage {
+    type Error: core::fmt::Debug;
+
+    fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Self::Error>;
+
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error>;
+}
+
+///`Storage` in RAM, for host tests and tools
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries.get(key).map(Vec::as_slice)
+    }
+}
+
+impl Storage for MemoryStorage {
+    type Error = core::convert::Infallible;
+
+    fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Self::Error> {
+        self.entries.insert(key.to_string(), data.to_vec());
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error> {
+        self.entries.remove(key);
+
+        Ok(())
+    }
+}
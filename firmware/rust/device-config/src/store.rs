@@ -0,0 +1,203 @@
+use crate::codec::{self, CodecError, CURRENT_VERSION};
+use crate::config::DeviceConfig;
+use crate::storage::Storage;
+
+///Storage key the config is kept under
+pub const KEY: &str = "device";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    ///Read as stored
+    Stored,
+    ///Read from an older version.  `saved` is false if writing it back as the current one failed,
+    ///in which case it is migrated again on the next load.
+    Migrated { from: u16, saved: bool },
+    ///Nothing stored yet
+    Default,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error<E> {
+    Storage(E),
+    Codec(CodecError),
+}
+
+impl<E> From<CodecError> for Error<E> {
+    fn from(value: CodecError) -> Self {
+        Error::Codec(value)
+    }
+}
+
+///Loads and saves a `DeviceConfig` in any `Storage`
+pub struct ConfigStore<S> {
+    storage: S,
+}
+
+impl<S> ConfigStore<S>
+where
+    S: Storage,
+{
+    pub fn new(storage: S) -> ConfigStore<S> {
+        ConfigStore { storage }
+    }
+
+    pub fn storage(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    pub fn destroy(self) -> S {
+        self.storage
+    }
+
+    ///Reads the stored config, migrating and rewriting it if it was saved by older firmware.  A
+    ///failed rewrite doesn't fail the load.
+    pub fn load(&mut self) -> Result<(DeviceConfig, Source), Error<S::Error>> {
+        let Some(data) = self.storage.read(KEY).map_err(Error::Storage)? else {
+            return Ok((DeviceConfig::default(), Source::Default));
+        };
+
+        let (config, version) = codec::decode(&data)?;
+
+        if version == CURRENT_VERSION {
+            return Ok((config, Source::Stored));
+        }
+
+        let saved = self.save(&config).is_ok();
+
+        Ok((config, Source::Migrated { from: version, saved }))
+    }
+
+    ///As `load`, but falls back to defaults when the stored config can't be read.  The damaged
+    ///copy is left alone until the next `save`.
+    pub fn load_or_default(&mut self) -> (DeviceConfig, Result<Source, Error<S::Error>>) {
+        match self.load() {
+            Ok((config, source)) => (config, Ok(source)),
+            Err(error) => (DeviceConfig::default(), Err(error)),
+        }
+    }
+
+    pub fn save(&mut self, config: &DeviceConfig) -> Result<(), Error<S::Error>> {
+        let data = codec::encode(config)?;
+
+        self.storage.write(KEY, &data).map_err(Error::Storage)
+    }
+
+    ///Forgets the stored config, so the next `load` returns defaults
+    pub fn reset(&mut self) -> Result<(), Error<S::Error>> {
+        self.storage.remove(KEY).map_err(Error::Storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::frame;
+    use crate::config::{LoadCell, Network};
+    use crate::storage::MemoryStorage;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn empty_storage_loads_defaults() {
+        let mut under_test = ConfigStore::new(MemoryStorage::new());
+
+        assert_eq!(Ok((DeviceConfig::default(), Source::Default)), under_test.load());
+        assert!(!DeviceConfig::default().is_provisioned());
+    }
+
+    #[test]
+    fn saved_config_loads_back() {
+        let mut under_test = ConfigStore::new(MemoryStorage::new());
+
+        let mut config = DeviceConfig::default();
+
+        config.network.networks.push(Network {
+            ssid: String::from("Shed"),
+            psk: String::from("hunter22"),
+        });
+        config.mqtt.url = String::from("mqtt://broker.local");
+        config.calibration.load_cell = Some(LoadCell {
+            zero_offset: 10,
+            counts_per_gram: 2.5,
+        });
+
+        under_test.save(&config).unwrap();
+
+        assert!(config.is_provisioned());
+        assert_eq!(Ok((config, Source::Stored)), under_test.load());
+    }
+
+    #[test]
+    fn old_version_is_migrated_and_rewritten() {
+        let mut storage = MemoryStorage::new();
+
+        storage
+            .write(KEY, &frame(0, b"\x03abc\x00\x00\x00\x00\x00"))
+            .unwrap();
+
+        let mut under_test = ConfigStore::new(storage);
+
+        let (config, source) = under_test.load().unwrap();
+
+        assert_eq!(Source::Migrated { from: 0, saved: true }, source);
+        assert_eq!("abc", config.device_id);
+        assert_eq!("abc", config.client_id());
+
+        assert_eq!(Ok((config, Source::Stored)), under_test.load());
+        assert_eq!(&b"DC\x01\x00"[..], &under_test.storage().get(KEY).unwrap()[0..4]);
+    }
+
+    ///Reads from `MemoryStorage` but refuses writes
+    struct ReadOnly(MemoryStorage);
+
+    impl Storage for ReadOnly {
+        type Error = ();
+
+        fn read(&mut self, key: &str) -> Result<Option<Vec<u8>>, ()> {
+            Ok(self.0.read(key).unwrap())
+        }
+
+        fn write(&mut self, _key: &str, _data: &[u8]) -> Result<(), ()> {
+            Err(())
+        }
+
+        fn remove(&mut self, _key: &str) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn migrated_config_is_kept_when_rewrite_fails() {
+        let mut storage = MemoryStorage::new();
+
+        storage
+            .write(KEY, &frame(0, b"\x03abc\x00\x00\x00\x00\x00"))
+            .unwrap();
+
+        let mut under_test = ConfigStore::new(ReadOnly(storage));
+
+        let (config, result) = under_test.load_or_default();
+
+        assert_eq!(Ok(Source::Migrated { from: 0, saved: false }), result);
+        assert_eq!("abc", config.device_id);
+        assert_eq!(&b"DC\x00\x00"[..], &under_test.storage().0.get(KEY).unwrap()[0..4]);
+    }
+
+    #[test]
+    fn damaged_config_falls_back_to_defaults() {
+        let mut storage = MemoryStorage::new();
+
+        storage.write(KEY, b"DC\x01\x00\x10\x00garbage").unwrap();
+
+        let mut under_test = ConfigStore::new(storage);
+
+        let (config, result) = under_test.load_or_default();
+
+        assert_eq!(DeviceConfig::default(), config);
+        assert_eq!(Err(Error::Codec(CodecError::Corrupt)), result);
+
+        under_test.reset().unwrap();
+
+        assert_eq!(Ok(Source::Default), under_test.load_or_default().1);
+    }
+}
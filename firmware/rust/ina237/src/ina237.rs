@@ -1,18 +1,32 @@
 extern crate embedded_hal;
 
+use core::ops::RangeInclusive;
+
 use embedded_hal::i2c;
 
 use crate::types::Configuration;
 use crate::types::ConfigurationRegisterValues;
 use crate::types::Measurement;
 
+///Addresses selectable with the A0 / A1 pins
+pub const ADDRESSES: RangeInclusive<u8> = 0x40..=0x4F;
+
+///MANUFACTURER_ID register value, "TI"
+pub const MANUFACTURER_ID: u16 = 0x5449;
+
 pub struct Ina237<I2C> {
     i2c: I2C,
     configuration: Configuration,
 }
 
-pub struct Error {
-
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error<E>
+where
+    E: i2c::Error,
+{
+    /// Failed I2C communication.
+    I2C(E),
 }
 
 #[allow(dead_code)]
@@ -26,10 +40,10 @@ enum Registers {
     Current = 0x07,
     Power = 0x08,
     DiagAlert = 0x09,
-    SOVL = 0x0C,
-    SUVL = 0x0D,
-    BOVL = 0x0E,
-    BUVOL = 0x0F,
+    Sovl = 0x0C,
+    Suvl = 0x0D,
+    Bovl = 0x0E,
+    Buvol = 0x0F,
     TempLimit = 0x10,
     PowerLimit = 0x11,
     ManufacturerId = 0x3E,
@@ -42,8 +56,8 @@ where
 {
     pub fn new(i2c: I2C, configuration: Configuration) -> Ina237<I2C> {
         Ina237 {
-            i2c: i2c,
-            configuration: configuration,
+            i2c,
+            configuration,
         }
     }
 
@@ -51,71 +65,65 @@ where
         self.i2c
     }
 
-    fn write_register(&mut self, register: Registers, data: &[u8; 2]) {
+    fn write_register(&mut self, register: Registers, data: &[u8; 2]) -> Result<(), E> {
         let buffer: [u8; 3] = [register as u8, data[0], data[1]];
 
-        self.i2c.write(self.configuration.addr(), &buffer).unwrap_or_else(|error| {
-            panic!("i2c write failed: {}", error.kind());
-        });
+        self.i2c.write(self.configuration.addr(), &buffer)
     }
 
-    fn read_register(&mut self, register: Registers) -> [u8; 2] {
+    fn read_register(&mut self, register: Registers) -> Result<[u8; 2], E> {
         let write_buffer: [u8; 1] = [register as u8];
         let mut read_buffer: [u8; 2] = [0x00 ; 2];
 
-        self.i2c.write_read(self.configuration.addr(), &write_buffer, &mut read_buffer).unwrap_or_else(|error|  {
-            panic!("i2c write_read failed: {}", error.kind());
-        });
-        
-        read_buffer
+        self.i2c.write_read(self.configuration.addr(), &write_buffer, &mut read_buffer)?;
+
+        Result::Ok(read_buffer)
     }
 
-    pub fn initialize(&mut self, configuration_register_values: ConfigurationRegisterValues) {
+    fn read_u16(&mut self, register: Registers) -> Result<u16, Error<E>> {
+        let result = self.read_register(register).map_err(Error::I2C)?;
+
+        Result::Ok(u16::from_be_bytes(result))
+    }
+
+    pub fn initialize(&mut self, configuration_register_values: ConfigurationRegisterValues) -> Result<(), Error<E>> {
         let data = self.configuration.shunt().to_be_bytes();
 
-        self.write_register(Registers::ShuntCal, &data);
+        self.write_register(Registers::ShuntCal, &data).map_err(Error::I2C)?;
 
         let data = configuration_register_values.into_configuration().to_be_bytes();
 
-        self.write_register(Registers::Config, &data);
+        self.write_register(Registers::Config, &data).map_err(Error::I2C)?;
 
         let data = configuration_register_values.into_adc_configuration().to_be_bytes();
 
-        self.write_register(Registers::AdcConfig, &data);
+        self.write_register(Registers::AdcConfig, &data).map_err(Error::I2C)
     }
 
-    pub fn configuration(&mut self) -> u16 {
-        let result = self.read_register(Registers::Config);
-
-        u16::from_be_bytes(result)
+    pub fn configuration(&mut self) -> Result<u16, Error<E>> {
+        self.read_u16(Registers::Config)
     }
 
-    pub fn adc_configuration(&mut self) -> u16 {
-        let result = self.read_register(Registers::AdcConfig);
-
-        u16::from_be_bytes(result)
+    pub fn adc_configuration(&mut self) -> Result<u16, Error<E>> {
+        self.read_u16(Registers::AdcConfig)
     }
 
-    pub fn manufacturer_id(&mut self) -> u16 {
-        let result = self.read_register(Registers::ManufacturerId);
-
-        u16::from_be_bytes(result)
+    pub fn manufacturer_id(&mut self) -> Result<u16, Error<E>> {
+        self.read_u16(Registers::ManufacturerId)
     }
 
-    pub fn shunt_cal(&mut self) -> u16 {
-        let result = self.read_register(Registers::ShuntCal);
-
-        u16::from_be_bytes(result)
+    pub fn shunt_cal(&mut self) -> Result<u16, Error<E>> {
+        self.read_u16(Registers::ShuntCal)
     }
     
-    pub fn read(&mut self) -> Result<Measurement, Error> {
-        let vbus_reading = self.read_register(Registers::VBus);
+    pub fn read(&mut self) -> Result<Measurement, Error<E>> {
+        let vbus_reading = self.read_register(Registers::VBus).map_err(Error::I2C)?;
 
-        let shunt_reading = self.read_register(Registers::VShunt);
+        let shunt_reading = self.read_register(Registers::VShunt).map_err(Error::I2C)?;
 
-        let current_reading = self.read_register(Registers::Current);
+        let current_reading = self.read_register(Registers::Current).map_err(Error::I2C)?;
 
-        let dietemp_reading = self.read_register(Registers::DieTemp);
+        let dietemp_reading = self.read_register(Registers::DieTemp).map_err(Error::I2C)?;
 
         Result::Ok(Measurement::from_readings(i16::from_be_bytes(vbus_reading),
         i16::from_be_bytes(shunt_reading),
@@ -124,15 +132,30 @@ where
     }
 }
 
+///Reads the manufacturer ID at `address`, None if the device doesn't answer or isn't an INA237.
+///Works without a driver, so it is safe on an unknown part.
+pub fn probe<I2C: i2c::I2c>(i2c: &mut I2C, address: u8) -> Option<u16> {
+    let mut read_buffer = [0x00; 2];
+
+    i2c.write_read(address, &[Registers::ManufacturerId as u8], &mut read_buffer)
+        .ok()?;
+
+    let manufacturer_id = u16::from_be_bytes(read_buffer);
+
+    (manufacturer_id == MANUFACTURER_ID).then_some(manufacturer_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     extern crate embedded_hal_mock;
 
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
     use embedded_hal_mock::eh1:: {
-        i2c::{Mock as I2cMock, Transaction as I2cTransaction},
-        MockError,
+        i2c::Mock as I2cMock,
+        i2c::Transaction as I2cTransaction,
     };
+    use std::vec;
 
     // fn default_instance<I2C>(bus: I2cMock) -> Ina237<I2C>
     // where I2C: i2c::Write + i2c::Read {
@@ -157,6 +180,43 @@ mod tests {
         i2c.done()
     }
 
+    #[test]
+    fn probe_checks_manufacturer_id() {
+        let expectations = [
+            I2cTransaction::write_read(0x40, vec![0x3E], vec![0x54, 0x49]),
+            I2cTransaction::write_read(0x41, vec![0x3E], vec![0x12, 0x34]),
+            I2cTransaction::write_read(0x44, vec![0x3E], vec![0x00, 0x00])
+                .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        assert_eq!(Some(MANUFACTURER_ID), probe(&mut i2c, 0x40));
+        assert_eq!(None, probe(&mut i2c, 0x41));
+        assert_eq!(None, probe(&mut i2c, 0x44));
+
+        i2c.done()
+    }
+
+    #[test]
+    fn bus_errors_are_returned() {
+        let expectations = [
+            I2cTransaction::write(0x46, vec![0x02, 0x0F, 0xA0])
+                .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+            I2cTransaction::write_read(0x46, vec![0x05], vec![0x00, 0x00]).with_error(ErrorKind::Bus),
+        ];
+
+        let mut under_test = Ina237::new(I2cMock::new(&expectations), Configuration::new(0x46, 4000));
+
+        assert!(matches!(
+            under_test.initialize(ConfigurationRegisterValues::new()),
+            Err(Error::I2C(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)))
+        ));
+        assert!(matches!(under_test.read(), Err(Error::I2C(ErrorKind::Bus))));
+
+        under_test.destroy().done();
+    }
+
     #[test]
     fn initialize_sets_calibration_register() {
         let i2c = I2cMock::new([]);
@@ -2,5 +2,7 @@
 extern crate std;
 
 pub mod ina237;
+#[cfg(feature = "sensor")]
+pub mod sensor;
 pub mod types;
 
@@ -0,0 +1,183 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c;
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use sensor_core::scan::Device;
+use sensor_core::sensor::{Error, Sensor};
+
+use crate::ina237::{self, Ina237};
+
+const QUANTITIES: [(Quantity, Unit); 5] = [
+    (Quantity::Voltage, Unit::Millivolt),
+    (Quantity::ShuntVoltage, Unit::Microvolt),
+    (Quantity::Current, Unit::Microamp),
+    (Quantity::Power, Unit::Microwatt),
+    (Quantity::Temperature, Unit::MilliCelsius),
+];
+
+///`Sensor` adapter for an INA237.  Each read produces bus voltage, shunt voltage, current, power
+///and die temperature.
+pub struct Ina237Sensor<I2C> {
+    driver: Ina237<I2C>,
+    channel: ChannelId,
+}
+
+impl<I2C, E> Ina237Sensor<I2C>
+where
+    I2C: i2c::I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(driver: Ina237<I2C>, instance: &'static str) -> Ina237Sensor<I2C> {
+        Ina237Sensor {
+            driver,
+            channel: ChannelId::new("ina237", instance),
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut Ina237<I2C> {
+        &mut self.driver
+    }
+
+    pub fn destroy(self) -> Ina237<I2C> {
+        self.driver
+    }
+}
+
+impl<E> From<ina237::Error<E>> for Error
+where
+    E: i2c::Error,
+{
+    fn from(value: ina237::Error<E>) -> Self {
+        match value {
+            ina237::Error::I2C(error) => Error::I2C(error.kind()),
+        }
+    }
+}
+
+impl<I2C, E> Sensor for Ina237Sensor<I2C>
+where
+    I2C: i2c::I2c<Error = E>,
+    E: i2c::Error,
+{
+    fn channel(&self) -> ChannelId {
+        self.channel
+    }
+
+    fn quantities(&self) -> &'static [(Quantity, Unit)] {
+        &QUANTITIES
+    }
+
+    fn read(
+        &mut self,
+        timestamp_ms: u64,
+        _delay: &mut dyn DelayNs,
+        sink: &mut dyn FnMut(Reading),
+    ) -> Result<(), Error> {
+        let measurement = self.driver.read()?;
+
+        //mV * uA / 1000 = uW
+        let power_uw = i64::from(measurement.voltage_mv()) * i64::from(measurement.current_ua()) / 1_000;
+
+        let values = [
+            measurement.voltage_mv(),
+            measurement.shunt_uv(),
+            measurement.current_ua(),
+            power_uw.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32,
+            measurement.temp_mc(),
+        ];
+
+        for ((quantity, unit), value) in QUANTITIES.into_iter().zip(values) {
+            sink(Reading::new(self.channel, quantity, unit, value, timestamp_ms));
+        }
+
+        Result::Ok(())
+    }
+}
+
+///`sensor_core::scan::Identify` for the INA237, by its manufacturer ID
+pub fn identify<I2C, D>(i2c: &mut I2C, _delay: &mut D, address: u8) -> Option<Device>
+where
+    I2C: i2c::I2c,
+    D: DelayNs,
+{
+    if !ina237::ADDRESSES.contains(&address) {
+        return None;
+    }
+
+    ina237::probe(i2c, address).map(|manufacturer_id| Device::Ina237 { manufacturer_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate embedded_hal_mock;
+
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use std::vec;
+    use std::vec::Vec;
+
+    use crate::types::Configuration;
+
+    #[test]
+    fn read_produces_all_quantities() {
+        let expectations = [
+            I2cTransaction::write_read(0x46, vec![0x05], vec![0x01, 0x00]),
+            I2cTransaction::write_read(0x46, vec![0x04], vec![0x00, 0x08]),
+            I2cTransaction::write_read(0x46, vec![0x07], vec![0x00, 0x0A]),
+            I2cTransaction::write_read(0x46, vec![0x06], vec![0x00, 0x80]),
+        ];
+
+        let driver = Ina237::new(I2cMock::new(&expectations), Configuration::new(0x46, 4000));
+
+        let mut under_test = Ina237Sensor::new(driver, "a");
+
+        let mut readings = Vec::new();
+
+        under_test
+            .read(42, &mut NoopDelay::new(), &mut |reading| readings.push(reading))
+            .unwrap();
+
+        assert_eq!(5, readings.len());
+        assert_eq!((Quantity::Voltage, Unit::Millivolt, 800), (readings[0].quantity, readings[0].unit, readings[0].value));
+        assert_eq!((Quantity::ShuntVoltage, 10), (readings[1].quantity, readings[1].value));
+        assert_eq!((Quantity::Current, 3051), (readings[2].quantity, readings[2].value));
+        //800 mV * 3051 uA
+        assert_eq!((Quantity::Power, Unit::Microwatt, 2440), (readings[3].quantity, readings[3].unit, readings[3].value));
+        assert_eq!(Quantity::Temperature, readings[4].quantity);
+        assert!(readings.iter().all(|reading| reading.timestamp_ms == 42));
+        assert_eq!(ChannelId::new("ina237", "a"), under_test.channel());
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn bus_errors_keep_their_kind() {
+        let expectations = [I2cTransaction::write_read(0x46, vec![0x05], vec![0x00, 0x00]).with_error(ErrorKind::Bus)];
+
+        let driver = Ina237::new(I2cMock::new(&expectations), Configuration::new(0x46, 4000));
+
+        let mut under_test = Ina237Sensor::new(driver, "a");
+
+        let result = under_test.read(42, &mut NoopDelay::new(), &mut |_| panic!("no readings expected"));
+
+        assert!(matches!(result, Err(Error::I2C(ErrorKind::Bus))));
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn identify_checks_address_and_manufacturer_id() {
+        let expectations = [I2cTransaction::write_read(0x46, vec![0x3E], vec![0x54, 0x49])];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        assert_eq!(
+            Some(Device::Ina237 { manufacturer_id: ina237::MANUFACTURER_ID }),
+            identify(&mut i2c, &mut NoopDelay::new(), 0x46)
+        );
+        assert_eq!(None, identify(&mut i2c, &mut NoopDelay::new(), 0x2A));
+
+        i2c.done();
+    }
+}
@@ -139,6 +139,12 @@ pub struct ConfigurationRegisterValues {
     pub adc_averaging: AdcAveraging,
 }
 
+impl Default for ConfigurationRegisterValues {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConfigurationRegisterValues {
     pub fn new() -> ConfigurationRegisterValues {
         ConfigurationRegisterValues {
@@ -154,15 +160,13 @@ impl ConfigurationRegisterValues {
     }
 
     pub fn into_configuration(&self) -> u16 {
-        0x0000
-            | if self.reset { 0x8000 } else { 0x0000 }
+        (if self.reset { 0x8000 } else { 0x0000 })
             | (self.conversion_delay as u16 & 0x000F) << 6
             | (self.adc_range as u16) << 4
     }
 
     pub fn into_adc_configuration(&self) -> u16 {
-        0x0000
-            | (self.mode as u16 & 0x0F) << 12
+        (self.mode as u16 & 0x0F) << 12
             | (self.bus_voltage_conversion_time as u16 & 0x07) << 9
             | (self.shunt_voltage_conversion_time as u16 & 0x07) << 6
             | (self.temperature_conversion_time as u16 & 0x07) << 3
@@ -275,6 +279,6 @@ mod tests {
 
         let result: u16 = configuration_register_values.into_adc_configuration();
 
-        assert_eq!(AdcAveraging::Avg256 as u16, (result >> 0) & 0x07);
+        assert_eq!(AdcAveraging::Avg256 as u16, result & 0x07);
     }
 }
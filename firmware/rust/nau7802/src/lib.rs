@@ -1,10 +1,198 @@
 
 pub mod registers;
 pub mod nau7802;
+#[cfg(feature = "async")]
+pub mod nau7802_async;
+#[cfg(feature = "sensor")]
+pub mod sensor;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use embedded_hal_mock::eh1::delay::NoopDelay;
     use embedded_hal_mock::eh1::i2c::*;
+
+    #[test]
+    fn ctrl1_read_with_crp_clear_reports_active_high() {
+        let expectations = [
+            Transaction::write(0x2a, vec![registers::Registers::CTRL1 as u8]),
+            Transaction::read(0x2a, vec![0x2f]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let mut under_test: nau7802::Nau7802<Mock> = nau7802::Nau7802::new(i2c);
+
+        let result = under_test.ctrl1().unwrap();
+
+        assert!(result.conversion_ready_polarity_high);
+        assert_eq!(registers::LdoVoltage::v3_0, result.ldo_voltage);
+        assert_eq!(registers::Gains::x128, result.gain_select);
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
+
+    #[test]
+    fn initialize_with_unknown_revision_returns_error() {
+        let expectations = [
+            //Reset and power up
+            Transaction::write(0x2a, vec![0x00, 0x01]),
+            Transaction::write(0x2a, vec![0x00, 0x02]),
+            Transaction::write(0x2a, vec![0x00]),
+            Transaction::read(0x2a, vec![0x0A]),
+            //Revision check
+            Transaction::write(0x2a, vec![registers::Registers::DEVICE_REVISION as u8]),
+            Transaction::read(0x2a, vec![0x0E]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let mut under_test: nau7802::Nau7802<Mock> = nau7802::Nau7802::new(i2c);
+
+        let result = under_test.initialize(&mut NoopDelay::new());
+
+        assert!(matches!(
+            result,
+            Err(nau7802::Error::InitializeUnknownRevision(0x0E))
+        ));
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
+
+    #[test]
+    fn device_info_reads_revision_and_otp() {
+        let expectations = [
+            Transaction::write(0x2a, vec![registers::Registers::DEVICE_REVISION as u8]),
+            Transaction::read(0x2a, vec![0xAF]),
+            Transaction::write(0x2a, vec![registers::Registers::OTP_B1 as u8]),
+            Transaction::read(0x2a, vec![0x12]),
+            Transaction::write(0x2a, vec![registers::Registers::OTP_B0 as u8]),
+            Transaction::read(0x2a, vec![0x34]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let mut under_test: nau7802::Nau7802<Mock> = nau7802::Nau7802::new(i2c);
+
+        let result = under_test.device_info().unwrap();
+
+        assert_eq!(
+            nau7802::DeviceInfo {
+                revision_id: nau7802::REVISION_ID,
+                otp: 0x1234,
+            },
+            result
+        );
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
+
+    #[test]
+    fn set_adc_offset_writes_most_significant_byte_first() {
+        let expectations = [
+            Transaction::write(0x2a, vec![registers::Registers::OCAL2_B2 as u8, 0xFE]),
+            Transaction::write(0x2a, vec![registers::Registers::OCAL2_B1 as u8, 0xDC]),
+            Transaction::write(0x2a, vec![registers::Registers::OCAL2_B0 as u8, 0xBA]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let mut under_test: nau7802::Nau7802<Mock> = nau7802::Nau7802::new(i2c);
+
+        under_test
+            .set_adc_offset(nau7802::AdcChannel::B, -0x012346)
+            .unwrap();
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
+
+    #[test]
+    fn calibrate_with_cal_err_set_returns_error() {
+        let expectations = [
+            Transaction::write(0x2a, vec![registers::Registers::CTRL2 as u8]),
+            Transaction::read(0x2a, vec![0x00]),
+            Transaction::write(0x2a, vec![registers::Registers::CTRL2 as u8, 0x04]),
+            Transaction::write(0x2a, vec![registers::Registers::CTRL2 as u8]),
+            Transaction::read(0x2a, vec![0x08]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let mut under_test: nau7802::Nau7802<Mock> = nau7802::Nau7802::new(i2c);
+
+        let result = under_test.calibrate(&mut NoopDelay::new());
+
+        assert!(matches!(result, Err(nau7802::Error::Calibration)));
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
+
+    #[test]
+    fn builder_initializes_configures_and_calibrates() {
+        const ADDR: u8 = 0x2b;
+
+        let expectations = [
+            //Reset and power up
+            Transaction::write(ADDR, vec![0x00, 0x01]),
+            Transaction::write(ADDR, vec![0x00, 0x02]),
+            Transaction::write(ADDR, vec![0x00]),
+            Transaction::read(ADDR, vec![0x0A]),
+            //Revision check
+            Transaction::write(ADDR, vec![0x1F]),
+            Transaction::read(ADDR, vec![0x0F]),
+            Transaction::write(ADDR, vec![0x00, 0x86]),
+            Transaction::write(ADDR, vec![0x00, 0x16]),
+            //LDO voltage and enable
+            Transaction::write(ADDR, vec![0x01]),
+            Transaction::read(ADDR, vec![0x00]),
+            Transaction::write(ADDR, vec![0x01, 0x28]),
+            Transaction::write(ADDR, vec![0x00]),
+            Transaction::read(ADDR, vec![0x16]),
+            Transaction::write(ADDR, vec![0x00, 0x96]),
+            //Gain
+            Transaction::write(ADDR, vec![0x01]),
+            Transaction::read(ADDR, vec![0x28]),
+            Transaction::write(ADDR, vec![0x01, 0x2C]),
+            //Rate
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x00]),
+            Transaction::write(ADDR, vec![0x02, 0x30]),
+            //Channel A already selected
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+            //Calibrate
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+            Transaction::write(ADDR, vec![0x02, 0x34]),
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+        ];
+
+        let i2c = Mock::new(&expectations);
+
+        let under_test = nau7802::Nau7802::builder(i2c)
+            .address(ADDR)
+            .ldo(registers::LdoVoltage::v3_0)
+            .gain(registers::Gains::x16)
+            .rate(registers::ConversionRate::SPS_80)
+            .build(&mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(ADDR, under_test.address());
+
+        let mut i2c = under_test.destroy();
+
+        i2c.done()
+    }
 }
@@ -6,21 +6,20 @@ use embedded_hal::{
 
 use crate::registers::*;
 
-use core::marker::PhantomData;
+///Default I2C address.  The NAU7802 address is fixed, but a mux or translator may move it.
+pub const DEFAULT_ADDRESS: u8 = 0x2a;
 
-const I2C_ADDR: u8 = 0x2a;
+///Expected value of the DEVICE_REVISION register's revision id (low nibble)
+pub const REVISION_ID: u8 = 0x0F;
 
-pub struct Nau7802<I2C, D>
+pub struct Nau7802<I2C>
 where
     I2C: I2c,
     // DRP: InputPin,
-    D: DelayNs,
 {
     i2c: I2C,
+    address: u8,
     // data_ready_pin: Option<DRP>,
-    // If we want to globally define the delay type for this struct, we have to consume the type
-    // parameter.
-    _delay: PhantomData<D>,
 }
 
 // #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -34,36 +33,138 @@ where
     Initialize,
     InitializeUnknownRevision(u8),
     InitializeNoPowerup(u8),
+    ///Internal calibration finished with CAL_ERR set
+    Calibration,
     NoDataReadyPin,
     DataNotReady,
     /// Failed I2C communication.
     I2C(E),
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AdcChannel {
     A,
     B,
 }
 
-impl<I2C, E, D> Nau7802<I2C, D>
+///Identifies the NAU7802 silicon on a board
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    ///Revision id from DEVICE_REVISION, low nibble only
+    pub revision_id: u8,
+    ///OTP_B1:OTP_B0 as a big-endian u16
+    pub otp: u16,
+}
+
+///Settings applied by `Nau7802Builder::build` / `Nau7802::configure`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub address: u8,
+    pub ldo: LdoVoltage,
+    pub gain: Gains,
+    pub rate: ConversionRate,
+    pub channel: AdcChannel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_ADDRESS,
+            ldo: LdoVoltage::v3_3,
+            gain: Gains::x128,
+            rate: ConversionRate::SPS_10,
+            channel: AdcChannel::A,
+        }
+    }
+}
+
+pub struct Nau7802Builder<I2C> {
+    i2c: I2C,
+    config: Config,
+}
+
+impl<I2C, E> Nau7802Builder<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn address(mut self, address: u8) -> Self {
+        self.config.address = address;
+        self
+    }
+
+    pub fn ldo(mut self, ldo: LdoVoltage) -> Self {
+        self.config.ldo = ldo;
+        self
+    }
+
+    pub fn gain(mut self, gain: Gains) -> Self {
+        self.config.gain = gain;
+        self
+    }
+
+    pub fn rate(mut self, rate: ConversionRate) -> Self {
+        self.config.rate = rate;
+        self
+    }
+
+    pub fn channel(mut self, channel: AdcChannel) -> Self {
+        self.config.channel = channel;
+        self
+    }
+
+    ///Initializes, configures and calibrates the device
+    pub fn build<D: DelayNs>(self, delay: &mut D) -> Result<Nau7802<I2C>, Error<E>> {
+        let mut nau7802 = Nau7802::with_address(self.i2c, self.config.address);
+
+        nau7802.configure(&self.config, delay)?;
+
+        Result::Ok(nau7802)
+    }
+}
+
+impl<I2C, E> Nau7802<I2C>
 where
     I2C: I2c<Error = E>,
     E: i2c::Error,
     // DRP: InputPin,
-    D: DelayNs,
 {
-    pub fn new(i2c: I2C) -> Nau7802<I2C, D> {
+    pub fn new(i2c: I2C) -> Nau7802<I2C> {
+        Self::with_address(i2c, DEFAULT_ADDRESS)
+    }
+
+    pub fn with_address(i2c: I2C, address: u8) -> Nau7802<I2C> {
         Self {
-            i2c: i2c,
+            i2c,
+            address,
             // data_ready_pin: data_ready_pin,
-            _delay: PhantomData,
         }
     }
 
+    pub fn builder(i2c: I2C) -> Nau7802Builder<I2C> {
+        Nau7802Builder {
+            i2c,
+            config: Config::default(),
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn destroy(self) -> I2C {
+        self.i2c
+    }
+
     fn write_register(&mut self, register: &Registers, value: &u8) -> Result<(), E> {
         let buffer: [u8; 2] = [*register as u8, *value];
 
-        match self.i2c.write(I2C_ADDR, &buffer) {
+        match self.i2c.write(self.address, &buffer) {
             Ok(_) => Result::Ok(()),
             Err(e) => Result::Err(e),
         }
@@ -73,29 +174,29 @@ where
         let write_buffer: [u8; 1] = [register as u8];
         let mut read_buffer: [u8; 1] = [0; 1];
 
-        self.i2c.write(I2C_ADDR, &write_buffer).unwrap();
+        self.i2c.write(self.address, &write_buffer)?;
 
-        match self.i2c.read(I2C_ADDR, &mut read_buffer) {
+        match self.i2c.read(self.address, &mut read_buffer) {
             Ok(_) => Result::Ok(read_buffer[0]),
             Err(e) => Result::Err(e),
         }
     }
 
-    pub fn initialize(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+    pub fn initialize<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
         //RR to 1
-        self.pu_ctrl_write(&&PU_CTRL::reset(true)).unwrap();
+        self.pu_ctrl_write(&PU_CTRL::reset(true))?;
 
         let mut cfg = PU_CTRL::reset(false);
         cfg.PUD = true;
         //RR to 0 and PUD to 1
-        self.pu_ctrl_write(&cfg).unwrap();
+        self.pu_ctrl_write(&cfg)?;
 
         //After ~200ms, PWRUP should be 1
         let mut attempts = 50;
         loop {
             delay.delay_ms(20);
 
-            let status = self.pu_ctrl().unwrap();
+            let status = self.pu_ctrl()?;
 
             if status.PUR {
                 break;
@@ -108,13 +209,20 @@ where
             }
         }
 
+        //Check the revision once powered up, as the reference drivers do
+        let revision_id = self.revision_id()?;
+
+        if revision_id != REVISION_ID {
+            return Result::Err(Error::InitializeUnknownRevision(revision_id));
+        }
+
         //Configure device
         let mut cfg = PU_CTRL::reset(false);
         cfg.AVDDS = true;
         cfg.OSCS = false;
         cfg.PUA = true;
         cfg.PUD = true;
-        self.pu_ctrl_write(&cfg).unwrap();
+        self.pu_ctrl_write(&cfg)?;
 
         //Start conversions with CS = 1
         let mut cfg = PU_CTRL::reset(false);
@@ -124,7 +232,7 @@ where
         cfg.PUD = true;
 
         cfg.CS = true;
-        self.pu_ctrl_write(&cfg).unwrap();
+        self.pu_ctrl_write(&cfg)?;
 
         Result::Ok(())
     }
@@ -157,8 +265,22 @@ where
         }
     }
 
+    pub fn otp(&mut self) -> Result<u16, Error<E>> {
+        let b1 = self.read_register(Registers::OTP_B1).map_err(Error::I2C)?;
+        let b0 = self.read_register(Registers::OTP_B0).map_err(Error::I2C)?;
+
+        Result::Ok(u16::from_be_bytes([b1, b0]))
+    }
+
+    pub fn device_info(&mut self) -> Result<DeviceInfo, Error<E>> {
+        Result::Ok(DeviceInfo {
+            revision_id: self.revision_id()?,
+            otp: self.otp()?,
+        })
+    }
+
     fn pu_ctrl(&mut self) -> Result<PU_CTRL, Error<E>> {
-        Result::Ok(PU_CTRL::from(self.read_register(Registers::PU_CTRL).unwrap()))
+        Result::Ok(PU_CTRL::from(self.read_register(Registers::PU_CTRL).map_err(Error::I2C)?))
     }
 
     fn pu_ctrl_write(&mut self, pu_ctrl: &PU_CTRL) -> Result<(), Error<E>> {
@@ -169,7 +291,7 @@ where
     }
 
     pub fn ctrl2(&mut self) -> Result<CTRL2, Error<E>> {
-        Result::Ok(CTRL2::from(self.read_register(Registers::CTRL2).unwrap()))
+        Result::Ok(CTRL2::from(self.read_register(Registers::CTRL2).map_err(Error::I2C)?))
     }
 
     fn ctrl2_write(&mut self, ctrl2: &CTRL2) -> Result<(), Error<E>> {
@@ -180,7 +302,7 @@ where
     }
 
     pub fn ctrl1(&mut self) -> Result<CTRL1, Error<E>> {
-        Result::Ok(CTRL1::from(self.read_register(Registers::CTRL1).unwrap()))
+        Result::Ok(CTRL1::from(self.read_register(Registers::CTRL1).map_err(Error::I2C)?))
     }
 
     fn ctrl1_write(&mut self, ctrl1: CTRL1) -> Result<(), Error<E>> {
@@ -191,7 +313,7 @@ where
     }
 
     pub fn enable_ldo(&mut self) -> Result<(), Error<E>> {
-        let mut pu_ctrl = self.pu_ctrl().unwrap();
+        let mut pu_ctrl = self.pu_ctrl()?;
 
         pu_ctrl.AVDDS = true;
 
@@ -199,7 +321,7 @@ where
     }
 
     pub fn set_ldo_voltage(&mut self, ldo_voltage: LdoVoltage) -> Result<(), Error<E>> {
-        let mut ctrl1 = self.ctrl1().unwrap();
+        let mut ctrl1 = self.ctrl1()?;
 
         ctrl1.ldo_voltage = ldo_voltage;
 
@@ -207,20 +329,48 @@ where
     }
 
     pub fn set_gain(&mut self, gain: Gains) -> Result<(), Error<E>> {
-        let mut ctrl1 = self.ctrl1().unwrap();
+        let mut ctrl1 = self.ctrl1()?;
 
         ctrl1.gain_select = gain;
 
         self.ctrl1_write(ctrl1)
     }
 
-    fn wait_for_calibration_completion(&mut self, delay: &mut D) -> Result<(), Error<E>> {
-        //Wait for calibration register to read as 0
+    pub fn set_conversion_rate(&mut self, rate: ConversionRate) -> Result<(), Error<E>> {
+        let mut ctrl2 = self.ctrl2()?;
+
+        ctrl2.conversion_rate = rate;
+
+        self.ctrl2_write(&ctrl2)
+    }
+
+    ///Runs the full init sequence: reset and power up, LDO, gain, rate, channel, then calibration.
+    ///The device is addressed at `config.address` from here on.
+    pub fn configure<D: DelayNs>(&mut self, config: &Config, delay: &mut D) -> Result<(), Error<E>> {
+        self.address = config.address;
+
+        self.initialize(delay)?;
+
+        self.set_ldo_voltage(config.ldo)?;
+        self.enable_ldo()?;
+        self.set_gain(config.gain)?;
+        self.set_conversion_rate(config.rate)?;
+
+        //select_channel calibrates when it changes channel
+        if !self.select_channel(config.channel, delay)? {
+            self.calibrate(delay)?;
+        }
+
+        Result::Ok(())
+    }
+
+    fn wait_for_calibration_completion<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        //Wait for calibration register to read as 0, then check CAL_ERR
         let mut attempts = 50;
         loop {
             delay.delay_ms(20);
 
-            let ctrl2 = self.ctrl2().unwrap();
+            let ctrl2 = self.ctrl2()?;
 
             if ctrl2.calibrate {
                 attempts -= 1;
@@ -230,6 +380,8 @@ where
                 }
 
                 delay.delay_ms(10);
+            } else if ctrl2.cal_error {
+                return Result::Err(Error::Calibration);
             } else {
                 break;
             }
@@ -238,23 +390,23 @@ where
         Result::Ok(())
     }
 
-    pub fn calibrate(&mut self, delay: &mut D) -> Result<(), Error<E>> {
-        let mut ctrl2 = self.ctrl2().unwrap();
+    pub fn calibrate<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        let mut ctrl2 = self.ctrl2()?;
 
         ctrl2.calibrate = true;
 
-        self.ctrl2_write(&ctrl2).unwrap();
+        self.ctrl2_write(&ctrl2)?;
 
         self.wait_for_calibration_completion(delay)
     }
 
-    pub fn select_channel(&mut self, adc_channel: AdcChannel, delay: &mut D) -> Result<bool, Error<E>> {
-        let ctrl2 = self.ctrl2().unwrap();
+    pub fn select_channel<D: DelayNs>(&mut self, adc_channel: AdcChannel, delay: &mut D) -> Result<bool, Error<E>> {
+        let ctrl2 = self.ctrl2()?;
 
         let updated_ctrl2 = match adc_channel {
             AdcChannel::A => {
                 if ctrl2.channel2_selected {
-                    let mut v = ctrl2.clone();
+                    let mut v = ctrl2;
                     v.channel2_selected = false;
                     v.calibrate = true;
                     Some(v)
@@ -266,7 +418,7 @@ where
                 if ctrl2.channel2_selected {
                     None
                 } else {
-                    let mut v = ctrl2.clone();
+                    let mut v = ctrl2;
                     v.channel2_selected = true;
                     v.calibrate = true;
                     Some(v)
@@ -276,9 +428,9 @@ where
 
         match updated_ctrl2 {
             Some(v) => {
-                self.ctrl2_write(&v).unwrap();
+                self.ctrl2_write(&v)?;
 
-                self.wait_for_calibration_completion(delay).unwrap();
+                self.wait_for_calibration_completion(delay)?;
 
                 Result::Ok(true)
             }
@@ -286,38 +438,12 @@ where
         }
     }
 
-    ///Converts 3 i24 bytes into an i32
-    fn i32_from_i24_be_bytes(b: &[u8; 3]) -> i32 {
-        let mut i32_bytes: [u8; 4] = [0x00; 4];
-        i32_bytes[0..3].copy_from_slice(b);
-
-        i32::from_be_bytes(i32_bytes) >> 8
-    }
-
-    fn i32_to_i24_be_bytes(v: i32) -> [u8; 3] {
-        let b = v.to_be_bytes();
-        [b[0], b[1], b[2]]
-    }
-
     pub fn set_adc_offset(&mut self, adc_channel: AdcChannel, offset: i32) -> Result<(), Error<E>> {
-        let b = Self::i32_to_i24_be_bytes(offset);
-
-        let registers = match adc_channel {
-            AdcChannel::A => [
-                Registers::OCAL1_B0,
-                Registers::OCAL1_B1,
-                Registers::OCAL1_B2,
-            ],
-            AdcChannel::B => [
-                Registers::OCAL2_B0,
-                Registers::OCAL2_B1,
-                Registers::OCAL2_B2,
-            ],
-        };
+        let b = i32_to_i24_be_bytes(offset);
 
-        registers.iter().zip(b.iter()).for_each(|(r, b)| {
-            self.write_register(r, b).unwrap();
-        });
+        for (r, b) in offset_calibration_registers(adc_channel).iter().zip(b.iter()) {
+            self.write_register(r, b).map_err(Error::I2C)?;
+        }
 
         Result::Ok(())
     }
@@ -325,31 +451,16 @@ where
     pub fn set_adc_gain_calibration(&mut self, adc_channel: AdcChannel, gain: i32) -> Result<(), Error<E>> {
         let b = gain.to_be_bytes();
 
-        let registers = match adc_channel {
-            AdcChannel::A => [
-                Registers::OCAL1_B0,
-                Registers::GCAL1_B1,
-                Registers::GCAL1_B2,
-                Registers::GCAL1_B3,
-            ],
-            AdcChannel::B => [
-                Registers::GCAL2_B0,
-                Registers::GCAL2_B1,
-                Registers::GCAL2_B2,
-                Registers::GCAL2_B3,
-            ],
-        };
-
-        registers.iter().zip(b.iter()).for_each(|(r, b)| {
-            self.write_register(r, b).unwrap();
-        });
+        for (r, b) in gain_calibration_registers(adc_channel).iter().zip(b.iter()) {
+            self.write_register(r, b).map_err(Error::I2C)?;
+        }
 
         Result::Ok(())
     }
 
     ///Reads the current ADC result.  i24 value returned in an i32.
     pub fn read_adc(&mut self) -> Result<i32, Error<E>> {
-        let pu_ctrl = self.pu_ctrl().unwrap();
+        let pu_ctrl = self.pu_ctrl()?;
 
         if !pu_ctrl.CR {
             return Result::Err(Error::DataNotReady)
@@ -360,10 +471,10 @@ where
 
         match self
             .i2c
-            .write_read(I2C_ADDR, &write_buffer, &mut read_buffer)
+            .write_read(self.address, &write_buffer, &mut read_buffer)
         {
             Ok(_) => {
-                let val = Self::i32_from_i24_be_bytes(&read_buffer);
+                let val = i32_from_i24_be_bytes(&read_buffer);
 
                 Result::Ok(val)
             }
@@ -371,3 +482,51 @@ where
         }
     }
 }
+
+///Converts 3 i24 bytes into an i32
+pub(crate) fn i32_from_i24_be_bytes(b: &[u8; 3]) -> i32 {
+    let mut i32_bytes: [u8; 4] = [0x00; 4];
+    i32_bytes[0..3].copy_from_slice(b);
+
+    i32::from_be_bytes(i32_bytes) >> 8
+}
+
+///Converts the low 24 bits of an i32 into 3 big-endian bytes
+pub(crate) fn i32_to_i24_be_bytes(v: i32) -> [u8; 3] {
+    let b = v.to_be_bytes();
+    [b[1], b[2], b[3]]
+}
+
+///Offset calibration registers for a channel, most significant byte first
+pub(crate) fn offset_calibration_registers(adc_channel: AdcChannel) -> [Registers; 3] {
+    match adc_channel {
+        AdcChannel::A => [
+            Registers::OCAL1_B2,
+            Registers::OCAL1_B1,
+            Registers::OCAL1_B0,
+        ],
+        AdcChannel::B => [
+            Registers::OCAL2_B2,
+            Registers::OCAL2_B1,
+            Registers::OCAL2_B0,
+        ],
+    }
+}
+
+///Gain calibration registers for a channel, most significant byte first
+pub(crate) fn gain_calibration_registers(adc_channel: AdcChannel) -> [Registers; 4] {
+    match adc_channel {
+        AdcChannel::A => [
+            Registers::GCAL1_B3,
+            Registers::GCAL1_B2,
+            Registers::GCAL1_B1,
+            Registers::GCAL1_B0,
+        ],
+        AdcChannel::B => [
+            Registers::GCAL2_B3,
+            Registers::GCAL2_B2,
+            Registers::GCAL2_B1,
+            Registers::GCAL2_B0,
+        ],
+    }
+}
@@ -0,0 +1,458 @@
+use embedded_hal::i2c;
+use embedded_hal_async::{delay::DelayNs, i2c::I2c};
+
+use crate::nau7802::{
+    gain_calibration_registers, i32_from_i24_be_bytes, i32_to_i24_be_bytes,
+    offset_calibration_registers, AdcChannel, Config, DeviceInfo, Error, DEFAULT_ADDRESS,
+    REVISION_ID,
+};
+use crate::registers::*;
+
+///Async variant of `Nau7802`.  Calibration and power-up waits await the delay rather than
+///blocking, so other tasks on the executor keep running.
+pub struct Nau7802Async<I2C>
+where
+    I2C: I2c,
+{
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C, E> Nau7802Async<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(i2c: I2C) -> Nau7802Async<I2C> {
+        Self::with_address(i2c, DEFAULT_ADDRESS)
+    }
+
+    pub fn with_address(i2c: I2C, address: u8) -> Nau7802Async<I2C> {
+        Self { i2c, address }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn destroy(self) -> I2C {
+        self.i2c
+    }
+
+    async fn write_register(&mut self, register: &Registers, value: &u8) -> Result<(), E> {
+        let buffer: [u8; 2] = [*register as u8, *value];
+
+        self.i2c.write(self.address, &buffer).await
+    }
+
+    async fn read_register(&mut self, register: Registers) -> Result<u8, E> {
+        let write_buffer: [u8; 1] = [register as u8];
+        let mut read_buffer: [u8; 1] = [0; 1];
+
+        self.i2c.write(self.address, &write_buffer).await?;
+
+        self.i2c.read(self.address, &mut read_buffer).await?;
+
+        Result::Ok(read_buffer[0])
+    }
+
+    pub async fn initialize<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        //RR to 1
+        self.pu_ctrl_write(&PU_CTRL::reset(true)).await?;
+
+        let mut cfg = PU_CTRL::reset(false);
+        cfg.PUD = true;
+        //RR to 0 and PUD to 1
+        self.pu_ctrl_write(&cfg).await?;
+
+        //After ~200ms, PWRUP should be 1
+        let mut attempts = 50;
+        loop {
+            delay.delay_ms(20).await;
+
+            let status = self.pu_ctrl().await?;
+
+            if status.PUR {
+                break;
+            } else {
+                attempts -= 1;
+
+                if attempts == 0 {
+                    return Result::Err(Error::InitializeNoPowerup(status.into()));
+                }
+            }
+        }
+
+        //Check the revision once powered up, as the reference drivers do
+        let revision_id = self.revision_id().await?;
+
+        if revision_id != REVISION_ID {
+            return Result::Err(Error::InitializeUnknownRevision(revision_id));
+        }
+
+        //Configure device
+        let mut cfg = PU_CTRL::reset(false);
+        cfg.AVDDS = true;
+        cfg.OSCS = false;
+        cfg.PUA = true;
+        cfg.PUD = true;
+        self.pu_ctrl_write(&cfg).await?;
+
+        //Start conversions with CS = 1
+        let mut cfg = PU_CTRL::reset(false);
+        cfg.CS = true;
+        cfg.OSCS = false;
+        cfg.PUA = true;
+        cfg.PUD = true;
+        self.pu_ctrl_write(&cfg).await?;
+
+        Result::Ok(())
+    }
+
+    pub async fn is_data_ready(&mut self) -> Result<bool, Error<E>> {
+        Result::Ok(self.pu_ctrl().await?.CR)
+    }
+
+    pub async fn revision_id(&mut self) -> Result<u8, Error<E>> {
+        match self.read_register(Registers::DEVICE_REVISION).await {
+            Ok(v) => Result::Ok(v & 0x0F),
+            Err(e) => Result::Err(Error::I2C(e)),
+        }
+    }
+
+    pub async fn otp(&mut self) -> Result<u16, Error<E>> {
+        let b1 = self.read_register(Registers::OTP_B1).await.map_err(Error::I2C)?;
+        let b0 = self.read_register(Registers::OTP_B0).await.map_err(Error::I2C)?;
+
+        Result::Ok(u16::from_be_bytes([b1, b0]))
+    }
+
+    pub async fn device_info(&mut self) -> Result<DeviceInfo, Error<E>> {
+        Result::Ok(DeviceInfo {
+            revision_id: self.revision_id().await?,
+            otp: self.otp().await?,
+        })
+    }
+
+    async fn pu_ctrl(&mut self) -> Result<PU_CTRL, Error<E>> {
+        match self.read_register(Registers::PU_CTRL).await {
+            Ok(v) => Result::Ok(PU_CTRL::from(v)),
+            Err(e) => Result::Err(Error::I2C(e)),
+        }
+    }
+
+    async fn pu_ctrl_write(&mut self, pu_ctrl: &PU_CTRL) -> Result<(), Error<E>> {
+        self.write_register(&Registers::PU_CTRL, &((*pu_ctrl).into()))
+            .await
+            .map_err(Error::I2C)
+    }
+
+    pub async fn ctrl1(&mut self) -> Result<CTRL1, Error<E>> {
+        match self.read_register(Registers::CTRL1).await {
+            Ok(v) => Result::Ok(CTRL1::from(v)),
+            Err(e) => Result::Err(Error::I2C(e)),
+        }
+    }
+
+    async fn ctrl1_write(&mut self, ctrl1: CTRL1) -> Result<(), Error<E>> {
+        self.write_register(&Registers::CTRL1, &ctrl1.into())
+            .await
+            .map_err(Error::I2C)
+    }
+
+    pub async fn ctrl2(&mut self) -> Result<CTRL2, Error<E>> {
+        match self.read_register(Registers::CTRL2).await {
+            Ok(v) => Result::Ok(CTRL2::from(v)),
+            Err(e) => Result::Err(Error::I2C(e)),
+        }
+    }
+
+    async fn ctrl2_write(&mut self, ctrl2: &CTRL2) -> Result<(), Error<E>> {
+        self.write_register(&Registers::CTRL2, &((*ctrl2).into()))
+            .await
+            .map_err(Error::I2C)
+    }
+
+    pub async fn enable_ldo(&mut self) -> Result<(), Error<E>> {
+        let mut pu_ctrl = self.pu_ctrl().await?;
+
+        pu_ctrl.AVDDS = true;
+
+        self.pu_ctrl_write(&pu_ctrl).await
+    }
+
+    pub async fn set_ldo_voltage(&mut self, ldo_voltage: LdoVoltage) -> Result<(), Error<E>> {
+        let mut ctrl1 = self.ctrl1().await?;
+
+        ctrl1.ldo_voltage = ldo_voltage;
+
+        self.ctrl1_write(ctrl1).await
+    }
+
+    pub async fn set_gain(&mut self, gain: Gains) -> Result<(), Error<E>> {
+        let mut ctrl1 = self.ctrl1().await?;
+
+        ctrl1.gain_select = gain;
+
+        self.ctrl1_write(ctrl1).await
+    }
+
+    pub async fn set_conversion_rate(&mut self, rate: ConversionRate) -> Result<(), Error<E>> {
+        let mut ctrl2 = self.ctrl2().await?;
+
+        ctrl2.conversion_rate = rate;
+
+        self.ctrl2_write(&ctrl2).await
+    }
+
+    ///Runs the full init sequence: reset and power up, LDO, gain, rate, channel, then calibration.
+    ///The device is addressed at `config.address` from here on.
+    pub async fn configure<D: DelayNs>(&mut self, config: &Config, delay: &mut D) -> Result<(), Error<E>> {
+        self.address = config.address;
+
+        self.initialize(delay).await?;
+
+        self.set_ldo_voltage(config.ldo).await?;
+        self.enable_ldo().await?;
+        self.set_gain(config.gain).await?;
+        self.set_conversion_rate(config.rate).await?;
+
+        //select_channel calibrates when it changes channel
+        if !self.select_channel(config.channel, delay).await? {
+            self.calibrate(delay).await?;
+        }
+
+        Result::Ok(())
+    }
+
+    async fn wait_for_calibration_completion<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        //Wait for calibration register to read as 0, then check CAL_ERR
+        let mut attempts = 50;
+        loop {
+            delay.delay_ms(20).await;
+
+            let ctrl2 = self.ctrl2().await?;
+
+            if ctrl2.calibrate {
+                attempts -= 1;
+
+                if attempts == 0 {
+                    return Result::Err(Error::Initialize);
+                }
+
+                delay.delay_ms(10).await;
+            } else if ctrl2.cal_error {
+                return Result::Err(Error::Calibration);
+            } else {
+                break;
+            }
+        }
+
+        Result::Ok(())
+    }
+
+    pub async fn calibrate<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        let mut ctrl2 = self.ctrl2().await?;
+
+        ctrl2.calibrate = true;
+
+        self.ctrl2_write(&ctrl2).await?;
+
+        self.wait_for_calibration_completion(delay).await
+    }
+
+    pub async fn select_channel<D: DelayNs>(&mut self, adc_channel: AdcChannel, delay: &mut D) -> Result<bool, Error<E>> {
+        let ctrl2 = self.ctrl2().await?;
+
+        let channel2_selected = adc_channel == AdcChannel::B;
+
+        if ctrl2.channel2_selected == channel2_selected {
+            return Result::Ok(false);
+        }
+
+        let mut v = ctrl2;
+        v.channel2_selected = channel2_selected;
+        v.calibrate = true;
+
+        self.ctrl2_write(&v).await?;
+
+        self.wait_for_calibration_completion(delay).await?;
+
+        Result::Ok(true)
+    }
+
+    pub async fn set_adc_offset(&mut self, adc_channel: AdcChannel, offset: i32) -> Result<(), Error<E>> {
+        let b = i32_to_i24_be_bytes(offset);
+
+        for (r, b) in offset_calibration_registers(adc_channel).iter().zip(b.iter()) {
+            self.write_register(r, b).await.map_err(Error::I2C)?;
+        }
+
+        Result::Ok(())
+    }
+
+    pub async fn set_adc_gain_calibration(&mut self, adc_channel: AdcChannel, gain: i32) -> Result<(), Error<E>> {
+        let b = gain.to_be_bytes();
+
+        for (r, b) in gain_calibration_registers(adc_channel).iter().zip(b.iter()) {
+            self.write_register(r, b).await.map_err(Error::I2C)?;
+        }
+
+        Result::Ok(())
+    }
+
+    ///Reads the current ADC result.  i24 value returned in an i32.
+    pub async fn read_adc(&mut self) -> Result<i32, Error<E>> {
+        let pu_ctrl = self.pu_ctrl().await?;
+
+        if !pu_ctrl.CR {
+            return Result::Err(Error::DataNotReady);
+        }
+
+        let write_buffer: [u8; 1] = [Registers::ADCO_B2 as u8];
+        let mut read_buffer: [u8; 3] = [0; 3];
+
+        match self
+            .i2c
+            .write_read(self.address, &write_buffer, &mut read_buffer)
+            .await
+        {
+            Ok(_) => Result::Ok(i32_from_i24_be_bytes(&read_buffer)),
+            Err(e) => Result::Err(Error::I2C(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    #[test]
+    fn read_adc_returns_sign_extended_value() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x20]),
+            Transaction::write_read(DEFAULT_ADDRESS, vec![Registers::ADCO_B2 as u8], vec![0xFF, 0xFF, 0xFE]),
+        ];
+
+        let mut under_test = Nau7802Async::new(Mock::new(&expectations));
+
+        let result = block_on(under_test.read_adc()).unwrap();
+
+        assert_eq!(-2, result);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_adc_without_conversion_ready_returns_error() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x00]),
+        ];
+
+        let mut under_test = Nau7802Async::new(Mock::new(&expectations));
+
+        let result = block_on(under_test.read_adc());
+
+        assert!(matches!(result, Err(Error::DataNotReady)));
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn calibrate_polls_until_calibration_clears() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::CTRL2 as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x00]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::CTRL2 as u8, 0x04]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::CTRL2 as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x04]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::CTRL2 as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x00]),
+        ];
+
+        let mut under_test = Nau7802Async::new(Mock::new(&expectations));
+
+        block_on(under_test.calibrate(&mut NoopDelay::new())).unwrap();
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn configure_uses_config_address() {
+        const ADDR: u8 = 0x2b;
+
+        let expectations = [
+            //Reset and power up
+            Transaction::write(ADDR, vec![0x00, 0x01]),
+            Transaction::write(ADDR, vec![0x00, 0x02]),
+            Transaction::write(ADDR, vec![0x00]),
+            Transaction::read(ADDR, vec![0x0A]),
+            //Revision check
+            Transaction::write(ADDR, vec![0x1F]),
+            Transaction::read(ADDR, vec![0x0F]),
+            Transaction::write(ADDR, vec![0x00, 0x86]),
+            Transaction::write(ADDR, vec![0x00, 0x16]),
+            //LDO voltage and enable
+            Transaction::write(ADDR, vec![0x01]),
+            Transaction::read(ADDR, vec![0x00]),
+            Transaction::write(ADDR, vec![0x01, 0x28]),
+            Transaction::write(ADDR, vec![0x00]),
+            Transaction::read(ADDR, vec![0x16]),
+            Transaction::write(ADDR, vec![0x00, 0x96]),
+            //Gain
+            Transaction::write(ADDR, vec![0x01]),
+            Transaction::read(ADDR, vec![0x28]),
+            Transaction::write(ADDR, vec![0x01, 0x2C]),
+            //Rate
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x00]),
+            Transaction::write(ADDR, vec![0x02, 0x30]),
+            //Channel A already selected
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+            //Calibrate
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+            Transaction::write(ADDR, vec![0x02, 0x34]),
+            Transaction::write(ADDR, vec![0x02]),
+            Transaction::read(ADDR, vec![0x30]),
+        ];
+
+        let config = Config {
+            address: ADDR,
+            ldo: LdoVoltage::v3_0,
+            gain: Gains::x16,
+            rate: ConversionRate::SPS_80,
+            channel: AdcChannel::A,
+        };
+
+        let mut under_test = Nau7802Async::new(Mock::new(&expectations));
+
+        block_on(under_test.configure(&config, &mut NoopDelay::new())).unwrap();
+
+        assert_eq!(ADDR, under_test.address());
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn set_adc_offset_writes_most_significant_byte_first() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::OCAL1_B2 as u8, 0x12]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::OCAL1_B1 as u8, 0x34]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::OCAL1_B0 as u8, 0x56]),
+        ];
+
+        let mut under_test = Nau7802Async::new(Mock::new(&expectations));
+
+        block_on(under_test.set_adc_offset(AdcChannel::A, 0x123456)).unwrap();
+
+        under_test.destroy().done();
+    }
+}
@@ -46,6 +46,9 @@ pub struct PU_CTRL {
 }
 
 impl PU_CTRL {
+    ///Bits which are status outputs (CR, PUR).  Writes to these bits are ignored by the device.
+    pub const READ_ONLY_MASK: u8 = 0x28;
+
     pub fn reset(reset: bool) -> PU_CTRL {
         Self {
             AVDDS: false,
@@ -75,16 +78,16 @@ impl From<u8> for PU_CTRL {
     }
 }
 
-impl Into<u8> for PU_CTRL {
-    fn into(self) -> u8 {
-        0x00 | if self.AVDDS { 0x80 } else { 0x00 }
-            | if self.OSCS { 0x40 } else { 0x00 }
-            | if self.CR { 0x20 } else { 0x00 }
-            | if self.CS { 0x10 } else { 0x00 }
-            | if self.PUR { 0x08 } else { 0x00 }
-            | if self.PUA { 0x04 } else { 0x00 }
-            | if self.PUD { 0x02 } else { 0x00 }
-            | if self.RR { 0x01 } else { 0x00 }
+impl From<PU_CTRL> for u8 {
+    fn from(value: PU_CTRL) -> u8 {
+        (if value.AVDDS { 0x80 } else { 0x00 })
+            | if value.OSCS { 0x40 } else { 0x00 }
+            | if value.CR { 0x20 } else { 0x00 }
+            | if value.CS { 0x10 } else { 0x00 }
+            | if value.PUR { 0x08 } else { 0x00 }
+            | if value.PUA { 0x04 } else { 0x00 }
+            | if value.PUD { 0x02 } else { 0x00 }
+            | if value.RR { 0x01 } else { 0x00 }
     }
 }
 
@@ -118,8 +121,8 @@ pub enum Gains {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-
 pub struct CTRL1 {
+    ///CRP, bit 7.  Datasheet: 0 = CRDY pin is active high, 1 = CRDY pin is active low.
     pub conversion_ready_polarity_high: bool,
     pub drdy_clock_output: bool,
     pub ldo_voltage: LdoVoltage,
@@ -129,20 +132,20 @@ pub struct CTRL1 {
 impl From<u8> for CTRL1 {
     fn from(value: u8) -> Self {
         Self {
-            conversion_ready_polarity_high: !(value & 0x80) == 0x80,
+            conversion_ready_polarity_high: value & 0x80 == 0,
             drdy_clock_output: value & 0x40 != 0,
-            ldo_voltage: LdoVoltage::from((value >> 3)  & 0x07),
+            ldo_voltage: LdoVoltage::from((value >> 3) & 0x07),
             gain_select: Gains::from(value & 0x07),
         }
     }
 }
 
-impl Into<u8> for CTRL1 {
-    fn into(self) -> u8 {
-        0x00 | if self.conversion_ready_polarity_high { 0x00 } else { 0x80 }
-            | if self.drdy_clock_output { 0x40 } else { 0x00 }
-            | ((self.ldo_voltage as u8) & 0x07) << 3
-            | ((self.gain_select as u8) & 0x07) << 0
+impl From<CTRL1> for u8 {
+    fn from(value: CTRL1) -> u8 {
+        (if value.conversion_ready_polarity_high { 0x00 } else { 0x80 })
+            | if value.drdy_clock_output { 0x40 } else { 0x00 }
+            | ((value.ldo_voltage as u8) & 0x07) << 3
+            | ((value.gain_select as u8) & 0x07)
     }
 }
 
@@ -151,6 +154,10 @@ impl Into<u8> for CTRL1 {
 #[allow(non_camel_case_types)]
 pub enum ConversionRate {
     SPS_320 = 0b111,
+    //Reserved by the datasheet.  Present so that any register value round-trips.
+    RESERVED_110 = 0b110,
+    RESERVED_101 = 0b101,
+    RESERVED_100 = 0b100,
     SPS_80 = 0b011,
     SPS_40 = 0b010,
     SPS_20 = 0b001,
@@ -158,6 +165,11 @@ pub enum ConversionRate {
 }
 
 
+impl CTRL2 {
+    ///Bits which are status outputs (CAL_ERR).  Writes to these bits are ignored by the device.
+    pub const READ_ONLY_MASK: u8 = 0x08;
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct CTRL2 {
     pub channel2_selected: bool,
@@ -179,13 +191,13 @@ impl From<u8> for CTRL2 {
     }
 }
 
-impl Into<u8> for CTRL2 {
-    fn into(self) -> u8 {
-        0x00 | if self.channel2_selected { 0x80 } else { 0x00 }
-            | ((self.conversion_rate as u8) & 0x07) << 4
-            | if self.cal_error { 0x08 } else { 0x00 }
-            | if self.calibrate { 0x04 } else { 0x00 }
-            | self.cal_mod & 0x03 
+impl From<CTRL2> for u8 {
+    fn from(value: CTRL2) -> u8 {
+        (if value.channel2_selected { 0x80 } else { 0x00 })
+            | ((value.conversion_rate as u8) & 0x07) << 4
+            | if value.cal_error { 0x08 } else { 0x00 }
+            | if value.calibrate { 0x04 } else { 0x00 }
+            | value.cal_mod & 0x03
     }
 }
 
@@ -205,4 +217,55 @@ mod tests {
 
         assert_eq!(INPUT, result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pu_ctrl_round_trips_every_value() {
+        for value in 0..=u8::MAX {
+            let result: u8 = PU_CTRL::from(value).into();
+
+            assert_eq!(
+                value & !PU_CTRL::READ_ONLY_MASK,
+                result & !PU_CTRL::READ_ONLY_MASK,
+                "PU_CTRL {:#04x}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn ctrl1_round_trips_every_value() {
+        for value in 0..=u8::MAX {
+            let result: u8 = CTRL1::from(value).into();
+
+            assert_eq!(value, result, "CTRL1 {:#04x}", value);
+        }
+    }
+
+    #[test]
+    fn ctrl2_round_trips_every_value() {
+        for value in 0..=u8::MAX {
+            let result: u8 = CTRL2::from(value).into();
+
+            assert_eq!(
+                value & !CTRL2::READ_ONLY_MASK,
+                result & !CTRL2::READ_ONLY_MASK,
+                "CTRL2 {:#04x}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn ctrl1_crp_clear_decodes_as_active_high() {
+        let result = CTRL1::from(0x00);
+
+        assert!(result.conversion_ready_polarity_high);
+    }
+
+    #[test]
+    fn ctrl1_crp_set_decodes_as_active_low() {
+        let result = CTRL1::from(0x80);
+
+        assert!(!result.conversion_ready_polarity_high);
+    }
+}
@@ -0,0 +1,291 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, I2c};
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use sensor_core::scan::Device;
+use sensor_core::sensor::{Error, Sensor};
+
+use crate::nau7802::{self, Nau7802};
+
+const RAW: [(Quantity, Unit); 1] = [(Quantity::RawCounts, Unit::Counts)];
+const RAW_AND_WEIGHT: [(Quantity, Unit); 2] = [
+    (Quantity::RawCounts, Unit::Counts),
+    (Quantity::Weight, Unit::Milligram),
+];
+
+///Converts load cell counts to weight
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoadCellCalibration {
+    ///Counts with nothing on the load cell
+    pub zero_offset: i32,
+    pub counts_per_gram: f32,
+}
+
+impl LoadCellCalibration {
+    pub fn weight_mg(&self, counts: i32) -> i32 {
+        ((counts - self.zero_offset) as f32 * 1000.0 / self.counts_per_gram) as i32
+    }
+}
+
+///`Sensor` adapter for a NAU7802.  Each read produces the raw ADC counts of the selected channel,
+///plus the weight once a `LoadCellCalibration` is set, or `Error::NotReady` if no conversion has
+///completed since the last read.
+pub struct Nau7802Sensor<I2C>
+where
+    I2C: I2c,
+{
+    driver: Nau7802<I2C>,
+    channel: ChannelId,
+    calibration: Option<LoadCellCalibration>,
+}
+
+impl<I2C, E> Nau7802Sensor<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(driver: Nau7802<I2C>, instance: &'static str) -> Nau7802Sensor<I2C> {
+        Nau7802Sensor {
+            driver,
+            channel: ChannelId::new("nau7802", instance),
+            calibration: None,
+        }
+    }
+
+    pub fn calibration(&self) -> Option<LoadCellCalibration> {
+        self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: Option<LoadCellCalibration>) {
+        self.calibration = calibration;
+    }
+
+    ///Zeroes the load cell at the latest conversion, keeping `counts_per_gram`.  Returns the new
+    ///zero offset.  Needs a calibration to have been set.
+    pub fn tare(&mut self) -> Result<i32, Error> {
+        let mut calibration = self.calibration.ok_or(Error::Other)?;
+
+        calibration.zero_offset = self.driver.read_adc()?;
+        self.calibration = Some(calibration);
+
+        Result::Ok(calibration.zero_offset)
+    }
+
+    pub fn driver(&mut self) -> &mut Nau7802<I2C> {
+        &mut self.driver
+    }
+
+    pub fn destroy(self) -> Nau7802<I2C> {
+        self.driver
+    }
+}
+
+impl<E> From<nau7802::Error<E>> for Error
+where
+    E: i2c::Error,
+{
+    fn from(value: nau7802::Error<E>) -> Self {
+        match value {
+            nau7802::Error::I2C(error) => Error::I2C(error.kind()),
+            nau7802::Error::DataNotReady => Error::NotReady,
+            _ => Error::Other,
+        }
+    }
+}
+
+impl<I2C, E> Sensor for Nau7802Sensor<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    fn channel(&self) -> ChannelId {
+        self.channel
+    }
+
+    fn quantities(&self) -> &'static [(Quantity, Unit)] {
+        match self.calibration {
+            Some(_) => &RAW_AND_WEIGHT,
+            None => &RAW,
+        }
+    }
+
+    fn read(
+        &mut self,
+        timestamp_ms: u64,
+        _delay: &mut dyn DelayNs,
+        sink: &mut dyn FnMut(Reading),
+    ) -> Result<(), Error> {
+        let value = self.driver.read_adc()?;
+
+        sink(Reading::new(
+            self.channel,
+            Quantity::RawCounts,
+            Unit::Counts,
+            value,
+            timestamp_ms,
+        ));
+
+        if let Some(calibration) = self.calibration {
+            sink(Reading::new(
+                self.channel,
+                Quantity::Weight,
+                Unit::Milligram,
+                calibration.weight_mg(value),
+                timestamp_ms,
+            ));
+        }
+
+        Result::Ok(())
+    }
+}
+
+///`sensor_core::scan::Identify` for the NAU7802, by its revision id.  Only the fixed address is
+///tried.
+pub fn identify<I2C, D>(i2c: &mut I2C, _delay: &mut D, address: u8) -> Option<Device>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    if address != nau7802::DEFAULT_ADDRESS {
+        return None;
+    }
+
+    let revision = Nau7802::with_address(&mut *i2c, address).revision_id().ok()?;
+
+    (revision == nau7802::REVISION_ID).then_some(Device::Nau7802 { revision })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use crate::nau7802::DEFAULT_ADDRESS;
+    use crate::registers::Registers;
+
+    #[test]
+    fn read_produces_raw_counts() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x20]),
+            Transaction::write_read(DEFAULT_ADDRESS, vec![Registers::ADCO_B2 as u8], vec![0xFF, 0xFF, 0xFE]),
+        ];
+
+        let mut under_test = Nau7802Sensor::new(Nau7802::new(Mock::new(&expectations)), "load");
+
+        let mut readings = Vec::new();
+
+        under_test
+            .read(7, &mut NoopDelay::new(), &mut |reading| readings.push(reading))
+            .unwrap();
+
+        assert_eq!(
+            vec![Reading::new(
+                ChannelId::new("nau7802", "load"),
+                Quantity::RawCounts,
+                Unit::Counts,
+                -2,
+                7
+            )],
+            readings
+        );
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn calibrated_read_adds_weight() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x20]),
+            Transaction::write_read(DEFAULT_ADDRESS, vec![Registers::ADCO_B2 as u8], vec![0x00, 0x10, 0x00]),
+        ];
+
+        let mut under_test = Nau7802Sensor::new(Nau7802::new(Mock::new(&expectations)), "load");
+
+        under_test.set_calibration(Some(LoadCellCalibration {
+            zero_offset: 0x0800,
+            counts_per_gram: 16.0,
+        }));
+
+        let mut readings = Vec::new();
+
+        under_test
+            .read(7, &mut NoopDelay::new(), &mut |reading| readings.push(reading))
+            .unwrap();
+
+        assert_eq!(2, under_test.quantities().len());
+        assert_eq!(2, readings.len());
+        //(0x1000 - 0x0800) / 16 = 128g
+        assert_eq!((Quantity::Weight, 128_000), (readings[1].quantity, readings[1].value));
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn tare_zeroes_at_current_reading() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x20]),
+            Transaction::write_read(DEFAULT_ADDRESS, vec![Registers::ADCO_B2 as u8], vec![0x00, 0x12, 0x34]),
+        ];
+
+        let mut under_test = Nau7802Sensor::new(Nau7802::new(Mock::new(&expectations)), "load");
+
+        assert_eq!(Err(Error::Other), under_test.tare());
+
+        under_test.set_calibration(Some(LoadCellCalibration {
+            zero_offset: 0,
+            counts_per_gram: 16.0,
+        }));
+
+        assert_eq!(Ok(0x1234), under_test.tare());
+        assert_eq!(
+            Some(LoadCellCalibration {
+                zero_offset: 0x1234,
+                counts_per_gram: 16.0
+            }),
+            under_test.calibration()
+        );
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn read_without_conversion_ready_reports_not_ready() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::PU_CTRL as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x00]),
+        ];
+
+        let mut under_test = Nau7802Sensor::new(Nau7802::new(Mock::new(&expectations)), "load");
+
+        let result = under_test.read(0, &mut NoopDelay::new(), &mut |_| panic!("no reading expected"));
+
+        assert_eq!(Err(Error::NotReady), result);
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn identify_checks_revision() {
+        let expectations = [
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::DEVICE_REVISION as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0xAF]),
+            Transaction::write(DEFAULT_ADDRESS, vec![Registers::DEVICE_REVISION as u8]),
+            Transaction::read(DEFAULT_ADDRESS, vec![0x03]),
+        ];
+
+        let mut i2c = Mock::new(&expectations);
+
+        assert_eq!(
+            Some(Device::Nau7802 { revision: nau7802::REVISION_ID }),
+            identify(&mut i2c, &mut NoopDelay::new(), DEFAULT_ADDRESS)
+        );
+        assert_eq!(None, identify(&mut i2c, &mut NoopDelay::new(), DEFAULT_ADDRESS));
+        assert_eq!(None, identify(&mut i2c, &mut NoopDelay::new(), 0x2B));
+
+        i2c.done();
+    }
+}
@@ -0,0 +1,5 @@
+#![deny(unsafe_code)]
+
+pub mod pinout;
+#[cfg(feature = "esp-idf")]
+pub mod v0;
@@ -0,0 +1,108 @@
+///Hardware revision of the esp32_sensor_board, as in `hardware/<revision>`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Revision {
+    V0,
+}
+
+impl Revision {
+    pub const LATEST: Revision = Revision::V0;
+
+    pub fn pinout(&self) -> &'static Pinout {
+        match self {
+            Revision::V0 => &V0,
+        }
+    }
+}
+
+///ESP32-C3 GPIO numbers for each board function.  Names follow the schematic nets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pinout {
+    ///I2C_SDA, shared by the INA237s, NAU7802 and SHT40
+    pub i2c_sda: u8,
+    ///I2C_SCL
+    pub i2c_scl: u8,
+    ///SPI_SCK
+    pub spi_sck: u8,
+    ///SPI_COPI
+    pub spi_copi: u8,
+    ///SPI_CIPO
+    pub spi_cipo: u8,
+    ///SD_CS
+    pub sd_cs: u8,
+    ///SD_DET, low when a card is inserted
+    pub sd_card_detect: u8,
+    ///SPI_CS1, spare chip select on the expansion header
+    pub spi_cs1: u8,
+    ///EXT_1, drives relay 1 on the relay board
+    pub relay_1: u8,
+    ///EXT_2, drives relay 2 on the relay board
+    pub relay_2: u8,
+    ///STATUS_LED
+    pub status_led: u8,
+    ///DRDY_NAU, NAU7802 conversion ready
+    pub nau_data_ready: u8,
+}
+
+impl Pinout {
+    pub fn gpios(&self) -> [u8; 12] {
+        [
+            self.i2c_sda,
+            self.i2c_scl,
+            self.spi_sck,
+            self.spi_copi,
+            self.spi_cipo,
+            self.sd_cs,
+            self.sd_card_detect,
+            self.spi_cs1,
+            self.relay_1,
+            self.relay_2,
+            self.status_led,
+            self.nau_data_ready,
+        ]
+    }
+}
+
+pub const V0: Pinout = Pinout {
+    i2c_sda: 4,
+    i2c_scl: 5,
+    spi_sck: 15,
+    spi_copi: 16,
+    spi_cipo: 17,
+    sd_cs: 14,
+    sd_card_detect: 11,
+    spi_cs1: 13,
+    relay_1: 3,
+    relay_2: 10,
+    status_led: 6,
+    nau_data_ready: 7,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Strapping pins and the USB / UART pins used for flashing and logging
+    const RESERVED: [u8; 7] = [2, 8, 9, 18, 19, 20, 21];
+
+    #[test]
+    fn pins_are_unique() {
+        let mut gpios = Revision::V0.pinout().gpios();
+
+        gpios.sort();
+
+        assert!(gpios.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn pins_avoid_reserved_gpios() {
+        for gpio in Revision::V0.pinout().gpios() {
+            assert!(gpio <= 21, "GPIO{} does not exist on the ESP32-C3", gpio);
+            assert!(!RESERVED.contains(&gpio), "GPIO{} is reserved", gpio);
+        }
+    }
+
+    #[test]
+    fn latest_is_v0() {
+        assert_eq!(&V0, Revision::LATEST.pinout());
+    }
+}
@@ -0,0 +1,251 @@
+//! Peripherals for hardware revision v0.  GPIO numbers match `pinout::V0`.
+
+use embedded_hal::i2c::{self, Error as _, ErrorKind, ErrorType, I2c, Operation};
+use esp_idf_svc::hal::{
+    delay::Ets,
+    gpio::{
+        Gpio10, Gpio11, Gpio14, Gpio3, Gpio4, Gpio5, Gpio6, Gpio7, Input, Level, Output, PinDriver,
+        Pull,
+    },
+    i2c::{I2cConfig, I2cDriver, I2cError, I2C0},
+    modem::Modem,
+    peripheral::Peripheral,
+    peripherals::Peripherals,
+    prelude::*,
+    spi::{SpiDriver, SpiDriverConfig},
+};
+use esp_idf_svc::sys::EspError;
+use sensor_core::bus_health::ClockOutRecovery;
+
+use crate::pinout::Revision;
+
+pub const REVISION: Revision = Revision::V0;
+
+pub struct Config {
+    pub i2c_baudrate: Hertz,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            i2c_baudrate: 100.kHz().into(),
+        }
+    }
+}
+
+///SPI bus and chip select for the micro SD slot
+pub struct SdCard {
+    pub spi: SpiDriver<'static>,
+    pub cs: PinDriver<'static, Gpio14, Output>,
+    card_detect: PinDriver<'static, Gpio11, Input>,
+}
+
+impl SdCard {
+    pub fn is_card_present(&self) -> bool {
+        self.card_detect.get_level() == Level::Low
+    }
+}
+
+///Outputs to the relay board, off after `Board::new`
+pub struct Relays {
+    pub relay_1: PinDriver<'static, Gpio3, Output>,
+    pub relay_2: PinDriver<'static, Gpio10, Output>,
+}
+
+impl Relays {
+    ///Switches relay `index`, 1 or 2.  Returns false for any other index.
+    pub fn set(&mut self, index: u8, on: bool) -> Result<bool, EspError> {
+        let level = if on { Level::High } else { Level::Low };
+
+        match index {
+            1 => self.relay_1.set_level(level)?,
+            2 => self.relay_2.set_level(level)?,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+#[derive(Debug)]
+pub enum SensorI2cError {
+    I2C(I2cError),
+    ///The driver could not be re-created after a recovery
+    NoDriver(EspError),
+}
+
+impl i2c::Error for SensorI2cError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SensorI2cError::I2C(error) => error.kind(),
+            SensorI2cError::NoDriver(_) => ErrorKind::Bus,
+        }
+    }
+}
+
+///Sensor I2C bus that keeps hold of its pins, so a device holding SDA low can be clocked free.
+///`recover` works as a `sensor_core::bus_health::BusRecovery`.
+pub struct SensorI2c {
+    driver: Option<I2cDriver<'static>>,
+    i2c0: I2C0,
+    sda: Gpio4,
+    scl: Gpio5,
+    config: I2cConfig,
+}
+
+impl SensorI2c {
+    pub fn new(
+        i2c0: I2C0,
+        sda: Gpio4,
+        scl: Gpio5,
+        config: I2cConfig,
+    ) -> Result<SensorI2c, EspError> {
+        let mut bus = SensorI2c {
+            driver: None,
+            i2c0,
+            sda,
+            scl,
+            config,
+        };
+
+        bus.driver = Some(bus.create_driver()?);
+
+        Ok(bus)
+    }
+
+    #[allow(unsafe_code)]
+    fn create_driver(&mut self) -> Result<I2cDriver<'static>, EspError> {
+        //SAFETY: the peripheral and pins are only used through the one driver, and as GPIO by
+        //`recover` only while no driver exists
+        unsafe {
+            I2cDriver::new(
+                self.i2c0.clone_unchecked(),
+                self.sda.clone_unchecked(),
+                self.scl.clone_unchecked(),
+                &self.config,
+            )
+        }
+    }
+
+    ///Drops the driver, clocks SCL as a GPIO until SDA is released, sends a STOP and re-creates
+    ///the driver.  Returns true if SDA is free and the driver is back.
+    pub fn recover(&mut self) -> bool {
+        self.driver = None;
+
+        let released = match (
+            PinDriver::input_output_od(&mut self.scl),
+            PinDriver::input_output_od(&mut self.sda),
+        ) {
+            (Ok(scl), Ok(sda)) => ClockOutRecovery::new(scl, sda, Ets).clock_out(),
+            _ => false,
+        };
+
+        self.driver = self.create_driver().ok();
+
+        released && self.driver.is_some()
+    }
+
+    ///The driver, re-created first if the last recovery couldn't
+    fn driver(&mut self) -> Result<&mut I2cDriver<'static>, SensorI2cError> {
+        if self.driver.is_none() {
+            self.driver = Some(self.create_driver().map_err(SensorI2cError::NoDriver)?);
+        }
+
+        Ok(self.driver.as_mut().unwrap())
+    }
+}
+
+impl ErrorType for SensorI2c {
+    type Error = SensorI2cError;
+}
+
+impl I2c for SensorI2c {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        I2c::read(self.driver()?, address, read).map_err(SensorI2cError::I2C)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        I2c::write(self.driver()?, address, write).map_err(SensorI2cError::I2C)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        I2c::write_read(self.driver()?, address, write, read).map_err(SensorI2cError::I2C)
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self.driver()?, address, operations).map_err(SensorI2cError::I2C)
+    }
+}
+
+pub struct Board {
+    ///Sensor I2C bus.  Wrap in an `AtomicCell` to share between drivers.
+    pub i2c: SensorI2c,
+    pub sd_card: SdCard,
+    pub relays: Relays,
+    pub status_led: PinDriver<'static, Gpio6, Output>,
+    pub nau_data_ready: PinDriver<'static, Gpio7, Input>,
+    ///Radio, for Wi-Fi
+    pub modem: Modem,
+}
+
+impl Board {
+    ///Takes the ESP32 peripherals and configures them with the default `Config`.  Fails if the
+    ///peripherals were already taken.
+    pub fn take() -> Result<Board, EspError> {
+        Self::new(Peripherals::take()?, &Config::default())
+    }
+
+    pub fn new(peripherals: Peripherals, config: &Config) -> Result<Board, EspError> {
+        let pins = peripherals.pins;
+
+        let i2c_config = I2cConfig::new().baudrate(config.i2c_baudrate);
+        let i2c = SensorI2c::new(peripherals.i2c0, pins.gpio4, pins.gpio5, i2c_config)?;
+
+        let spi = SpiDriver::new(
+            peripherals.spi2,
+            pins.gpio15,
+            pins.gpio16,
+            Some(pins.gpio17),
+            &SpiDriverConfig::new(),
+        )?;
+
+        let mut cs = PinDriver::output(pins.gpio14)?;
+        cs.set_high()?;
+
+        let mut card_detect = PinDriver::input(pins.gpio11)?;
+        card_detect.set_pull(Pull::Up)?;
+
+        let mut relay_1 = PinDriver::output(pins.gpio3)?;
+        relay_1.set_low()?;
+
+        let mut relay_2 = PinDriver::output(pins.gpio10)?;
+        relay_2.set_low()?;
+
+        let mut status_led = PinDriver::output(pins.gpio6)?;
+        status_led.set_low()?;
+
+        let nau_data_ready = PinDriver::input(pins.gpio7)?;
+
+        Ok(Board {
+            i2c,
+            sd_card: SdCard {
+                spi,
+                cs,
+                card_detect,
+            },
+            relays: Relays { relay_1, relay_2 },
+            status_led,
+            nau_data_ready,
+            modem: peripherals.modem,
+        })
+    }
+}
@@ -0,0 +1,46 @@
+///Exponential backoff between retries of anything that can fail for a while, like a network
+///connection
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Backoff {
+    ///Wait after the first failure
+    pub initial_ms: u64,
+    ///Longest wait between attempts
+    pub max_ms: u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial_ms: 1_000,
+            max_ms: 60_000,
+        }
+    }
+}
+
+impl Backoff {
+    ///Wait before the next attempt after `failures` consecutive failures.  Doubles each time.
+    pub fn delay_ms(&self, failures: u32) -> u64 {
+        let shift = failures.saturating_sub(1).min(32);
+
+        self.initial_ms.saturating_mul(1 << shift).min(self.max_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff {
+            initial_ms: 500,
+            max_ms: 5_000,
+        };
+
+        assert_eq!(500, backoff.delay_ms(1));
+        assert_eq!(1_000, backoff.delay_ms(2));
+        assert_eq!(4_000, backoff.delay_ms(4));
+        assert_eq!(5_000, backoff.delay_ms(5));
+        assert_eq!(5_000, backoff.delay_ms(100));
+    }
+}
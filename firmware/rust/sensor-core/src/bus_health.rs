@@ -0,0 +1,564 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::i2c::{Error as _, ErrorKind, ErrorType, I2c, Operation};
+
+///Devices `BusHealth` keeps failure counts for
+pub const MAX_TRACKED_DEVICES: usize = 8;
+
+///Frees a stuck bus.  Called by `BusHealth` with the wrapped bus once a device has failed
+///`HealthConfig::recover_after` times in a row.
+pub trait BusRecovery<I2C> {
+    ///Returns true if the bus is free again
+    fn recover(&mut self, i2c: &mut I2C) -> bool;
+}
+
+///Leaves the bus alone and only counts failures
+pub struct NoRecovery;
+
+impl<I2C> BusRecovery<I2C> for NoRecovery {
+    fn recover(&mut self, _i2c: &mut I2C) -> bool {
+        false
+    }
+}
+
+///Any closure can act as a strategy, e.g. one that rebuilds the I2C driver in place
+impl<I2C, F> BusRecovery<I2C> for F
+where
+    F: FnMut(&mut I2C) -> bool,
+{
+    fn recover(&mut self, i2c: &mut I2C) -> bool {
+        self(i2c)
+    }
+}
+
+///Clocks up to 9 SCL pulses until the device holding SDA low lets go, then sends a STOP.  The
+///pins must be open drain and not claimed by the I2C peripheral while this runs.
+pub struct ClockOutRecovery<SCL, SDA, D> {
+    scl: SCL,
+    sda: SDA,
+    delay: D,
+}
+
+impl<SCL, SDA, D> ClockOutRecovery<SCL, SDA, D>
+where
+    SCL: OutputPin,
+    SDA: InputPin + OutputPin,
+    D: DelayNs,
+{
+    ///Half of a 100kHz SCL period
+    const HALF_PERIOD_US: u32 = 5;
+
+    pub fn new(scl: SCL, sda: SDA, delay: D) -> ClockOutRecovery<SCL, SDA, D> {
+        ClockOutRecovery { scl, sda, delay }
+    }
+
+    pub fn destroy(self) -> (SCL, SDA, D) {
+        (self.scl, self.sda, self.delay)
+    }
+
+    ///Returns true if SDA is high afterwards
+    pub fn clock_out(&mut self) -> bool {
+        let _ = self.sda.set_high();
+
+        for _ in 0..9 {
+            if self.sda.is_high().unwrap_or(false) {
+                break;
+            }
+
+            let _ = self.scl.set_low();
+            self.delay.delay_us(Self::HALF_PERIOD_US);
+            let _ = self.scl.set_high();
+            self.delay.delay_us(Self::HALF_PERIOD_US);
+        }
+
+        //STOP: SDA rises while SCL is high
+        let _ = self.scl.set_low();
+        let _ = self.sda.set_low();
+        self.delay.delay_us(Self::HALF_PERIOD_US);
+        let _ = self.scl.set_high();
+        self.delay.delay_us(Self::HALF_PERIOD_US);
+        let _ = self.sda.set_high();
+        self.delay.delay_us(Self::HALF_PERIOD_US);
+
+        self.sda.is_high().unwrap_or(false)
+    }
+}
+
+impl<I2C, SCL, SDA, D> BusRecovery<I2C> for ClockOutRecovery<SCL, SDA, D>
+where
+    SCL: OutputPin,
+    SDA: InputPin + OutputPin,
+    D: DelayNs,
+{
+    fn recover(&mut self, _i2c: &mut I2C) -> bool {
+        self.clock_out()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HealthConfig {
+    ///Further attempts after a failed operation.  NACKs are not retried: nothing answered, or the
+    ///device refused a byte of a write that may already have taken effect.
+    pub max_retries: u8,
+    ///Wait before the first retry.  Doubles on each further retry.
+    pub backoff_us: u32,
+    pub max_backoff_us: u32,
+    ///Consecutive failures on one device before running the recovery strategy, and again after
+    ///every further `recover_after` failures
+    pub recover_after: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff_us: 1_000,
+            max_backoff_us: 20_000,
+            recover_after: 3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct DeviceHealth {
+    pub address: u8,
+    pub consecutive_failures: u32,
+    pub total_failures: u32,
+}
+
+///Wraps an I2C bus, retrying failed operations and running a `BusRecovery` when a device keeps
+///failing.  Drivers use it like any other `I2c`.  Only addresses that have answered at least once
+///are tracked, so probing empty addresses doesn't fill the table.
+pub struct BusHealth<I2C, R, D> {
+    i2c: I2C,
+    recovery: R,
+    delay: D,
+    config: HealthConfig,
+    devices: [Option<DeviceHealth>; MAX_TRACKED_DEVICES],
+    recoveries: u32,
+    failed_recoveries: u32,
+}
+
+impl<I2C, R, D> BusHealth<I2C, R, D>
+where
+    I2C: I2c,
+    R: BusRecovery<I2C>,
+    D: DelayNs,
+{
+    pub fn new(i2c: I2C, recovery: R, delay: D, config: HealthConfig) -> BusHealth<I2C, R, D> {
+        BusHealth {
+            i2c,
+            recovery,
+            delay,
+            config,
+            devices: [None; MAX_TRACKED_DEVICES],
+            recoveries: 0,
+            failed_recoveries: 0,
+        }
+    }
+
+    pub fn destroy(self) -> (I2C, R, D) {
+        (self.i2c, self.recovery, self.delay)
+    }
+
+    pub fn health(&self, address: u8) -> Option<DeviceHealth> {
+        self.devices
+            .iter()
+            .flatten()
+            .find(|device| device.address == address)
+            .copied()
+    }
+
+    ///Times the recovery strategy freed the bus
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+
+    ///Times the recovery strategy ran but the bus stayed stuck
+    pub fn failed_recoveries(&self) -> u32 {
+        self.failed_recoveries
+    }
+
+    fn tracked(&mut self, address: u8) -> Option<&mut DeviceHealth> {
+        self.devices
+            .iter_mut()
+            .flatten()
+            .find(|device| device.address == address)
+    }
+
+    fn record_success(&mut self, address: u8) {
+        if let Some(device) = self.tracked(address) {
+            device.consecutive_failures = 0;
+        } else if let Some(slot) = self.devices.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(DeviceHealth {
+                address,
+                ..DeviceHealth::default()
+            });
+        }
+    }
+
+    ///Returns the device's consecutive failure count, or `attempt + 1` for an untracked address
+    fn record_failure(&mut self, address: u8, attempt: u8) -> u32 {
+        match self.tracked(address) {
+            Some(device) => {
+                device.consecutive_failures += 1;
+                device.total_failures += 1;
+
+                device.consecutive_failures
+            }
+            None => u32::from(attempt) + 1,
+        }
+    }
+
+    fn backoff_us(&self, attempt: u8) -> u32 {
+        self.config
+            .backoff_us
+            .checked_shl(u32::from(attempt))
+            .unwrap_or(u32::MAX)
+            .min(self.config.max_backoff_us)
+    }
+
+    fn with_retry<T>(
+        &mut self,
+        address: u8,
+        mut operation: impl FnMut(&mut I2C) -> Result<T, I2C::Error>,
+    ) -> Result<T, I2C::Error> {
+        let mut attempt = 0;
+
+        loop {
+            let error = match operation(&mut self.i2c) {
+                Ok(value) => {
+                    self.record_success(address);
+
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
+
+            //A NACK means the bus itself is working, so there is nothing to recover.  Some HALs
+            //can't tell an address NACK from a data NACK, so all are treated alike.
+            if let ErrorKind::NoAcknowledge(_) = error.kind() {
+                self.record_failure(address, attempt);
+
+                return Err(error);
+            }
+
+            let failures = self.record_failure(address, attempt);
+
+            if self.config.recover_after > 0 && failures % self.config.recover_after == 0 {
+                if self.recovery.recover(&mut self.i2c) {
+                    self.recoveries += 1;
+                } else {
+                    self.failed_recoveries += 1;
+                }
+            }
+
+            if attempt >= self.config.max_retries {
+                return Err(error);
+            }
+
+            self.delay.delay_us(self.backoff_us(attempt));
+
+            attempt += 1;
+        }
+    }
+}
+
+impl<I2C, R, D> ErrorType for BusHealth<I2C, R, D>
+where
+    I2C: I2c,
+{
+    type Error = I2C::Error;
+}
+
+impl<I2C, R, D> I2c for BusHealth<I2C, R, D>
+where
+    I2C: I2c,
+    R: BusRecovery<I2C>,
+    D: DelayNs,
+{
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.with_retry(address, |i2c| i2c.read(address, read))
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.with_retry(address, |i2c| i2c.write(address, write))
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.with_retry(address, |i2c| i2c.write_read(address, write, read))
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.with_retry(address, |i2c| i2c.transaction(address, operations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::NoAcknowledgeSource;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use std::cell::Cell;
+
+    const ADDRESS: u8 = 0x44;
+
+    fn stuck(transaction: I2cTransaction) -> I2cTransaction {
+        transaction.with_error(ErrorKind::Bus)
+    }
+
+    fn nack(transaction: I2cTransaction, source: NoAcknowledgeSource) -> I2cTransaction {
+        transaction.with_error(ErrorKind::NoAcknowledge(source))
+    }
+
+    ///A probe the device answers, so `BusHealth` tracks it
+    fn answered(address: u8) -> I2cTransaction {
+        I2cTransaction::write(address, vec![])
+    }
+
+    fn under_test<R: BusRecovery<I2cMock>>(
+        expectations: &[I2cTransaction],
+        recovery: R,
+    ) -> BusHealth<I2cMock, R, NoopDelay> {
+        BusHealth::new(
+            I2cMock::new(expectations),
+            recovery,
+            NoopDelay::new(),
+            HealthConfig::default(),
+        )
+    }
+
+    #[test]
+    fn flaky_device_succeeds_on_retry() {
+        let expectations = [
+            answered(ADDRESS),
+            stuck(I2cTransaction::write(ADDRESS, vec![0xFD])),
+            I2cTransaction::write(ADDRESS, vec![0xFD]),
+        ];
+
+        let mut under_test = under_test(&expectations, NoRecovery);
+
+        under_test.write(ADDRESS, &[]).unwrap();
+        under_test.write(ADDRESS, &[0xFD]).unwrap();
+
+        assert_eq!(
+            Some(DeviceHealth {
+                address: ADDRESS,
+                consecutive_failures: 0,
+                total_failures: 1
+            }),
+            under_test.health(ADDRESS)
+        );
+        assert_eq!(0, under_test.recoveries());
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn stuck_device_triggers_recovery_and_fails() {
+        let expectations = [
+            answered(ADDRESS),
+            stuck(I2cTransaction::write_read(ADDRESS, vec![0x3E], vec![0x00, 0x00])),
+            stuck(I2cTransaction::write_read(ADDRESS, vec![0x3E], vec![0x00, 0x00])),
+            stuck(I2cTransaction::write_read(ADDRESS, vec![0x3E], vec![0x00, 0x00])),
+        ];
+
+        let recovered = Cell::new(0);
+
+        let mut under_test = under_test(&expectations, |_: &mut I2cMock| {
+            recovered.set(recovered.get() + 1);
+            true
+        });
+
+        under_test.write(ADDRESS, &[]).unwrap();
+
+        let mut read_buffer = [0x00; 2];
+        let result = under_test.write_read(ADDRESS, &[0x3E], &mut read_buffer);
+
+        assert_eq!(Err(ErrorKind::Bus), result);
+        assert_eq!(1, recovered.get());
+        assert_eq!(1, under_test.recoveries());
+        assert_eq!(3, under_test.health(ADDRESS).unwrap().consecutive_failures);
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn recovery_frees_stuck_bus() {
+        let expectations = [
+            stuck(I2cTransaction::read(ADDRESS, vec![0x00])),
+            stuck(I2cTransaction::read(ADDRESS, vec![0x00])),
+            stuck(I2cTransaction::read(ADDRESS, vec![0x00])),
+            I2cTransaction::read(ADDRESS, vec![0x2A]),
+        ];
+
+        let mut under_test = BusHealth::new(
+            I2cMock::new(&expectations),
+            |_: &mut I2cMock| true,
+            NoopDelay::new(),
+            HealthConfig {
+                max_retries: 3,
+                ..HealthConfig::default()
+            },
+        );
+
+        let mut read_buffer = [0x00; 1];
+
+        under_test.read(ADDRESS, &mut read_buffer).unwrap();
+
+        assert_eq!([0x2A], read_buffer);
+        assert_eq!(1, under_test.recoveries());
+        assert_eq!(0, under_test.health(ADDRESS).unwrap().consecutive_failures);
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn missing_device_is_not_retried() {
+        let expectations = [I2cTransaction::write(0x50, vec![])
+            .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))];
+
+        let mut under_test = under_test(&expectations, NoRecovery);
+
+        assert!(under_test.write(0x50, &[]).is_err());
+        assert_eq!(None, under_test.health(0x50));
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn failures_are_counted_per_device() {
+        let expectations = [
+            answered(0x44),
+            answered(0x46),
+            stuck(I2cTransaction::write(0x44, vec![0x94])),
+            I2cTransaction::write(0x44, vec![0x94]),
+            stuck(I2cTransaction::write(0x46, vec![0x00])),
+            stuck(I2cTransaction::write(0x46, vec![0x00])),
+            stuck(I2cTransaction::write(0x46, vec![0x00])),
+        ];
+
+        let mut under_test = under_test(&expectations, NoRecovery);
+
+        under_test.write(0x44, &[]).unwrap();
+        under_test.write(0x46, &[]).unwrap();
+        under_test.write(0x44, &[0x94]).unwrap();
+        let _ = under_test.write(0x46, &[0x00]);
+
+        assert_eq!(1, under_test.health(0x44).unwrap().total_failures);
+        assert_eq!(3, under_test.health(0x46).unwrap().consecutive_failures);
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn unknown_nack_on_empty_addresses_is_not_tracked() {
+        let mut expectations: Vec<_> = (0x08..=0x17)
+            .map(|address| nack(I2cTransaction::write(address, vec![]), NoAcknowledgeSource::Unknown))
+            .collect();
+        expectations.push(answered(ADDRESS));
+
+        let mut under_test = under_test(&expectations, NoRecovery);
+
+        for address in 0x08..=0x17 {
+            assert!(under_test.write(address, &[]).is_err());
+            assert_eq!(None, under_test.health(address));
+        }
+
+        under_test.write(ADDRESS, &[]).unwrap();
+
+        assert_eq!(0, under_test.recoveries() + under_test.failed_recoveries());
+        assert!(under_test.health(ADDRESS).is_some());
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn data_nack_on_write_is_counted_but_not_retried() {
+        let expectations = [
+            answered(ADDRESS),
+            nack(I2cTransaction::write(ADDRESS, vec![0x94]), NoAcknowledgeSource::Data),
+        ];
+
+        let mut under_test = under_test(&expectations, |_: &mut I2cMock| -> bool {
+            panic!("no recovery expected")
+        });
+
+        under_test.write(ADDRESS, &[]).unwrap();
+
+        assert!(under_test.write(ADDRESS, &[0x94]).is_err());
+        assert_eq!(1, under_test.health(ADDRESS).unwrap().consecutive_failures);
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn failed_recovery_is_counted() {
+        let expectations = [
+            stuck(I2cTransaction::write(ADDRESS, vec![0x94])),
+            stuck(I2cTransaction::write(ADDRESS, vec![0x94])),
+            stuck(I2cTransaction::write(ADDRESS, vec![0x94])),
+        ];
+
+        let mut under_test = under_test(&expectations, |_: &mut I2cMock| false);
+
+        assert_eq!(Err(ErrorKind::Bus), under_test.write(ADDRESS, &[0x94]));
+        assert_eq!(0, under_test.recoveries());
+        assert_eq!(1, under_test.failed_recoveries());
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_limit() {
+        let under_test = under_test(&[], NoRecovery);
+
+        assert_eq!(1_000, under_test.backoff_us(0));
+        assert_eq!(4_000, under_test.backoff_us(2));
+        assert_eq!(20_000, under_test.backoff_us(10));
+
+        under_test.destroy().0.done();
+    }
+
+    #[test]
+    fn clock_out_pulses_until_sda_released() {
+        let scl_expectations = [
+            //Two pulses while SDA is held low
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+            //STOP
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+        ];
+        let sda_expectations = [
+            PinTransaction::set(State::High),
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::High),
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+            PinTransaction::get(State::High),
+        ];
+
+        let mut under_test = ClockOutRecovery::new(
+            PinMock::new(&scl_expectations),
+            PinMock::new(&sda_expectations),
+            NoopDelay::new(),
+        );
+
+        assert!(under_test.clock_out());
+
+        let (mut scl, mut sda, _) = under_test.destroy();
+
+        scl.done();
+        sda.done();
+    }
+}
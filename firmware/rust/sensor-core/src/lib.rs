@@ -0,0 +1,10 @@
+#![deny(unsafe_code)]
+#![cfg_attr(not(test), no_std)]
+
+pub mod backoff;
+pub mod bus_health;
+pub mod queue;
+pub mod reading;
+pub mod scan;
+pub mod scheduler;
+pub mod sensor;
@@ -0,0 +1,554 @@
+//! Store-and-forward queue for readings taken while they can't be sent.  Readings are held in a
+//! RAM ring buffer; when that fills, the oldest move to an optional `Spill` store (SD card, flash)
+//! and, once that is full too, the `OverflowPolicy` decides what is lost.  `drain` hands readings
+//! back oldest first.
+
+use crate::reading::{ChannelId, Quantity, Reading, Unit};
+
+///Most channels a queue can spill.  Channels are stored as their index in this table.
+pub const MAX_CHANNELS: usize = 16;
+
+pub const RECORD_LEN: usize = 16;
+
+///A reading as stored by `Spill`: channel index, quantity, unit, reserved, value (LE i32),
+///timestamp (LE u64)
+pub type Record = [u8; RECORD_LEN];
+
+///Backing store for readings that don't fit in RAM.  First in, first out.
+pub trait Spill {
+    ///Appends a record.  Returns false if the store is full.
+    fn push(&mut self, record: &Record) -> bool;
+
+    ///Oldest record, without removing it
+    fn front(&mut self) -> Option<Record>;
+
+    fn pop(&mut self);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+///RAM only
+pub struct NoSpill;
+
+impl Spill for NoSpill {
+    fn push(&mut self, _record: &Record) -> bool {
+        false
+    }
+
+    fn front(&mut self) -> Option<Record> {
+        None
+    }
+
+    fn pop(&mut self) {}
+
+    fn len(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    ///Discard the oldest reading
+    DropOldest,
+    ///Discard every other reading of each quantity in RAM, halving the time resolution of the
+    ///backlog but keeping its span
+    Downsample,
+}
+
+const QUANTITIES: [Quantity; 8] = [
+    Quantity::Voltage,
+    Quantity::ShuntVoltage,
+    Quantity::Current,
+    Quantity::Power,
+    Quantity::Temperature,
+    Quantity::RelativeHumidity,
+    Quantity::Weight,
+    Quantity::RawCounts,
+];
+
+const UNITS: [Unit; 8] = [
+    Unit::Millivolt,
+    Unit::Microvolt,
+    Unit::Microamp,
+    Unit::Microwatt,
+    Unit::MilliCelsius,
+    Unit::MilliPercentRh,
+    Unit::Milligram,
+    Unit::Counts,
+];
+
+///Bounded queue of up to `N` readings in RAM, plus whatever `S` holds
+pub struct ReadingQueue<const N: usize, S = NoSpill> {
+    buffer: [Option<Reading>; N],
+    head: usize,
+    len: usize,
+    policy: OverflowPolicy,
+    spill: S,
+    channels: [Option<ChannelId>; MAX_CHANNELS],
+    dropped: u32,
+}
+
+impl<const N: usize> ReadingQueue<N, NoSpill> {
+    pub fn new(policy: OverflowPolicy) -> ReadingQueue<N, NoSpill> {
+        ReadingQueue::with_spill(policy, NoSpill, &[])
+    }
+}
+
+impl<const N: usize, S> ReadingQueue<N, S>
+where
+    S: Spill,
+{
+    ///Only readings from `channels` can be spilled; others are subject to the overflow policy.
+    ///Keep the order of `channels` stable if the spill store outlives a reboot.
+    pub fn with_spill(policy: OverflowPolicy, spill: S, channels: &[ChannelId]) -> ReadingQueue<N, S> {
+        let mut table = [None; MAX_CHANNELS];
+
+        for (entry, channel) in table.iter_mut().zip(channels) {
+            *entry = Some(*channel);
+        }
+
+        ReadingQueue {
+            buffer: [None; N],
+            head: 0,
+            len: 0,
+            policy,
+            spill,
+            channels: table,
+            dropped: 0,
+        }
+    }
+
+    ///Readings in RAM and spilled
+    pub fn len(&self) -> usize {
+        self.len + self.spill.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///Readings lost to overflow or unreadable spill records
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn spill(&mut self) -> &mut S {
+        &mut self.spill
+    }
+
+    pub fn push(&mut self, reading: Reading) {
+        if N == 0 {
+            self.dropped += 1;
+            return;
+        }
+
+        if self.len == N {
+            self.make_room();
+        }
+
+        self.buffer[(self.head + self.len) % N] = Some(reading);
+        self.len += 1;
+    }
+
+    ///Oldest reading, spilled ones first
+    pub fn front(&mut self) -> Option<Reading> {
+        while let Some(record) = self.spill.front() {
+            match self.decode(&record) {
+                Some(reading) => return Some(reading),
+                None => {
+                    self.spill.pop();
+                    self.dropped += 1;
+                }
+            }
+        }
+
+        if self.len == 0 {
+            None
+        } else {
+            self.buffer[self.head]
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Reading> {
+        let reading = self.front()?;
+
+        if !self.spill.is_empty() {
+            self.spill.pop();
+        } else {
+            self.pop_ram();
+        }
+
+        Some(reading)
+    }
+
+    ///Passes readings to `send` oldest first until it returns false, e.g. because the connection
+    ///dropped.  The reading it refused stays at the front.  Returns how many were sent.
+    pub fn drain(&mut self, mut send: impl FnMut(&Reading) -> bool) -> usize {
+        let mut sent = 0;
+
+        while let Some(reading) = self.front() {
+            if !send(&reading) {
+                break;
+            }
+
+            self.pop();
+            sent += 1;
+        }
+
+        sent
+    }
+
+    pub fn encode(&self, reading: &Reading) -> Option<Record> {
+        let channel = self
+            .channels
+            .iter()
+            .position(|channel| *channel == Some(reading.channel))?;
+        let quantity = QUANTITIES.iter().position(|quantity| *quantity == reading.quantity)?;
+        let unit = UNITS.iter().position(|unit| *unit == reading.unit)?;
+
+        let mut record = [0; RECORD_LEN];
+
+        record[0] = channel as u8;
+        record[1] = quantity as u8;
+        record[2] = unit as u8;
+        record[4..8].copy_from_slice(&reading.value.to_le_bytes());
+        record[8..16].copy_from_slice(&reading.timestamp_ms.to_le_bytes());
+
+        Some(record)
+    }
+
+    pub fn decode(&self, record: &Record) -> Option<Reading> {
+        let channel = (*self.channels.get(record[0] as usize)?)?;
+        let quantity = *QUANTITIES.get(record[1] as usize)?;
+        let unit = *UNITS.get(record[2] as usize)?;
+
+        let value = i32::from_le_bytes(record[4..8].try_into().ok()?);
+        let timestamp_ms = u64::from_le_bytes(record[8..16].try_into().ok()?);
+
+        Some(Reading::new(channel, quantity, unit, value, timestamp_ms))
+    }
+
+    fn pop_ram(&mut self) -> Option<Reading> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let reading = self.buffer[self.head].take();
+
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        reading
+    }
+
+    ///Frees at least one slot in RAM
+    fn make_room(&mut self) {
+        if let Some(oldest) = self.buffer[self.head] {
+            if let Some(record) = self.encode(&oldest) {
+                if self.spill.push(&record) {
+                    self.pop_ram();
+                    return;
+                }
+            }
+        }
+
+        if self.policy == OverflowPolicy::Downsample && self.downsample() {
+            return;
+        }
+
+        //The oldest reading overall is at the front of a full spill store
+        if !self.spill.is_empty() {
+            if let Some(record) = self.buffer[self.head].and_then(|oldest| self.encode(&oldest)) {
+                self.spill.pop();
+                self.dropped += 1;
+
+                if self.spill.push(&record) {
+                    self.pop_ram();
+                    return;
+                }
+            }
+        }
+
+        self.pop_ram();
+        self.dropped += 1;
+    }
+
+    ///Removes every second reading of each channel and key.  Returns false if nothing could be
+    ///removed, i.e. no key has two readings.
+    fn downsample(&mut self) -> bool {
+        let mut keep = [true; N];
+
+        for (index, keep) in keep.iter_mut().take(self.len).enumerate() {
+            let Some(reading) = self.buffer[(self.head + index) % N] else { continue };
+
+            //Position of this reading in its series
+            let earlier = (0..index)
+                .filter_map(|position| self.buffer[(self.head + position) % N])
+                .filter(|other| other.channel == reading.channel && other.key() == reading.key())
+                .count();
+
+            *keep = earlier % 2 == 0;
+        }
+
+        let mut kept = 0;
+
+        for (index, keep) in keep.iter().take(self.len).enumerate() {
+            let reading = self.buffer[(self.head + index) % N].take();
+
+            if *keep {
+                self.buffer[(self.head + kept) % N] = reading;
+                kept += 1;
+            }
+        }
+
+        let removed = self.len - kept;
+
+        self.len = kept;
+        self.dropped += removed as u32;
+
+        removed > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    const INA_A: ChannelId = ChannelId::new("ina237", "a");
+    const SHT_INSIDE: ChannelId = ChannelId::new("sht40", "inside");
+
+    fn current(timestamp_ms: u64) -> Reading {
+        Reading::new(INA_A, Quantity::Current, Unit::Microamp, timestamp_ms as i32, timestamp_ms)
+    }
+
+    fn humidity(timestamp_ms: u64) -> Reading {
+        Reading::new(SHT_INSIDE, Quantity::RelativeHumidity, Unit::MilliPercentRh, 45_000, timestamp_ms)
+    }
+
+    ///Accepts readings only while connected
+    struct Link {
+        connected: bool,
+        received: Vec<Reading>,
+    }
+
+    impl Link {
+        fn send(&mut self, reading: &Reading) -> bool {
+            if self.connected {
+                self.received.push(*reading);
+            }
+
+            self.connected
+        }
+    }
+
+    struct MemorySpill {
+        records: VecDeque<Record>,
+        capacity: usize,
+    }
+
+    impl Spill for MemorySpill {
+        fn push(&mut self, record: &Record) -> bool {
+            if self.records.len() == self.capacity {
+                return false;
+            }
+
+            self.records.push_back(*record);
+
+            true
+        }
+
+        fn front(&mut self) -> Option<Record> {
+            self.records.front().copied()
+        }
+
+        fn pop(&mut self) {
+            self.records.pop_front();
+        }
+
+        fn len(&self) -> usize {
+            self.records.len()
+        }
+    }
+
+    fn timestamps(readings: &[Reading]) -> Vec<u64> {
+        readings.iter().map(|reading| reading.timestamp_ms).collect()
+    }
+
+    #[test]
+    fn readings_taken_offline_drain_in_order() {
+        let mut link = Link {
+            connected: false,
+            received: Vec::new(),
+        };
+        let mut under_test = ReadingQueue::<8>::new(OverflowPolicy::DropOldest);
+
+        for ts in 0..5 {
+            under_test.push(current(ts));
+
+            assert_eq!(0, under_test.drain(|reading| link.send(reading)));
+        }
+
+        link.connected = true;
+
+        assert_eq!(5, under_test.drain(|reading| link.send(reading)));
+        assert_eq!(vec![0, 1, 2, 3, 4], timestamps(&link.received));
+        assert!(under_test.is_empty());
+    }
+
+    #[test]
+    fn drain_resumes_after_connection_drops() {
+        let mut link = Link {
+            connected: true,
+            received: Vec::new(),
+        };
+        let mut under_test = ReadingQueue::<8>::new(OverflowPolicy::DropOldest);
+
+        for ts in 0..6 {
+            under_test.push(current(ts));
+        }
+
+        //Connection drops after the third message
+        let sent = under_test.drain(|reading| {
+            link.connected = link.received.len() < 3;
+            link.send(reading)
+        });
+
+        assert_eq!(3, sent);
+        assert_eq!(3, under_test.len());
+
+        under_test.push(current(6));
+        link.connected = true;
+        under_test.drain(|reading| link.send(reading));
+
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6], timestamps(&link.received));
+    }
+
+    #[test]
+    fn drop_oldest_keeps_newest() {
+        let mut under_test = ReadingQueue::<4>::new(OverflowPolicy::DropOldest);
+
+        for ts in 0..10 {
+            under_test.push(current(ts));
+        }
+
+        let mut drained = Vec::new();
+        under_test.drain(|reading| {
+            drained.push(*reading);
+            true
+        });
+
+        assert_eq!(vec![6, 7, 8, 9], timestamps(&drained));
+        assert_eq!(6, under_test.dropped());
+    }
+
+    #[test]
+    fn downsample_thins_each_series() {
+        let mut under_test = ReadingQueue::<8>::new(OverflowPolicy::Downsample);
+
+        for ts in 0..6 {
+            under_test.push(current(ts));
+        }
+        under_test.push(humidity(0));
+        under_test.push(humidity(6));
+
+        //Full: current 0..6 becomes 0, 2, 4 and humidity 0, 6 becomes 0
+        under_test.push(current(7));
+
+        let mut drained = Vec::new();
+        under_test.drain(|reading| {
+            drained.push(*reading);
+            true
+        });
+
+        assert_eq!(
+            vec![(INA_A, 0), (INA_A, 2), (INA_A, 4), (SHT_INSIDE, 0), (INA_A, 7)],
+            drained
+                .iter()
+                .map(|reading| (reading.channel, reading.timestamp_ms))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(4, under_test.dropped());
+    }
+
+    #[test]
+    fn downsample_falls_back_to_drop_oldest() {
+        let mut under_test = ReadingQueue::<2>::new(OverflowPolicy::Downsample);
+
+        under_test.push(current(0));
+        under_test.push(humidity(1));
+        under_test.push(current(2));
+
+        assert_eq!(Some(humidity(1)), under_test.pop());
+        assert_eq!(Some(current(2)), under_test.pop());
+        assert_eq!(None, under_test.pop());
+    }
+
+    #[test]
+    fn overflow_spills_and_drains_spilled_first() {
+        let spill = MemorySpill {
+            records: VecDeque::new(),
+            capacity: 3,
+        };
+        let mut under_test =
+            ReadingQueue::<2, MemorySpill>::with_spill(OverflowPolicy::DropOldest, spill, &[INA_A]);
+
+        for ts in 0..7 {
+            under_test.push(current(ts));
+        }
+
+        //2 in RAM, 3 spilled, the 2 oldest dropped from the spill once it filled
+        assert_eq!(5, under_test.len());
+        assert_eq!(3, under_test.spill().len());
+        assert_eq!(2, under_test.dropped());
+
+        let mut drained = Vec::new();
+        under_test.drain(|reading| {
+            drained.push(*reading);
+            true
+        });
+
+        assert_eq!(vec![2, 3, 4, 5, 6], timestamps(&drained));
+        assert_eq!(current(2), drained[0]);
+    }
+
+    #[test]
+    fn unknown_spill_records_are_dropped() {
+        let spill = MemorySpill {
+            records: VecDeque::from(vec![[0xFF; RECORD_LEN]]),
+            capacity: 3,
+        };
+        let mut under_test =
+            ReadingQueue::<2, MemorySpill>::with_spill(OverflowPolicy::DropOldest, spill, &[INA_A]);
+
+        under_test.push(current(1));
+
+        assert_eq!(Some(current(1)), under_test.pop());
+        assert_eq!(1, under_test.dropped());
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let under_test = ReadingQueue::<1, MemorySpill>::with_spill(
+            OverflowPolicy::DropOldest,
+            MemorySpill {
+                records: VecDeque::new(),
+                capacity: 0,
+            },
+            &[INA_A, SHT_INSIDE],
+        );
+
+        let reading = Reading::new(SHT_INSIDE, Quantity::Temperature, Unit::MilliCelsius, -12_345, 1 << 40);
+
+        let record = under_test.encode(&reading).unwrap();
+
+        assert_eq!(1, record[0]);
+        assert_eq!(Some(reading), under_test.decode(&record));
+        assert_eq!(None, under_test.encode(&Reading::new(ChannelId::new("x", "y"), Quantity::Weight, Unit::Milligram, 0, 0)));
+    }
+}
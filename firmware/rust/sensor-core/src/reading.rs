@@ -0,0 +1,170 @@
+use core::fmt;
+
+///What a reading measures
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quantity {
+    Voltage,
+    ShuntVoltage,
+    Current,
+    Power,
+    Temperature,
+    RelativeHumidity,
+    Weight,
+    ///Unscaled ADC output, e.g. a load cell before calibration
+    RawCounts,
+}
+
+impl Quantity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantity::Voltage => "voltage",
+            Quantity::ShuntVoltage => "shunt",
+            Quantity::Current => "current",
+            Quantity::Power => "power",
+            Quantity::Temperature => "temperature",
+            Quantity::RelativeHumidity => "humidity",
+            Quantity::Weight => "weight",
+            Quantity::RawCounts => "raw",
+        }
+    }
+}
+
+///Unit of `Reading::value`.  Values are integer milli/micro units so no floating point is needed
+///on the device.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Millivolt,
+    Microvolt,
+    Microamp,
+    Microwatt,
+    ///Thousandths of a degree C
+    MilliCelsius,
+    ///Thousandths of a %RH
+    MilliPercentRh,
+    Milligram,
+    Counts,
+}
+
+impl Unit {
+    ///Short lowercase suffix, as used in the driver accessor names (`current_ua`, `temperature_mc`)
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Unit::Millivolt => "mv",
+            Unit::Microvolt => "uv",
+            Unit::Microamp => "ua",
+            Unit::Microwatt => "uw",
+            Unit::MilliCelsius => "mc",
+            Unit::MilliPercentRh => "mrh",
+            Unit::Milligram => "mg",
+            Unit::Counts => "counts",
+        }
+    }
+
+    ///Scale to the base unit, i.e. value / divisor is V, A, W, degrees C, %RH or g
+    pub fn divisor(&self) -> i32 {
+        match self {
+            Unit::Millivolt | Unit::MilliCelsius | Unit::MilliPercentRh | Unit::Milligram => 1_000,
+            Unit::Microvolt | Unit::Microamp | Unit::Microwatt => 1_000_000,
+            Unit::Counts => 1,
+        }
+    }
+}
+
+///Identifies one physical sensor, e.g. `ina237/a` or `sht40/inside`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChannelId {
+    ///Part name, lowercase
+    pub sensor: &'static str,
+    ///Distinguishes several sensors of the same part on the board
+    pub instance: &'static str,
+}
+
+impl ChannelId {
+    pub const fn new(sensor: &'static str, instance: &'static str) -> ChannelId {
+        ChannelId { sensor, instance }
+    }
+}
+
+impl fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.sensor, self.instance)
+    }
+}
+
+///Displays as `<quantity>_<unit>`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Key(pub Quantity, pub Unit);
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.0.name(), self.1.suffix())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Reading {
+    pub channel: ChannelId,
+    pub quantity: Quantity,
+    pub unit: Unit,
+    pub value: i32,
+    ///Milliseconds from the caller's monotonic clock when the reading was taken
+    pub timestamp_ms: u64,
+}
+
+impl Reading {
+    pub fn new(
+        channel: ChannelId,
+        quantity: Quantity,
+        unit: Unit,
+        value: i32,
+        timestamp_ms: u64,
+    ) -> Reading {
+        Reading {
+            channel,
+            quantity,
+            unit,
+            value,
+            timestamp_ms,
+        }
+    }
+
+    ///Quantity and unit, e.g. `current_ua`.  Names the reading within its channel.
+    pub fn key(&self) -> Key {
+        Key(self.quantity, self.unit)
+    }
+
+    ///Value in the base unit
+    pub fn value_f32(&self) -> f32 {
+        self.value as f32 / self.unit.divisor() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_id_displays_sensor_and_instance() {
+        let result = ChannelId::new("ina237", "a");
+
+        assert_eq!("ina237/a", format!("{}", result));
+    }
+
+    #[test]
+    fn value_f32_scales_to_base_unit() {
+        let channel = ChannelId::new("sht40", "inside");
+
+        let temperature = Reading::new(channel, Quantity::Temperature, Unit::MilliCelsius, 25_500, 0);
+        let current = Reading::new(channel, Quantity::Current, Unit::Microamp, -250_000, 0);
+
+        assert_eq!(25.5, temperature.value_f32());
+        assert_eq!(-0.25, current.value_f32());
+    }
+
+    #[test]
+    fn key_joins_quantity_and_unit() {
+        let reading = Reading::new(ChannelId::new("ina237", "a"), Quantity::Current, Unit::Microamp, 0, 0);
+
+        assert_eq!("current_ua", format!("{}", reading.key()));
+    }
+}
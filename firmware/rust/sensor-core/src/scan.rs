@@ -0,0 +1,293 @@
+use core::ops::RangeInclusive;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::reading::{Quantity, Unit};
+
+///Addresses probed by `scan`, skipping the reserved ranges at either end
+pub const ADDRESS_RANGE: RangeInclusive<u8> = 0x08..=0x77;
+
+///Most devices `Inventory` holds.  Further devices are counted in `Inventory::overflow`.
+pub const MAX_DEVICES: usize = 16;
+
+///Identifies the part at an address that acknowledged, or None if it isn't that part.  Each
+///driver crate's `sensor` module provides one, so identification stays with the driver.
+pub type Identify<I2C, D> = fn(&mut I2C, &mut D, u8) -> Option<Device>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Device {
+    Ina237 { manufacturer_id: u16 },
+    Nau7802 { revision: u8 },
+    Sht4x { serial_number: u32 },
+    ///Acknowledged its address but did not match a known part
+    Unknown,
+}
+
+impl Device {
+    ///Part name, as used in `ChannelId::sensor`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Device::Ina237 { .. } => "ina237",
+            Device::Nau7802 { .. } => "nau7802",
+            Device::Sht4x { .. } => "sht40",
+            Device::Unknown => "unknown",
+        }
+    }
+
+    ///What the part measures, matching its `Sensor` adapter
+    pub fn quantities(&self) -> &'static [(Quantity, Unit)] {
+        match self {
+            Device::Ina237 { .. } => &[
+                (Quantity::Voltage, Unit::Millivolt),
+                (Quantity::ShuntVoltage, Unit::Microvolt),
+                (Quantity::Current, Unit::Microamp),
+                (Quantity::Power, Unit::Microwatt),
+                (Quantity::Temperature, Unit::MilliCelsius),
+            ],
+            Device::Nau7802 { .. } => &[(Quantity::RawCounts, Unit::Counts)],
+            Device::Sht4x { .. } => &[
+                (Quantity::Temperature, Unit::MilliCelsius),
+                (Quantity::RelativeHumidity, Unit::MilliPercentRh),
+            ],
+            Device::Unknown => &[],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Found {
+    pub address: u8,
+    pub device: Device,
+}
+
+///Devices found by `scan`, in address order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    found: [Option<Found>; MAX_DEVICES],
+    len: usize,
+    ///Devices that acknowledged but did not fit
+    pub overflow: usize,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory {
+            found: [None; MAX_DEVICES],
+            len: 0,
+            overflow: 0,
+        }
+    }
+
+    pub fn push(&mut self, found: Found) {
+        if self.len < MAX_DEVICES {
+            self.found[self.len] = Some(found);
+            self.len += 1;
+        } else {
+            self.overflow += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Found> {
+        self.found[..self.len].iter().flatten()
+    }
+
+    pub fn get(&self, address: u8) -> Option<&Found> {
+        self.iter().find(|found| found.address == address)
+    }
+
+    ///Addresses of every device whose `Device::name` is `name`
+    pub fn addresses_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = u8> + 'a {
+        self.iter()
+            .filter(move |found| found.device.name() == name)
+            .map(|found| found.address)
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///Probes every address in `ADDRESS_RANGE` and identifies what answers with `identifiers`
+pub fn scan<I2C, D>(i2c: &mut I2C, delay: &mut D, identifiers: &[Identify<I2C, D>]) -> Inventory
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    scan_range(i2c, delay, ADDRESS_RANGE, identifiers)
+}
+
+pub fn scan_range<I2C, D>(
+    i2c: &mut I2C,
+    delay: &mut D,
+    addresses: RangeInclusive<u8>,
+    identifiers: &[Identify<I2C, D>],
+) -> Inventory
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    let mut inventory = Inventory::new();
+
+    for address in addresses {
+        if i2c.write(address, &[]).is_err() {
+            continue;
+        }
+
+        inventory.push(Found {
+            address,
+            device: identify(i2c, delay, address, identifiers),
+        });
+    }
+
+    inventory
+}
+
+///Tries each identifier in order.  INA237 and SHT4x share 0x44 - 0x46, so list the INA237 first:
+///an SHT4x NACKs the unknown register pointer.
+pub fn identify<I2C, D>(
+    i2c: &mut I2C,
+    delay: &mut D,
+    address: u8,
+    identifiers: &[Identify<I2C, D>],
+) -> Device
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    identifiers
+        .iter()
+        .find_map(|identify| identify(i2c, delay, address))
+        .unwrap_or(Device::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    fn nack(transaction: I2cTransaction) -> I2cTransaction {
+        transaction.with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))
+    }
+
+    ///Address probes for the whole range, with `responses` following the ACK of each listed address
+    fn expectations(present: &[(u8, Vec<I2cTransaction>)]) -> Vec<I2cTransaction> {
+        let mut result = Vec::new();
+
+        for address in ADDRESS_RANGE {
+            match present.iter().find(|(present_address, _)| *present_address == address) {
+                Some((_, responses)) => {
+                    result.push(I2cTransaction::write(address, vec![]));
+                    result.extend(responses.iter().cloned());
+                }
+                None => result.push(nack(I2cTransaction::write(address, vec![]))),
+            }
+        }
+
+        result
+    }
+
+    ///Stand-ins for the drivers' identifiers: the part answers its id register with its id
+    fn id_register(i2c: &mut I2cMock, address: u8, register: u8) -> Option<u8> {
+        let mut read_buffer = [0x00; 1];
+
+        i2c.write_read(address, &[register], &mut read_buffer).ok()?;
+
+        (read_buffer[0] == register).then_some(read_buffer[0])
+    }
+
+    fn nau7802(i2c: &mut I2cMock, _delay: &mut NoopDelay, address: u8) -> Option<Device> {
+        id_register(i2c, address, 0x0A).map(|revision| Device::Nau7802 { revision })
+    }
+
+    fn sht4x(i2c: &mut I2cMock, _delay: &mut NoopDelay, address: u8) -> Option<Device> {
+        id_register(i2c, address, 0x0B).map(|id| Device::Sht4x {
+            serial_number: id.into(),
+        })
+    }
+
+    #[test]
+    fn empty_bus_returns_empty_inventory() {
+        let mut i2c = I2cMock::new(&expectations(&[]));
+
+        let result = scan(&mut i2c, &mut NoopDelay::new(), &[nau7802, sht4x]);
+
+        assert!(result.is_empty());
+
+        i2c.done();
+    }
+
+    #[test]
+    fn identifiers_are_tried_in_order() {
+        let present = [
+            (
+                0x20,
+                vec![I2cTransaction::write_read(0x20, vec![0x0A], vec![0x0A])],
+            ),
+            (
+                0x21,
+                vec![
+                    nack(I2cTransaction::write_read(0x21, vec![0x0A], vec![0x00])),
+                    I2cTransaction::write_read(0x21, vec![0x0B], vec![0x0B]),
+                ],
+            ),
+            (
+                0x22,
+                vec![
+                    I2cTransaction::write_read(0x22, vec![0x0A], vec![0x00]),
+                    I2cTransaction::write_read(0x22, vec![0x0B], vec![0x00]),
+                ],
+            ),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations(&present));
+
+        let result = scan(&mut i2c, &mut NoopDelay::new(), &[nau7802, sht4x]);
+
+        assert_eq!(3, result.len());
+        assert_eq!(
+            Some(&Found {
+                address: 0x20,
+                device: Device::Nau7802 { revision: 0x0A }
+            }),
+            result.get(0x20)
+        );
+        assert_eq!(
+            Device::Sht4x {
+                serial_number: 0x0B
+            },
+            result.get(0x21).unwrap().device
+        );
+        assert_eq!(Device::Unknown, result.get(0x22).unwrap().device);
+        assert_eq!(vec![0x21], result.addresses_of("sht40").collect::<Vec<_>>());
+
+        i2c.done();
+    }
+
+    #[test]
+    fn inventory_counts_overflow() {
+        let mut under_test = Inventory::new();
+
+        for address in 0..(MAX_DEVICES as u8 + 2) {
+            under_test.push(Found {
+                address,
+                device: Device::Unknown,
+            });
+        }
+
+        assert_eq!(MAX_DEVICES, under_test.len());
+        assert_eq!(2, under_test.overflow);
+    }
+}
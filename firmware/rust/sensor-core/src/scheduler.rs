@@ -0,0 +1,365 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::reading::{ChannelId, Reading};
+use crate::sensor::{Error, Sensor};
+
+///Monotonic milliseconds.  Any `Fn() -> u64` works, e.g. a closure over `Instant::elapsed`.
+pub trait Clock {
+    fn now_ms(&self) -> u64;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> u64,
+{
+    fn now_ms(&self) -> u64 {
+        self()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    ///Time between readings, at least 1ms
+    pub interval_ms: u64,
+    ///How long a read takes, mostly conversion time.  Reads start this much before they are due.
+    pub latency_ms: u64,
+}
+
+impl Schedule {
+    ///An interval of 0 is raised to 1ms, so a sensor can't be due forever
+    pub const fn new(interval_ms: u64, latency_ms: u64) -> Schedule {
+        Schedule {
+            interval_ms: if interval_ms == 0 { 1 } else { interval_ms },
+            latency_ms,
+        }
+    }
+
+    ///Interval for a sample rate in Hz, clamped to 1 - 1000Hz
+    pub const fn hz(rate: u64, latency_ms: u64) -> Schedule {
+        let rate = if rate == 0 {
+            1
+        } else if rate > 1_000 {
+            1_000
+        } else {
+            rate
+        };
+
+        Schedule::new(1_000 / rate, latency_ms)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Entry {
+    schedule: Schedule,
+    due_ms: u64,
+}
+
+impl Entry {
+    fn start_ms(&self) -> u64 {
+        self.due_ms.saturating_sub(self.schedule.latency_ms)
+    }
+}
+
+///Decides which of up to `N` sensors to read next.  Sensor `i` in the slice passed to `run_due` is
+///read on the `i`th schedule added.
+pub struct Scheduler<const N: usize> {
+    entries: [Option<Entry>; N],
+    start_ms: u64,
+    skipped: u32,
+}
+
+impl<const N: usize> Scheduler<N> {
+    ///Every sensor added is first due at `start_ms`
+    pub fn new(start_ms: u64) -> Scheduler<N> {
+        Scheduler {
+            entries: [None; N],
+            start_ms,
+            skipped: 0,
+        }
+    }
+
+    ///Returns the sensor's index, or None if the scheduler is full
+    pub fn add(&mut self, schedule: Schedule) -> Option<usize> {
+        let index = self.entries.iter().position(Option::is_none)?;
+
+        self.entries[index] = Some(Entry {
+            schedule,
+            due_ms: self.start_ms,
+        });
+
+        Some(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn set_schedule(&mut self, index: usize, schedule: Schedule) {
+        if let Some(entry) = self.entries[index].as_mut() {
+            entry.schedule = schedule;
+        }
+    }
+
+    pub fn schedule(&self, index: usize) -> Option<Schedule> {
+        self.entries[index].map(|entry| entry.schedule)
+    }
+
+    ///Readings dropped because the sensor fell a whole interval behind
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+
+    ///When the next read should start
+    pub fn next_start_ms(&self) -> u64 {
+        self.entries
+            .iter()
+            .flatten()
+            .map(Entry::start_ms)
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    ///The sensor to read at `now_ms`, earliest due first, or None if nothing has started yet
+    pub fn next_due(&self, now_ms: u64) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| entry.as_ref().map(|entry| (index, entry)))
+            .filter(|(_, entry)| entry.start_ms() <= now_ms)
+            .min_by_key(|(_, entry)| entry.due_ms)
+            .map(|(index, _)| index)
+    }
+
+    ///Moves sensor `index` on to its next due time.  A sensor that has fallen behind skips the
+    ///missed readings rather than reading back to back.
+    fn complete(&mut self, index: usize, now_ms: u64) {
+        let Some(entry) = self.entries[index].as_mut() else {
+            return;
+        };
+
+        //The field is public, so a zero interval can still get here
+        let interval_ms = entry.schedule.interval_ms.max(1);
+
+        entry.due_ms += interval_ms;
+
+        if entry.start_ms() < now_ms {
+            let behind = now_ms - entry.start_ms();
+            let missed = (behind + interval_ms - 1) / interval_ms;
+
+            entry.due_ms += missed * interval_ms;
+            self.skipped += missed as u32;
+        }
+    }
+
+    ///Reads every sensor that is due, stamping readings with their due time.  Returns the ms to
+    ///wait before calling again.  `sensors` must hold one sensor per schedule added, in order.
+    pub fn run_due(
+        &mut self,
+        clock: &dyn Clock,
+        sensors: &mut [&mut dyn Sensor],
+        delay: &mut dyn DelayNs,
+        sink: &mut dyn FnMut(Reading),
+        on_error: &mut dyn FnMut(ChannelId, Error),
+    ) -> u64 {
+        loop {
+            let now_ms = clock.now_ms();
+
+            let Some(index) = self.next_due(now_ms) else {
+                return self.next_start_ms().saturating_sub(now_ms);
+            };
+
+            let due_ms = self.entries[index].map_or(now_ms, |entry| entry.due_ms.max(now_ms));
+            let sensor = &mut sensors[index];
+
+            if let Err(error) = sensor.read(due_ms, delay, sink) {
+                on_error(sensor.channel(), error);
+            }
+
+            self.complete(index, clock.now_ms());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reading::{Quantity, Unit};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct NoopDelay;
+
+    impl DelayNs for NoopDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    ///Advances the shared clock by its latency on every read
+    struct FakeSensor {
+        channel: ChannelId,
+        clock: Rc<Cell<u64>>,
+        latency_ms: u64,
+    }
+
+    impl Sensor for FakeSensor {
+        fn channel(&self) -> ChannelId {
+            self.channel
+        }
+
+        fn quantities(&self) -> &'static [(Quantity, Unit)] {
+            &[(Quantity::RawCounts, Unit::Counts)]
+        }
+
+        fn read(
+            &mut self,
+            timestamp_ms: u64,
+            _delay: &mut dyn DelayNs,
+            sink: &mut dyn FnMut(Reading),
+        ) -> Result<(), Error> {
+            self.clock.set(self.clock.get() + self.latency_ms);
+
+            sink(Reading::new(
+                self.channel,
+                Quantity::RawCounts,
+                Unit::Counts,
+                0,
+                timestamp_ms,
+            ));
+
+            Ok(())
+        }
+    }
+
+    fn sensor(instance: &'static str, clock: &Rc<Cell<u64>>, latency_ms: u64) -> FakeSensor {
+        FakeSensor {
+            channel: ChannelId::new("fake", instance),
+            clock: clock.clone(),
+            latency_ms,
+        }
+    }
+
+    #[test]
+    fn next_due_prefers_earliest_due() {
+        let mut under_test = Scheduler::<4>::new(50);
+
+        under_test.add(Schedule::new(100, 0));
+        under_test.add(Schedule::new(100, 10));
+
+        assert_eq!(40, under_test.next_start_ms());
+        assert_eq!(None, under_test.next_due(39));
+        assert_eq!(Some(1), under_test.next_due(40));
+        assert_eq!(Some(0), under_test.next_due(50));
+    }
+
+    #[test]
+    fn power_at_10hz_and_humidity_once_a_minute() {
+        let clock = Rc::new(Cell::new(0));
+        let now = {
+            let clock = clock.clone();
+            move || clock.get()
+        };
+
+        let mut power = sensor("power", &clock, 2);
+        let mut humidity = sensor("humidity", &clock, 9);
+
+        let mut under_test = Scheduler::<4>::new(0);
+
+        assert_eq!(Some(0), under_test.add(Schedule::hz(10, 2)));
+        assert_eq!(Some(1), under_test.add(Schedule::new(60_000, 9)));
+
+        let mut power_timestamps = Vec::new();
+        let mut humidity_timestamps = Vec::new();
+
+        while clock.get() < 600_000 {
+            let wait_ms = under_test.run_due(
+                &now,
+                &mut [&mut power, &mut humidity],
+                &mut NoopDelay,
+                &mut |reading| match reading.channel.instance {
+                    "power" => power_timestamps.push(reading.timestamp_ms),
+                    _ => humidity_timestamps.push(reading.timestamp_ms),
+                },
+                &mut |_, _| panic!("no errors expected"),
+            );
+
+            clock.set(clock.get() + wait_ms);
+        }
+
+        //Reads start ahead of their due time, so the ones due at 600s are already in
+        assert_eq!(6_001, power_timestamps.len());
+        assert!(power_timestamps.iter().enumerate().all(|(i, timestamp)| *timestamp == i as u64 * 100));
+        //Both are due at 0, so humidity waits for the first power read
+        assert_eq!(2, humidity_timestamps[0]);
+        assert_eq!(
+            (1..=10).map(|minute| minute * 60_000).collect::<Vec<_>>(),
+            humidity_timestamps[1..]
+        );
+        assert_eq!(0, under_test.skipped());
+    }
+
+    #[test]
+    fn zero_intervals_are_raised_to_1ms() {
+        assert_eq!(Schedule::new(1, 2), Schedule::new(0, 2));
+        assert_eq!(Schedule::new(1_000, 0), Schedule::hz(0, 0));
+        assert_eq!(Schedule::new(1, 0), Schedule::hz(5_000, 0));
+
+        let clock = Rc::new(Cell::new(0));
+        let now = {
+            let clock = clock.clone();
+            move || clock.get()
+        };
+
+        let mut instant = sensor("instant", &clock, 0);
+
+        let mut under_test = Scheduler::<1>::new(0);
+
+        under_test.add(Schedule {
+            interval_ms: 0,
+            latency_ms: 0,
+        });
+
+        let mut readings = 0;
+
+        let wait_ms = under_test.run_due(
+            &now,
+            &mut [&mut instant],
+            &mut NoopDelay,
+            &mut |_| readings += 1,
+            &mut |_, _| {},
+        );
+
+        assert_eq!(1, readings);
+        assert_eq!(1, wait_ms);
+    }
+
+    #[test]
+    fn late_sensor_skips_missed_readings() {
+        let clock = Rc::new(Cell::new(0));
+        let now = {
+            let clock = clock.clone();
+            move || clock.get()
+        };
+
+        let mut slow = sensor("slow", &clock, 250);
+
+        let mut under_test = Scheduler::<1>::new(0);
+
+        under_test.add(Schedule::new(100, 0));
+
+        assert_eq!(None, under_test.add(Schedule::new(100, 0)));
+
+        let wait_ms = under_test.run_due(
+            &now,
+            &mut [&mut slow],
+            &mut NoopDelay,
+            &mut |_| {},
+            &mut |_, _| {},
+        );
+
+        assert_eq!(2, under_test.skipped());
+        assert_eq!(50, wait_ms);
+    }
+}
@@ -0,0 +1,129 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c;
+
+use crate::reading::{ChannelId, Quantity, Reading, Unit};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Failed I2C communication.
+    I2C(i2c::ErrorKind),
+    /// A response failed its CRC check.
+    Crc,
+    /// The sensor has no new data yet.  Try again later.
+    NotReady,
+    /// Any other driver specific failure.
+    Other,
+}
+
+///A sensor the application can read without knowing which driver is behind it.  Object safe, so
+///a board can keep its sensors in a single `&mut [&mut dyn Sensor]`.
+pub trait Sensor {
+    fn channel(&self) -> ChannelId;
+
+    ///Every quantity `read` can produce, e.g. to announce the sensor before its first reading
+    fn quantities(&self) -> &'static [(Quantity, Unit)];
+
+    ///Takes one measurement and passes each resulting reading to `sink`, stamped with
+    ///`timestamp_ms`.  A sensor may produce several readings per call, e.g. voltage and current.
+    fn read(
+        &mut self,
+        timestamp_ms: u64,
+        delay: &mut dyn DelayNs,
+        sink: &mut dyn FnMut(Reading),
+    ) -> Result<(), Error>;
+}
+
+///Reads every sensor in turn.  A failing sensor does not stop the others; its error is passed to
+///`on_error` along with its channel.
+pub fn read_all(
+    sensors: &mut [&mut dyn Sensor],
+    timestamp_ms: u64,
+    delay: &mut dyn DelayNs,
+    sink: &mut dyn FnMut(Reading),
+    on_error: &mut dyn FnMut(ChannelId, Error),
+) {
+    for sensor in sensors.iter_mut() {
+        if let Err(error) = sensor.read(timestamp_ms, delay, sink) {
+            on_error(sensor.channel(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopDelay;
+
+    impl DelayNs for NoopDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    struct FakeSensor {
+        channel: ChannelId,
+        result: Result<i32, Error>,
+    }
+
+    impl Sensor for FakeSensor {
+        fn channel(&self) -> ChannelId {
+            self.channel
+        }
+
+        fn quantities(&self) -> &'static [(Quantity, Unit)] {
+            &[(Quantity::Temperature, Unit::MilliCelsius)]
+        }
+
+        fn read(
+            &mut self,
+            timestamp_ms: u64,
+            _delay: &mut dyn DelayNs,
+            sink: &mut dyn FnMut(Reading),
+        ) -> Result<(), Error> {
+            let value = self.result?;
+
+            sink(Reading::new(
+                self.channel,
+                Quantity::Temperature,
+                Unit::MilliCelsius,
+                value,
+                timestamp_ms,
+            ));
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_all_collects_readings_and_reports_failures() {
+        let mut good = FakeSensor {
+            channel: ChannelId::new("fake", "good"),
+            result: Ok(21_000),
+        };
+        let mut bad = FakeSensor {
+            channel: ChannelId::new("fake", "bad"),
+            result: Err(Error::Crc),
+        };
+        let mut other = FakeSensor {
+            channel: ChannelId::new("fake", "other"),
+            result: Ok(-5_000),
+        };
+
+        let mut readings = Vec::new();
+        let mut errors = Vec::new();
+
+        read_all(
+            &mut [&mut good, &mut bad, &mut other],
+            1_234,
+            &mut NoopDelay,
+            &mut |reading| readings.push(reading),
+            &mut |channel, error| errors.push((channel, error)),
+        );
+
+        assert_eq!(2, readings.len());
+        assert_eq!(21_000, readings[0].value);
+        assert_eq!(1_234, readings[0].timestamp_ms);
+        assert_eq!("other", readings[1].channel.instance);
+        assert_eq!(vec![(ChannelId::new("fake", "bad"), Error::Crc)], errors);
+    }
+}
@@ -0,0 +1,299 @@
+//! Bench API and dashboard:
+//!
+//! - `GET /` a page showing the latest readings and sensor health, refreshed every few seconds
+//! - `GET /api/readings` the latest value of every channel and quantity
+//! - `GET /api/sensors` every sensor with its quantities and health
+//! - `GET /api/config` the stored config, without secrets, see `settings`
+//! - `PUT /api/config` updates and stores the config; it takes effect at the next restart
+
+use std::fmt::{Debug, Write};
+use std::sync::{Arc, Mutex};
+
+use device_config::config::DeviceConfig;
+use device_config::storage::Storage;
+use device_config::store::{self, ConfigStore};
+use sensor_core::reading::Unit;
+use telemetry::json::{self, push_string};
+
+use crate::http::{Method, Request, Response};
+use crate::router::Router;
+use crate::server::Handler;
+use crate::settings;
+use crate::status::Status;
+
+///The firmware as seen by the API
+pub trait Device {
+    type Error: Debug;
+
+    fn status(&mut self) -> Status;
+
+    fn load_config(&mut self) -> Result<DeviceConfig, Self::Error>;
+
+    fn save_config(&mut self, config: &DeviceConfig) -> Result<(), Self::Error>;
+}
+
+///A `Device` over state shared with the firmware's main loop, for serving from another thread
+pub struct SharedDevice<S> {
+    status: Arc<Mutex<Status>>,
+    store: Arc<Mutex<ConfigStore<S>>>,
+}
+
+impl<S> SharedDevice<S> {
+    pub fn new(status: Arc<Mutex<Status>>, store: Arc<Mutex<ConfigStore<S>>>) -> SharedDevice<S> {
+        SharedDevice { status, store }
+    }
+}
+
+impl<S> Device for SharedDevice<S>
+where
+    S: Storage,
+    S::Error: Debug,
+{
+    type Error = store::Error<S::Error>;
+
+    fn status(&mut self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn load_config(&mut self) -> Result<DeviceConfig, Self::Error> {
+        self.store.lock().unwrap().load().map(|(config, _)| config)
+    }
+
+    fn save_config(&mut self, config: &DeviceConfig) -> Result<(), Self::Error> {
+        self.store.lock().unwrap().save(config)
+    }
+}
+
+pub struct Api<D> {
+    device: D,
+    router: Router<D>,
+}
+
+impl<D> Api<D>
+where
+    D: Device,
+{
+    pub fn new(device: D) -> Api<D> {
+        Api {
+            device,
+            router: Router::new()
+                .route(Method::Get, "/", |_, _| Response::html(DASHBOARD))
+                .route(Method::Get, "/api/readings", readings::<D>)
+                .route(Method::Get, "/api/sensors", sensors::<D>)
+                .route(Method::Get, "/api/config", get_config::<D>)
+                .route(Method::Put, "/api/config", put_config::<D>),
+        }
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+}
+
+impl<D> Handler for Api<D>
+where
+    D: Device,
+{
+    fn handle(&mut self, request: &Request) -> Response {
+        self.router.dispatch(&mut self.device, request)
+    }
+}
+
+fn readings<D: Device>(device: &mut D, _request: &Request) -> Response {
+    let status = device.status();
+
+    let mut output = String::from("{\"readings\":[");
+
+    for (index, reading) in status.readings().iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+
+        let _ = write!(
+            output,
+            "{{\"channel\":\"{}\",\"key\":\"{}\",\"value\":{},\"scaled\":{},\"unit\":\"{}\",\"timestamp_ms\":{}}}",
+            reading.channel,
+            reading.key(),
+            reading.value,
+            reading.value as f64 / reading.unit.divisor() as f64,
+            base_unit(reading.unit),
+            reading.timestamp_ms
+        );
+    }
+
+    output.push_str("]}");
+
+    Response::json(output)
+}
+
+fn sensors<D: Device>(device: &mut D, _request: &Request) -> Response {
+    let status = device.status();
+
+    let mut output = String::from("{\"sensors\":[");
+
+    for (index, sensor) in status.sensors().iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+
+        let _ = write!(output, "{{\"channel\":\"{}\",\"keys\":[", sensor.channel);
+
+        for (index, (quantity, unit)) in sensor.quantities.iter().enumerate() {
+            if index > 0 {
+                output.push(',');
+            }
+
+            let _ = write!(output, "\"{}_{}\"", quantity.name(), unit.suffix());
+        }
+
+        let _ = write!(
+            output,
+            "],\"health\":\"{}\",\"last_reading_ms\":",
+            sensor.health().name()
+        );
+
+        match sensor.last_reading_ms {
+            Some(timestamp_ms) => {
+                let _ = write!(output, "{}", timestamp_ms);
+            }
+            None => output.push_str("null"),
+        }
+
+        let _ = write!(
+            output,
+            ",\"consecutive_failures\":{},\"total_failures\":{},\"last_error\":",
+            sensor.consecutive_failures, sensor.total_failures
+        );
+
+        match sensor.last_error {
+            Some(error) => push_string(&mut output, &format!("{:?}", error)),
+            None => output.push_str("null"),
+        }
+
+        output.push('}');
+    }
+
+    output.push_str("]}");
+
+    Response::json(output)
+}
+
+fn get_config<D: Device>(device: &mut D, _request: &Request) -> Response {
+    match device.load_config() {
+        Ok(config) => Response::json(settings::encode(&config)),
+        Err(_) => error(500, "config unreadable", None),
+    }
+}
+
+fn put_config<D: Device>(device: &mut D, request: &Request) -> Response {
+    let Some(update) = std::str::from_utf8(&request.body)
+        .ok()
+        .and_then(json::parse)
+    else {
+        return error(400, "invalid JSON", None);
+    };
+
+    let Ok(mut config) = device.load_config() else {
+        return error(500, "config unreadable", None);
+    };
+
+    if let Err(rejected) = settings::update(&mut config, &update) {
+        return error(422, rejected.message, Some(&rejected.field));
+    }
+
+    match device.save_config(&config) {
+        Ok(()) => Response::json(settings::encode(&config)),
+        Err(_) => error(500, "config not saved", None),
+    }
+}
+
+///`{"error":"<message>","field":"<field>"}`
+fn error(status: u16, message: &str, field: Option<&str>) -> Response {
+    let mut output = String::from("{\"error\":");
+    push_string(&mut output, message);
+
+    if let Some(field) = field {
+        output.push_str(",\"field\":");
+        push_string(&mut output, field);
+    }
+
+    output.push('}');
+
+    Response::json(output).with_status(status)
+}
+
+///Unit of the scaled value
+fn base_unit(unit: Unit) -> &'static str {
+    match unit {
+        Unit::Millivolt | Unit::Microvolt => "V",
+        Unit::Microamp => "A",
+        Unit::Microwatt => "W",
+        Unit::MilliCelsius => "°C",
+        Unit::MilliPercentRh => "%RH",
+        Unit::Milligram => "g",
+        Unit::Counts => "",
+    }
+}
+
+const DASHBOARD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Sensor board</title>
+<style>
+body{font-family:sans-serif;max-width:40em;margin:1em auto;padding:0 1em}
+table{border-collapse:collapse;width:100%;margin-bottom:1.5em}
+td,th{text-align:left;padding:.2em .5em;border-bottom:1px solid #ddd}
+td.value{text-align:right;font-variant-numeric:tabular-nums}
+.ok{color:#070}.waiting{color:#888}.failing{color:#b00}
+</style>
+</head>
+<body>
+<h1>Sensor board</h1>
+<h2>Readings</h2>
+<table><thead><tr><th>Channel</th><th>Reading</th><th>Value</th><th></th></tr></thead><tbody id="readings"></tbody></table>
+<h2>Sensors</h2>
+<table><thead><tr><th>Channel</th><th>Health</th><th>Failures</th><th>Last error</th></tr></thead><tbody id="sensors"></tbody></table>
+<p><a href="/api/config">Config</a></p>
+<script>
+function cell(row, text, className) {
+  const td = row.insertCell();
+  td.textContent = text;
+  if (className) td.className = className;
+}
+
+async function refresh() {
+  try {
+    const readings = (await (await fetch("/api/readings")).json()).readings;
+    const readingRows = document.getElementById("readings");
+    readingRows.replaceChildren();
+    for (const reading of readings) {
+      const row = readingRows.insertRow();
+      cell(row, reading.channel);
+      cell(row, reading.key);
+      cell(row, reading.scaled, "value");
+      cell(row, reading.unit);
+    }
+
+    const sensors = (await (await fetch("/api/sensors")).json()).sensors;
+    const sensorRows = document.getElementById("sensors");
+    sensorRows.replaceChildren();
+    for (const sensor of sensors) {
+      const row = sensorRows.insertRow();
+      cell(row, sensor.channel);
+      cell(row, sensor.health, sensor.health);
+      cell(row, sensor.consecutive_failures + " / " + sensor.total_failures);
+      cell(row, sensor.last_error || "");
+    }
+  } catch (error) {
+    console.log(error);
+  }
+}
+
+refresh();
+setInterval(refresh, 2000);
+</script>
+</body>
+</html>
+"#;
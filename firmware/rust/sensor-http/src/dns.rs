@@ -0,0 +1,145 @@
+//! The DNS half of a captive portal: every name resolves to the access point, so phones and
+//! laptops that join the setup network open the setup page on their own.
+
+///Port the responder listens on
+pub const PORT: u16 = 53;
+
+///Seconds clients may cache an answer
+pub const TTL: u32 = 60;
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+///Builds the response to `query`, answering A (and ANY) questions with `address` and anything
+///else with no records.  Returns None for packets that aren't a single standard query.
+pub fn answer(query: &[u8], address: [u8; 4]) -> Option<Vec<u8>> {
+    if query.len() < HEADER_LEN {
+        return None;
+    }
+
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    let questions = u16::from_be_bytes([query[4], query[5]]);
+
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0x0F;
+
+    if is_response || opcode != 0 || questions != 1 {
+        return None;
+    }
+
+    let question_end = question_end(query)?;
+
+    let qtype = u16::from_be_bytes([query[question_end - 4], query[question_end - 3]]);
+    let qclass = u16::from_be_bytes([query[question_end - 2], query[question_end - 1]]);
+
+    let answered = (qtype == TYPE_A || qtype == TYPE_ANY) && qclass == CLASS_IN;
+
+    let mut response = Vec::with_capacity(question_end + 16);
+
+    //Header: same id, response + authoritative + recursion available, RD copied from the query
+    response.extend_from_slice(&query[0..2]);
+    response.extend_from_slice(&(0x8480 | (flags & 0x0100)).to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&(answered as u16).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+
+    response.extend_from_slice(&query[HEADER_LEN..question_end]);
+
+    if answered {
+        //Name is a pointer back to the question
+        response.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+        response.extend_from_slice(&TYPE_A.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&TTL.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&address);
+    }
+
+    Some(response)
+}
+
+///Offset just past the first question's type and class
+fn question_end(query: &[u8]) -> Option<usize> {
+    let mut offset = HEADER_LEN;
+
+    loop {
+        let len = *query.get(offset)? as usize;
+
+        //Compression isn't used in questions of a query
+        if len & 0xC0 != 0 {
+            return None;
+        }
+
+        offset += 1 + len;
+
+        if len == 0 {
+            break;
+        }
+    }
+
+    let end = offset + 4;
+
+    (end <= query.len()).then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+
+        for label in name.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+
+        query.push(0);
+        query.extend_from_slice(&qtype.to_be_bytes());
+        query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        query
+    }
+
+    #[test]
+    fn a_query_resolves_to_the_portal() {
+        let query = query("connectivitycheck.gstatic.com", TYPE_A);
+
+        let response = answer(&query, [192, 168, 71, 1]).unwrap();
+
+        assert_eq!(
+            &[0x12, 0x34, 0x85, 0x80, 0, 1, 0, 1, 0, 0, 0, 0],
+            &response[..12]
+        );
+        assert_eq!(&query[12..], &response[12..query.len()]);
+        assert_eq!(
+            &[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 168, 71, 1],
+            &response[query.len()..]
+        );
+    }
+
+    #[test]
+    fn other_types_get_no_records() {
+        let query = query("example.com", 28);
+
+        let response = answer(&query, [192, 168, 71, 1]).unwrap();
+
+        assert_eq!(&[0, 0], &response[6..8]);
+        assert_eq!(query.len(), response.len());
+    }
+
+    #[test]
+    fn garbage_is_ignored() {
+        assert_eq!(None, answer(&[0; 5], [192, 168, 71, 1]));
+
+        let mut truncated = query("example.com", TYPE_A);
+        truncated.truncate(truncated.len() - 2);
+        assert_eq!(None, answer(&truncated, [192, 168, 71, 1]));
+
+        let mut response = query("example.com", TYPE_A);
+        response[2] |= 0x80;
+        assert_eq!(None, answer(&response, [192, 168, 71, 1]));
+    }
+}
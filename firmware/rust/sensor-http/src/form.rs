@@ -0,0 +1,351 @@
+use device_config::config::{DeviceConfig, Network, MAX_NETWORKS};
+
+use crate::http::parse_form;
+
+///Longest SSID 802.11 allows, in bytes
+pub const MAX_SSID_LEN: usize = 32;
+///Longest device name; it ends up in topics and entity ids
+pub const MAX_DEVICE_NAME_LEN: usize = 32;
+///Longest string the config codec stores
+pub const MAX_TEXT_LEN: usize = 255;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    Ssid,
+    Psk,
+    MqttUrl,
+    MqttUser,
+    MqttPassword,
+    DeviceName,
+}
+
+impl Field {
+    ///Form field name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Ssid => "ssid",
+            Field::Psk => "psk",
+            Field::MqttUrl => "mqtt_url",
+            Field::MqttUser => "mqtt_user",
+            Field::MqttPassword => "mqtt_password",
+            Field::DeviceName => "device_name",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: Field,
+    pub message: &'static str,
+}
+
+///What the setup page submits
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SetupForm {
+    pub ssid: String,
+    ///Empty for an open network, or to keep the stored one for a known network
+    pub psk: String,
+    pub mqtt_url: String,
+    pub mqtt_user: String,
+    ///Empty to keep the stored one
+    pub mqtt_password: String,
+    pub device_name: String,
+}
+
+impl SetupForm {
+    ///Prefilled from the current config.  Secrets are never sent back to the browser.
+    pub fn from_config(config: &DeviceConfig) -> SetupForm {
+        SetupForm {
+            ssid: config
+                .network
+                .networks
+                .first()
+                .map(|network| network.ssid.clone())
+                .unwrap_or_default(),
+            psk: String::new(),
+            mqtt_url: config.mqtt.url.clone(),
+            mqtt_user: config.mqtt.user.clone(),
+            mqtt_password: String::new(),
+            device_name: config.device_id.clone(),
+        }
+    }
+
+    ///Parses an urlencoded body.  Unknown fields are ignored and missing ones left empty.
+    pub fn parse(body: &[u8]) -> SetupForm {
+        let mut form = SetupForm::default();
+
+        for (name, value) in parse_form(body) {
+            match name.as_str() {
+                "ssid" => form.ssid = value,
+                "psk" => form.psk = value,
+                "mqtt_url" => form.mqtt_url = value.trim().to_string(),
+                "mqtt_user" => form.mqtt_user = value,
+                "mqtt_password" => form.mqtt_password = value,
+                "device_name" => form.device_name = value.trim().to_string(),
+                _ => {}
+            }
+        }
+
+        form
+    }
+
+    ///Every problem with the form, empty when it can be applied
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        let mut check = |field, ok: bool, message| {
+            if !ok {
+                errors.push(FieldError { field, message });
+            }
+        };
+
+        check(Field::Ssid, !self.ssid.is_empty(), "Choose a network");
+        check(
+            Field::Ssid,
+            self.ssid.len() <= MAX_SSID_LEN,
+            "Network names are at most 32 bytes",
+        );
+
+        check(
+            Field::Psk,
+            valid_psk(&self.psk),
+            "Passwords are 8 to 63 characters, or 64 hex digits",
+        );
+
+        check(
+            Field::MqttUrl,
+            valid_broker(&self.mqtt_url),
+            "Use mqtt://host[:port] or mqtts://host[:port]",
+        );
+
+        check(
+            Field::MqttUser,
+            self.mqtt_user.len() <= MAX_TEXT_LEN,
+            "Too long",
+        );
+        check(
+            Field::MqttPassword,
+            self.mqtt_password.len() <= MAX_TEXT_LEN,
+            "Too long",
+        );
+
+        check(
+            Field::DeviceName,
+            valid_device_name(&self.device_name),
+            "Use 1 to 32 letters, digits, '-' or '_'",
+        );
+
+        errors
+    }
+
+    ///Writes the form into `config`.  The network becomes the most preferred one, replacing any
+    ///entry with the same SSID; the least preferred is dropped if the list is full.
+    pub fn apply(&self, config: &mut DeviceConfig) {
+        let networks = &mut config.network.networks;
+
+        let existing = networks
+            .iter()
+            .position(|network| network.ssid == self.ssid)
+            .map(|index| networks.remove(index));
+
+        let psk = match existing {
+            Some(existing) if self.psk.is_empty() => existing.psk,
+            _ => self.psk.clone(),
+        };
+
+        networks.insert(
+            0,
+            Network {
+                ssid: self.ssid.clone(),
+                psk,
+            },
+        );
+        networks.truncate(MAX_NETWORKS);
+
+        config.mqtt.url = self.mqtt_url.clone();
+        config.mqtt.user = self.mqtt_user.clone();
+
+        if !self.mqtt_password.is_empty() {
+            config.mqtt.password = self.mqtt_password.clone();
+        }
+
+        config.device_id = self.device_name.clone();
+    }
+}
+
+pub(crate) fn valid_psk(psk: &str) -> bool {
+    match psk.len() {
+        0 => true,
+        8..=63 => psk.chars().all(|c| c.is_ascii() && !c.is_ascii_control()),
+        64 => psk.chars().all(|c| c.is_ascii_hexdigit()),
+        _ => false,
+    }
+}
+
+pub(crate) fn valid_broker(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("mqtt://")
+        .or_else(|| url.strip_prefix("mqtts://"))
+    else {
+        return false;
+    };
+
+    let authority = rest.split('/').next().unwrap_or_default();
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+
+    url.len() <= MAX_TEXT_LEN
+        && !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+        && port.is_none_or(|port| port.parse::<u16>().is_ok_and(|port| port != 0))
+}
+
+pub(crate) fn valid_device_name(name: &str) -> bool {
+    (1..=MAX_DEVICE_NAME_LEN).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form() -> SetupForm {
+        SetupForm {
+            ssid: "Shed".to_string(),
+            psk: "hunter22".to_string(),
+            mqtt_url: "mqtt://broker.local:1883".to_string(),
+            mqtt_user: "sensor".to_string(),
+            mqtt_password: "secret".to_string(),
+            device_name: "greenhouse-1".to_string(),
+        }
+    }
+
+    fn fields(form: &SetupForm) -> Vec<Field> {
+        form.validate().iter().map(|error| error.field).collect()
+    }
+
+    #[test]
+    fn body_is_parsed() {
+        let body =
+            b"ssid=Shed&psk=hunter22&mqtt_url=+mqtt%3A%2F%2Fbroker.local%3A1883+&mqtt_user=sensor\
+                     &mqtt_password=secret&device_name=greenhouse-1&submit=Save";
+
+        assert_eq!(form(), SetupForm::parse(body));
+    }
+
+    #[test]
+    fn valid_form_has_no_errors() {
+        assert_eq!(Vec::<FieldError>::new(), form().validate());
+
+        let open = SetupForm {
+            psk: String::new(),
+            mqtt_url: "mqtts://10.0.0.2".to_string(),
+            ..form()
+        };
+
+        assert_eq!(Vec::<Field>::new(), fields(&open));
+    }
+
+    #[test]
+    fn bad_fields_are_reported() {
+        let form = SetupForm {
+            ssid: "x".repeat(33),
+            psk: "short".to_string(),
+            mqtt_url: "http://broker.local".to_string(),
+            mqtt_user: String::new(),
+            mqtt_password: String::new(),
+            device_name: "green house".to_string(),
+        };
+
+        assert_eq!(
+            vec![Field::Ssid, Field::Psk, Field::MqttUrl, Field::DeviceName],
+            fields(&form)
+        );
+    }
+
+    #[test]
+    fn broker_urls() {
+        assert!(valid_broker("mqtt://broker.local"));
+        assert!(valid_broker("mqtt://192.168.1.10:1883"));
+        assert!(!valid_broker("mqtt://"));
+        assert!(!valid_broker("mqtt://broker.local:0"));
+        assert!(!valid_broker("mqtt://broker.local:http"));
+        assert!(!valid_broker("mqtt://bro ker"));
+        assert!(!valid_broker("broker.local"));
+    }
+
+    #[test]
+    fn psks() {
+        assert!(valid_psk(""));
+        assert!(valid_psk("12345678"));
+        assert!(valid_psk(&"a".repeat(63)));
+        assert!(valid_psk(&"0f".repeat(32)));
+        assert!(!valid_psk("1234567"));
+        assert!(!valid_psk(&"g".repeat(64)));
+        assert!(!valid_psk("pass\u{7}word"));
+    }
+
+    #[test]
+    fn apply_prefers_the_new_network() {
+        let mut config = DeviceConfig::default();
+
+        config.network.networks = vec![
+            Network {
+                ssid: "House".to_string(),
+                psk: "house-psk".to_string(),
+            },
+            Network {
+                ssid: "Shed".to_string(),
+                psk: "old-psk!".to_string(),
+            },
+        ];
+        config.mqtt.password = "kept".to_string();
+
+        SetupForm {
+            psk: String::new(),
+            mqtt_password: String::new(),
+            ..form()
+        }
+        .apply(&mut config);
+
+        assert_eq!(
+            vec!["Shed", "House"],
+            config
+                .network
+                .networks
+                .iter()
+                .map(|network| network.ssid.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("old-psk!", config.network.networks[0].psk);
+        assert_eq!("kept", config.mqtt.password);
+        assert_eq!("mqtt://broker.local:1883", config.mqtt.url);
+        assert_eq!("greenhouse-1", config.device_id);
+        assert!(config.is_provisioned());
+    }
+
+    #[test]
+    fn apply_keeps_the_list_bounded() {
+        let mut config = DeviceConfig::default();
+
+        for index in 0..MAX_NETWORKS {
+            config.network.networks.push(Network {
+                ssid: format!("net{}", index),
+                psk: String::new(),
+            });
+        }
+
+        form().apply(&mut config);
+
+        assert_eq!(MAX_NETWORKS, config.network.networks.len());
+        assert_eq!("Shed", config.network.networks[0].ssid);
+        assert_eq!("hunter22", config.network.networks[0].psk);
+    }
+}
@@ -0,0 +1,333 @@
+//! Just enough HTTP/1.1 for a handful of small pages: one request per connection, bodies sized by
+//! `Content-Length`, no chunked encoding.
+
+use std::io::{self, Read, Write};
+
+///Largest request line plus headers accepted
+pub const MAX_HEAD: usize = 4096;
+///Largest body accepted
+pub const MAX_BODY: usize = 4096;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    ///Path without the query, e.g. `/save`
+    pub path: String,
+    ///Everything after `?`, still encoded
+    pub query: String,
+    ///(name, value), names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(method: Method, target: &str, body: &[u8]) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        Request {
+            method,
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: Vec::new(),
+            body: body.to_vec(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    ///Reads one request
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Request, ReadError> {
+        let mut head = Vec::new();
+        let mut byte = [0; 1];
+
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_HEAD {
+                return Err(ReadError::TooLarge);
+            }
+
+            if reader.read(&mut byte)? == 0 {
+                return Err(ReadError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+
+            head.push(byte[0]);
+        }
+
+        let head = std::str::from_utf8(&head).map_err(|_| ReadError::Malformed)?;
+        let mut lines = head.split("\r\n");
+
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+
+        let method = match request_line.next() {
+            Some("GET") => Method::Get,
+            Some("POST") => Method::Post,
+            Some("PUT") => Method::Put,
+            Some(_) => Method::Other,
+            None => return Err(ReadError::Malformed),
+        };
+
+        let target = request_line.next().ok_or(ReadError::Malformed)?;
+
+        let mut request = Request::new(method, target, &[]);
+
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or(ReadError::Malformed)?;
+
+            request
+                .headers
+                .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        let length = match request.header("content-length") {
+            Some(length) => length.parse::<usize>().map_err(|_| ReadError::Malformed)?,
+            None => 0,
+        };
+
+        if length > MAX_BODY {
+            return Err(ReadError::TooLarge);
+        }
+
+        request.body = vec![0; length];
+        reader
+            .read_exact(&mut request.body)
+            .map_err(ReadError::Io)?;
+
+        Ok(request)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    ///Not something this parser understands
+    Malformed,
+    ///Over `MAX_HEAD` or `MAX_BODY`
+    TooLarge,
+}
+
+impl From<io::Error> for ReadError {
+    fn from(value: io::Error) -> Self {
+        ReadError::Io(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn html(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200, "text/html; charset=utf-8", body)
+    }
+
+    pub fn json(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200, "application/json", body)
+    }
+
+    pub fn redirect(location: &str) -> Response {
+        Response::new(302, "text/plain", "").with_header("Location", location)
+    }
+
+    pub fn error(status: u16) -> Response {
+        Response::new(status, "text/plain", reason(status))
+    }
+
+    pub fn with_status(mut self, status: u16) -> Response {
+        self.status = status;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason(self.status)
+        )?;
+
+        for (name, value) in &self.headers {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+
+        write!(
+            writer,
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        )?;
+
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+///Decodes `application/x-www-form-urlencoded` into (name, value) pairs
+pub fn parse_form(body: &[u8]) -> Vec<(String, String)> {
+    body.split(|byte| *byte == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, |byte| *byte == b'=');
+            let name = parts.next().unwrap_or_default();
+            let value = parts.next().unwrap_or_default();
+
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect()
+}
+
+///`+` is a space and `%XX` a byte; invalid escapes are kept as they are
+pub fn percent_decode(text: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut index = 0;
+
+    while index < text.len() {
+        match text[index] {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let escaped = text
+                    .get(index + 1..index + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+                match escaped {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        index += 2;
+                    }
+                    None => bytes.push(b'%'),
+                }
+            }
+            byte => bytes.push(byte),
+        }
+
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+///Escapes text for HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_is_parsed() {
+        let raw =
+            b"POST /save?x=1 HTTP/1.1\r\nHost: 192.168.71.1\r\nContent-Length: 7\r\n\r\nssid=ab";
+
+        let request = Request::read_from(&mut &raw[..]).unwrap();
+
+        assert_eq!(Method::Post, request.method);
+        assert_eq!("/save", request.path);
+        assert_eq!("x=1", request.query);
+        assert_eq!(Some("192.168.71.1"), request.header("HOST"));
+        assert_eq!(b"ssid=ab".to_vec(), request.body);
+    }
+
+    #[test]
+    fn oversized_body_is_rejected() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 100000\r\n\r\n";
+
+        assert!(matches!(
+            Request::read_from(&mut &raw[..]),
+            Err(ReadError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn response_is_written() {
+        let mut output = Vec::new();
+
+        Response::redirect("/").write_to(&mut output).unwrap();
+
+        assert_eq!(
+            "HTTP/1.1 302 Found\r\nContent-Type: text/plain\r\nLocation: /\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn form_is_decoded() {
+        assert_eq!(
+            vec![
+                ("ssid".to_string(), "My Net&Co".to_string()),
+                ("psk".to_string(), "100%".to_string()),
+                ("empty".to_string(), String::new()),
+            ],
+            parse_form(b"ssid=My+Net%26Co&psk=100%25&empty=")
+        );
+        assert_eq!("%zz é", percent_decode(b"%zz+%C3%A9"));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!("&lt;b&gt; &amp; &quot;x&quot;", escape_html("<b> & \"x\""));
+    }
+}
@@ -0,0 +1,11 @@
+#![deny(unsafe_code)]
+
+pub mod api;
+pub mod dns;
+pub mod form;
+pub mod http;
+pub mod portal;
+pub mod router;
+pub mod server;
+pub mod settings;
+pub mod status;
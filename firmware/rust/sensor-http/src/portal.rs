@@ -0,0 +1,221 @@
+use device_config::config::DeviceConfig;
+use device_config::storage::Storage;
+use device_config::store::ConfigStore;
+
+use crate::form::{Field, FieldError, SetupForm};
+use crate::http::{escape_html, Method, Request, Response};
+use crate::server::Handler;
+
+///A network found by a scan
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannedNetwork {
+    pub ssid: String,
+    ///dBm
+    pub rssi: i8,
+}
+
+///The provisioning web app: `GET /` serves the setup form, `POST /save` validates it and writes
+///the config.  Any other page redirects to the form, which is what makes phones pop it up as a
+///captive portal.
+pub struct Portal<S> {
+    store: ConfigStore<S>,
+    config: DeviceConfig,
+    networks: Vec<ScannedNetwork>,
+    saved: bool,
+}
+
+impl<S> Portal<S>
+where
+    S: Storage,
+{
+    ///`config` is the one currently stored, used to prefill the form and as the base for changes
+    pub fn new(store: ConfigStore<S>, config: DeviceConfig) -> Portal<S> {
+        Portal {
+            store,
+            config,
+            networks: Vec::new(),
+            saved: false,
+        }
+    }
+
+    ///Replaces the scan results offered on the form, strongest first, one entry per SSID
+    pub fn set_networks(&mut self, mut networks: Vec<ScannedNetwork>) {
+        networks.retain(|network| !network.ssid.is_empty());
+        networks.sort_by_key(|network| std::cmp::Reverse(network.rssi));
+
+        let mut seen = Vec::new();
+        networks.retain(|network| {
+            let first = !seen.contains(&network.ssid);
+            seen.push(network.ssid.clone());
+            first
+        });
+
+        self.networks = networks;
+    }
+
+    pub fn networks(&self) -> &[ScannedNetwork] {
+        &self.networks
+    }
+
+    pub fn config(&self) -> &DeviceConfig {
+        &self.config
+    }
+
+    ///True once a valid form has been written to the store
+    pub fn is_saved(&self) -> bool {
+        self.saved
+    }
+
+    pub fn destroy(self) -> ConfigStore<S> {
+        self.store
+    }
+
+    fn save(&mut self, request: &Request) -> Response {
+        let form = SetupForm::parse(&request.body);
+        let errors = form.validate();
+
+        if !errors.is_empty() {
+            return Response::html(self.page(&form, &errors)).with_status(422);
+        }
+
+        let mut config = self.config.clone();
+        form.apply(&mut config);
+
+        if self.store.save(&config).is_err() {
+            return Response::html(message_page(
+                "Not saved",
+                "The settings couldn't be written.  Try again, or power cycle the board.",
+            ))
+            .with_status(500);
+        }
+
+        self.config = config;
+        self.saved = true;
+
+        Response::html(message_page(
+            "Saved",
+            &format!(
+                "The board will restart and join <b>{}</b>.",
+                escape_html(&form.ssid)
+            ),
+        ))
+    }
+
+    fn page(&self, form: &SetupForm, errors: &[FieldError]) -> String {
+        let mut page = String::new();
+
+        page.push_str(HEAD);
+        page.push_str("<h1>Sensor board setup</h1>\n<form method=\"post\" action=\"/save\">\n");
+
+        page.push_str("<datalist id=\"networks\">\n");
+        for network in &self.networks {
+            page.push_str(&format!(
+                "<option value=\"{}\">{} dBm</option>\n",
+                escape_html(&network.ssid),
+                network.rssi
+            ));
+        }
+        page.push_str("</datalist>\n");
+
+        let field =
+            |page: &mut String, field: Field, label: &str, kind: &str, value: &str, extra: &str| {
+                page.push_str(&format!(
+                    "<label>{}<input name=\"{}\" type=\"{}\" value=\"{}\"{}></label>\n",
+                    label,
+                    field.name(),
+                    kind,
+                    escape_html(value),
+                    extra
+                ));
+
+                for error in errors.iter().filter(|error| error.field == field) {
+                    page.push_str(&format!(
+                        "<p class=\"error\">{}</p>\n",
+                        escape_html(error.message)
+                    ));
+                }
+            };
+
+        field(
+            &mut page,
+            Field::Ssid,
+            "Wi-Fi network",
+            "text",
+            &form.ssid,
+            " list=\"networks\" required",
+        );
+        field(
+            &mut page,
+            Field::Psk,
+            "Wi-Fi password",
+            "password",
+            "",
+            " placeholder=\"blank for open or unchanged\"",
+        );
+        field(
+            &mut page,
+            Field::MqttUrl,
+            "MQTT broker",
+            "text",
+            &form.mqtt_url,
+            " placeholder=\"mqtt://broker.local:1883\" required",
+        );
+        field(
+            &mut page,
+            Field::MqttUser,
+            "MQTT user",
+            "text",
+            &form.mqtt_user,
+            "",
+        );
+        field(
+            &mut page,
+            Field::MqttPassword,
+            "MQTT password",
+            "password",
+            "",
+            " placeholder=\"blank for unchanged\"",
+        );
+        field(
+            &mut page,
+            Field::DeviceName,
+            "Device name",
+            "text",
+            &form.device_name,
+            " required",
+        );
+
+        page.push_str("<button type=\"submit\">Save</button>\n</form>\n</body>\n</html>\n");
+
+        page
+    }
+}
+
+impl<S> Handler for Portal<S>
+where
+    S: Storage,
+{
+    fn handle(&mut self, request: &Request) -> Response {
+        match (request.method, request.path.as_str()) {
+            (Method::Get, "/") => {
+                Response::html(self.page(&SetupForm::from_config(&self.config), &[]))
+            }
+            (Method::Post, "/save") => self.save(request),
+            (Method::Get, _) => Response::redirect("/"),
+            _ => Response::error(405),
+        }
+    }
+}
+
+const HEAD: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>Sensor board setup</title>\n\
+<style>body{font-family:sans-serif;max-width:24em;margin:1em auto;padding:0 1em}\
+label{display:block;margin:.8em 0}input{display:block;width:100%;box-sizing:border-box}\
+.error{color:#b00;margin:.2em 0}</style>\n</head>\n<body>\n";
+
+fn message_page(title: &str, message: &str) -> String {
+    format!(
+        "{}<h1>{}</h1>\n<p>{}</p>\n</body>\n</html>\n",
+        HEAD, title, message
+    )
+}
@@ -0,0 +1,91 @@
+use crate::http::{Method, Request, Response};
+
+///Handles one route, given the state the router was dispatched with
+pub type Route<C> = fn(&mut C, &Request) -> Response;
+
+///Exact-path routing.  Unknown paths get a 404, known paths with the wrong method a 405.
+pub struct Router<C> {
+    routes: Vec<(Method, &'static str, Route<C>)>,
+}
+
+impl<C> Default for Router<C> {
+    fn default() -> Self {
+        Router { routes: Vec::new() }
+    }
+}
+
+impl<C> Router<C> {
+    pub fn new() -> Router<C> {
+        Router::default()
+    }
+
+    pub fn route(mut self, method: Method, path: &'static str, route: Route<C>) -> Router<C> {
+        self.routes.push((method, path, route));
+        self
+    }
+
+    pub fn dispatch(&self, context: &mut C, request: &Request) -> Response {
+        let mut path_known = false;
+
+        for (method, path, route) in &self.routes {
+            if *path != request.path {
+                continue;
+            }
+
+            if *method == request.method {
+                return route(context, request);
+            }
+
+            path_known = true;
+        }
+
+        Response::error(if path_known { 405 } else { 404 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> Router<u32> {
+        Router::new()
+            .route(Method::Get, "/count", |count: &mut u32, _| {
+                Response::json(count.to_string())
+            })
+            .route(Method::Put, "/count", |count: &mut u32, _| {
+                *count += 1;
+                Response::new(204, "text/plain", "")
+            })
+    }
+
+    #[test]
+    fn routes_by_method_and_path() {
+        let router = router();
+        let mut count = 0;
+
+        assert_eq!(
+            204,
+            router
+                .dispatch(&mut count, &Request::new(Method::Put, "/count", b""))
+                .status
+        );
+        assert_eq!(
+            b"1".to_vec(),
+            router
+                .dispatch(&mut count, &Request::new(Method::Get, "/count?x", b""))
+                .body
+        );
+        assert_eq!(
+            405,
+            router
+                .dispatch(&mut count, &Request::new(Method::Post, "/count", b""))
+                .status
+        );
+        assert_eq!(
+            404,
+            router
+                .dispatch(&mut count, &Request::new(Method::Get, "/other", b""))
+                .status
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+use crate::http::{ReadError, Request, Response};
+
+///Anything that turns a request into a response.  Handlers know nothing about sockets, so they
+///run the same behind a `TcpListener` on the board or on a desktop.
+pub trait Handler {
+    fn handle(&mut self, request: &Request) -> Response;
+}
+
+impl<F> Handler for F
+where
+    F: FnMut(&Request) -> Response,
+{
+    fn handle(&mut self, request: &Request) -> Response {
+        self(request)
+    }
+}
+
+///How long a client gets to send its request before the connection is dropped
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+///Reads one request from `stream`, answers it and returns.  Requests that can't be parsed get a
+///400 (or 413 when too large) without reaching the handler.
+pub fn serve_connection<S, H>(stream: &mut S, handler: &mut H) -> io::Result<()>
+where
+    S: Read + Write,
+    H: Handler + ?Sized,
+{
+    let response = match Request::read_from(stream) {
+        Ok(request) => handler.handle(&request),
+        Err(ReadError::Malformed) => Response::error(400),
+        Err(ReadError::TooLarge) => Response::error(413),
+        Err(ReadError::Io(error)) => return Err(error),
+    };
+
+    response.write_to(stream)
+}
+
+///Accepts connections one at a time until `done` returns true after a request.  Errors accepting
+///or serving a connection are passed to `on_error` and don't stop the server.
+pub fn serve<H, D, E>(listener: &TcpListener, handler: &mut H, mut done: D, mut on_error: E)
+where
+    H: Handler + ?Sized,
+    D: FnMut(&H) -> bool,
+    E: FnMut(io::Error),
+{
+    for stream in listener.incoming() {
+        let served = stream.and_then(|mut stream| {
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            serve_connection(&mut stream, handler)
+        });
+
+        if let Err(error) = served {
+            on_error(error);
+        }
+
+        if done(handler) {
+            break;
+        }
+    }
+}
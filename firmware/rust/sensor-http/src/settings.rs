@@ -0,0 +1,418 @@
+//! `DeviceConfig` as JSON for `/api/config`.
+//!
+//! Secrets are never written out: networks report `has_psk` and MQTT `has_password` instead.  An
+//! update may contain any subset of the fields, so a `GET` response can be edited and sent back.
+//! `networks` replaces the whole list; a network without a `psk` keeps the stored one for that
+//! SSID, and `mqtt.password` is only changed when present.
+
+use std::fmt::Write;
+
+use device_config::config::{DeviceConfig, LoadCell, Network, MAX_NETWORKS};
+use telemetry::json::{push_string, Value};
+
+use crate::form::{valid_broker, valid_device_name, valid_psk, MAX_SSID_LEN, MAX_TEXT_LEN};
+
+///A rejected update
+#[derive(Clone, Debug, PartialEq)]
+pub struct SettingsError {
+    ///Dotted path of the offending field, e.g. `mqtt.url`
+    pub field: String,
+    pub message: &'static str,
+}
+
+impl SettingsError {
+    fn new(field: &str, message: &'static str) -> SettingsError {
+        SettingsError {
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+pub fn encode(config: &DeviceConfig) -> String {
+    let mut output = String::new();
+
+    output.push_str("{\"device_id\":");
+    push_string(&mut output, &config.device_id);
+
+    output.push_str(",\"networks\":[");
+    for (index, network) in config.network.networks.iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+
+        output.push_str("{\"ssid\":");
+        push_string(&mut output, &network.ssid);
+        let _ = write!(output, ",\"has_psk\":{}}}", !network.psk.is_empty());
+    }
+
+    output.push_str("],\"mqtt\":{\"url\":");
+    push_string(&mut output, &config.mqtt.url);
+    output.push_str(",\"user\":");
+    push_string(&mut output, &config.mqtt.user);
+    let _ = write!(
+        output,
+        ",\"has_password\":{}",
+        !config.mqtt.password.is_empty()
+    );
+    output.push_str(",\"client_id\":");
+    push_string(&mut output, &config.mqtt.client_id);
+    output.push_str(",\"prefix\":");
+    push_string(&mut output, &config.mqtt.prefix);
+
+    let sampling = &config.sampling;
+    let _ = write!(
+        output,
+        "}},\"sampling\":{{\"power_interval_ms\":{},\"load_interval_ms\":{},\"climate_interval_ms\":{}}}",
+        sampling.power_interval_ms, sampling.load_interval_ms, sampling.climate_interval_ms
+    );
+
+    let _ = write!(
+        output,
+        ",\"calibration\":{{\"ina237_shunt_cal\":{},\"load_cell\":",
+        config.calibration.ina237_shunt_cal
+    );
+
+    match config.calibration.load_cell {
+        Some(load_cell) => {
+            let _ = write!(
+                output,
+                "{{\"zero_offset\":{},\"counts_per_gram\":{}}}",
+                load_cell.zero_offset, load_cell.counts_per_gram
+            );
+        }
+        None => output.push_str("null"),
+    }
+
+    output.push_str("}}");
+
+    output
+}
+
+///Applies the fields present in `update` to `config`.  On error `config` may be partly updated,
+///so callers should work on a copy.
+pub fn update(config: &mut DeviceConfig, update: &Value) -> Result<(), SettingsError> {
+    for (name, value) in members(update, "")? {
+        match name.as_str() {
+            "device_id" => {
+                let device_id = string(value, "device_id")?;
+
+                if !valid_device_name(device_id) {
+                    return Err(SettingsError::new(
+                        "device_id",
+                        "use 1 to 32 letters, digits, '-' or '_'",
+                    ));
+                }
+
+                config.device_id = device_id.to_string();
+            }
+            "networks" => update_networks(config, value)?,
+            "mqtt" => update_mqtt(config, value)?,
+            "sampling" => update_sampling(config, value)?,
+            "calibration" => update_calibration(config, value)?,
+            _ => return Err(SettingsError::new(name, "unknown field")),
+        }
+    }
+
+    Ok(())
+}
+
+fn update_networks(config: &mut DeviceConfig, value: &Value) -> Result<(), SettingsError> {
+    let Value::Array(items) = value else {
+        return Err(SettingsError::new("networks", "expected a list"));
+    };
+
+    if items.len() > MAX_NETWORKS {
+        return Err(SettingsError::new("networks", "at most 4 networks"));
+    }
+
+    let mut networks = Vec::with_capacity(items.len());
+
+    for item in items {
+        let mut ssid = None;
+        let mut psk = None;
+
+        for (name, value) in members(item, "networks")? {
+            match name.as_str() {
+                "ssid" => ssid = Some(string(value, "networks.ssid")?),
+                "psk" => psk = Some(string(value, "networks.psk")?),
+                "has_psk" => {}
+                _ => {
+                    return Err(SettingsError::new(
+                        &format!("networks.{}", name),
+                        "unknown field",
+                    ))
+                }
+            }
+        }
+
+        let ssid = ssid.ok_or(SettingsError::new("networks.ssid", "required"))?;
+
+        if ssid.is_empty() || ssid.len() > MAX_SSID_LEN {
+            return Err(SettingsError::new("networks.ssid", "1 to 32 bytes"));
+        }
+
+        let psk = match psk {
+            Some(psk) if !valid_psk(psk) => {
+                return Err(SettingsError::new(
+                    "networks.psk",
+                    "8 to 63 characters, or 64 hex digits",
+                ))
+            }
+            Some(psk) => psk.to_string(),
+            None => config
+                .network
+                .networks
+                .iter()
+                .find(|network| network.ssid == ssid)
+                .map(|network| network.psk.clone())
+                .unwrap_or_default(),
+        };
+
+        networks.push(Network {
+            ssid: ssid.to_string(),
+            psk,
+        });
+    }
+
+    config.network.networks = networks;
+
+    Ok(())
+}
+
+fn update_mqtt(config: &mut DeviceConfig, value: &Value) -> Result<(), SettingsError> {
+    for (name, value) in members(value, "mqtt")? {
+        let field = format!("mqtt.{}", name);
+
+        let target = match name.as_str() {
+            "url" => &mut config.mqtt.url,
+            "user" => &mut config.mqtt.user,
+            "password" => &mut config.mqtt.password,
+            "client_id" => &mut config.mqtt.client_id,
+            "prefix" => &mut config.mqtt.prefix,
+            "has_password" => continue,
+            _ => return Err(SettingsError::new(&field, "unknown field")),
+        };
+
+        let text = string(value, &field)?;
+
+        if text.len() > MAX_TEXT_LEN {
+            return Err(SettingsError::new(&field, "too long"));
+        }
+
+        if name == "url" && !valid_broker(text) {
+            return Err(SettingsError::new(
+                &field,
+                "use mqtt://host[:port] or mqtts://host[:port]",
+            ));
+        }
+
+        if name == "prefix" && (text.is_empty() || text.contains(['+', '#'])) {
+            return Err(SettingsError::new(
+                &field,
+                "a topic level without wildcards",
+            ));
+        }
+
+        *target = text.to_string();
+    }
+
+    Ok(())
+}
+
+fn update_sampling(config: &mut DeviceConfig, value: &Value) -> Result<(), SettingsError> {
+    for (name, value) in members(value, "sampling")? {
+        let field = format!("sampling.{}", name);
+
+        let target = match name.as_str() {
+            "power_interval_ms" => &mut config.sampling.power_interval_ms,
+            "load_interval_ms" => &mut config.sampling.load_interval_ms,
+            "climate_interval_ms" => &mut config.sampling.climate_interval_ms,
+            _ => return Err(SettingsError::new(&field, "unknown field")),
+        };
+
+        *target = value
+            .as_i64()
+            .filter(|interval| (10..=86_400_000).contains(interval))
+            .ok_or(SettingsError::new(&field, "10 ms to 24 h"))? as u32;
+    }
+
+    Ok(())
+}
+
+fn update_calibration(config: &mut DeviceConfig, value: &Value) -> Result<(), SettingsError> {
+    for (name, value) in members(value, "calibration")? {
+        match name.as_str() {
+            "ina237_shunt_cal" => {
+                config.calibration.ina237_shunt_cal = value
+                    .as_i64()
+                    .and_then(|cal| u16::try_from(cal).ok())
+                    .ok_or(SettingsError::new(
+                        "calibration.ina237_shunt_cal",
+                        "0 to 65535",
+                    ))?;
+            }
+            "load_cell" if *value == Value::Null => config.calibration.load_cell = None,
+            "load_cell" => {
+                let zero_offset = value
+                    .get("zero_offset")
+                    .and_then(Value::as_i64)
+                    .and_then(|offset| i32::try_from(offset).ok())
+                    .ok_or(SettingsError::new(
+                        "calibration.load_cell.zero_offset",
+                        "expected an integer",
+                    ))?;
+
+                let counts_per_gram = value
+                    .get("counts_per_gram")
+                    .and_then(Value::as_f64)
+                    .map(|counts| counts as f32)
+                    .filter(|counts| counts.is_finite() && *counts != 0.0)
+                    .ok_or(SettingsError::new(
+                        "calibration.load_cell.counts_per_gram",
+                        "expected a non-zero number",
+                    ))?;
+
+                config.calibration.load_cell = Some(LoadCell {
+                    zero_offset,
+                    counts_per_gram,
+                });
+            }
+            _ => {
+                return Err(SettingsError::new(
+                    &format!("calibration.{}", name),
+                    "unknown field",
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn members<'a>(value: &'a Value, field: &str) -> Result<&'a [(String, Value)], SettingsError> {
+    match value {
+        Value::Object(members) => Ok(members),
+        _ => Err(SettingsError::new(field, "expected an object")),
+    }
+}
+
+fn string<'a>(value: &'a Value, field: &str) -> Result<&'a str, SettingsError> {
+    value
+        .as_str()
+        .ok_or(SettingsError::new(field, "expected a string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telemetry::json::parse;
+
+    fn config() -> DeviceConfig {
+        let mut config = DeviceConfig::default();
+
+        config.network.networks.push(Network {
+            ssid: "Shed".to_string(),
+            psk: "hunter22".to_string(),
+        });
+        config.mqtt.url = "mqtt://broker.local".to_string();
+        config.mqtt.password = "secret".to_string();
+
+        config
+    }
+
+    fn apply(json: &str) -> Result<DeviceConfig, SettingsError> {
+        let mut config = config();
+
+        update(&mut config, &parse(json).unwrap())?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn encoding_leaves_out_secrets() {
+        let encoded = encode(&config());
+
+        assert!(encoded.contains(r#""networks":[{"ssid":"Shed","has_psk":true}]"#));
+        assert!(encoded.contains(r#""has_password":true"#));
+        assert!(!encoded.contains("hunter22"));
+        assert!(!encoded.contains("secret"));
+        assert!(encoded.ends_with(r#""load_cell":null}}"#));
+    }
+
+    #[test]
+    fn encoded_config_can_be_sent_back() {
+        let mut config = config();
+        config.calibration.load_cell = Some(LoadCell {
+            zero_offset: -12,
+            counts_per_gram: 420.5,
+        });
+
+        let mut updated = config.clone();
+        update(&mut updated, &parse(&encode(&config)).unwrap()).unwrap();
+
+        assert_eq!(config, updated);
+    }
+
+    #[test]
+    fn partial_update() {
+        let updated =
+            apply(r#"{"sampling":{"climate_interval_ms":30000},"mqtt":{"user":"bench"}}"#).unwrap();
+
+        assert_eq!(30_000, updated.sampling.climate_interval_ms);
+        assert_eq!(100, updated.sampling.power_interval_ms);
+        assert_eq!("bench", updated.mqtt.user);
+        assert_eq!("secret", updated.mqtt.password);
+    }
+
+    #[test]
+    fn networks_keep_stored_psks() {
+        let updated = apply(
+            r#"{"networks":[{"ssid":"House","psk":"house-psk"},{"ssid":"Shed"},{"ssid":"Cafe"}]}"#,
+        )
+        .unwrap();
+
+        let psks: Vec<_> = updated
+            .network
+            .networks
+            .iter()
+            .map(|network| (network.ssid.as_str(), network.psk.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![("House", "house-psk"), ("Shed", "hunter22"), ("Cafe", "")],
+            psks
+        );
+    }
+
+    #[test]
+    fn invalid_updates_name_the_field() {
+        let field = |json| apply(json).unwrap_err().field;
+
+        assert_eq!("device_id", field(r#"{"device_id":"has space"}"#));
+        assert_eq!("mqtt.url", field(r#"{"mqtt":{"url":"http://x"}}"#));
+        assert_eq!("mqtt.prefix", field(r##"{"mqtt":{"prefix":"#"}}"##));
+        assert_eq!(
+            "networks.psk",
+            field(r#"{"networks":[{"ssid":"x","psk":"short"}]}"#)
+        );
+        assert_eq!(
+            "networks.ssid",
+            field(r#"{"networks":[{"psk":"longenough"}]}"#)
+        );
+        assert_eq!(
+            "sampling.power_interval_ms",
+            field(r#"{"sampling":{"power_interval_ms":1}}"#)
+        );
+        assert_eq!(
+            "calibration.ina237_shunt_cal",
+            field(r#"{"calibration":{"ina237_shunt_cal":70000}}"#)
+        );
+        assert_eq!(
+            "calibration.load_cell.counts_per_gram",
+            field(r#"{"calibration":{"load_cell":{"zero_offset":0,"counts_per_gram":0}}}"#)
+        );
+        assert_eq!("colour", field(r#"{"colour":"blue"}"#));
+        assert_eq!("", field("[]"));
+    }
+}
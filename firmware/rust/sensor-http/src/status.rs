@@ -0,0 +1,185 @@
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use sensor_core::sensor::Error;
+
+///What the API knows about one sensor
+#[derive(Clone, Debug, PartialEq)]
+pub struct SensorStatus {
+    pub channel: ChannelId,
+    pub quantities: &'static [(Quantity, Unit)],
+    ///Timestamp of the last successful reading
+    pub last_reading_ms: Option<u64>,
+    ///Failed reads since the last successful one
+    pub consecutive_failures: u32,
+    pub total_failures: u32,
+    pub last_error: Option<Error>,
+}
+
+impl SensorStatus {
+    pub fn health(&self) -> Health {
+        match (self.consecutive_failures, self.last_reading_ms) {
+            (0, Some(_)) => Health::Ok,
+            (0, None) => Health::Waiting,
+            _ => Health::Failing,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Health {
+    Ok,
+    ///No reading yet
+    Waiting,
+    ///The last read failed
+    Failing,
+}
+
+impl Health {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Health::Ok => "ok",
+            Health::Waiting => "waiting",
+            Health::Failing => "failing",
+        }
+    }
+}
+
+///Latest reading of each channel and quantity, and the health of each sensor.  The firmware feeds
+///it from the scheduler's sink and error callbacks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    sensors: Vec<SensorStatus>,
+    readings: Vec<Reading>,
+}
+
+impl Status {
+    pub fn new<I>(inventory: I) -> Status
+    where
+        I: IntoIterator<Item = (ChannelId, &'static [(Quantity, Unit)])>,
+    {
+        Status {
+            sensors: inventory
+                .into_iter()
+                .map(|(channel, quantities)| SensorStatus {
+                    channel,
+                    quantities,
+                    last_reading_ms: None,
+                    consecutive_failures: 0,
+                    total_failures: 0,
+                    last_error: None,
+                })
+                .collect(),
+            readings: Vec::new(),
+        }
+    }
+
+    pub fn sensors(&self) -> &[SensorStatus] {
+        &self.sensors
+    }
+
+    ///In the order first seen
+    pub fn readings(&self) -> &[Reading] {
+        &self.readings
+    }
+
+    pub fn record(&mut self, reading: &Reading) {
+        match self
+            .readings
+            .iter_mut()
+            .find(|latest| latest.channel == reading.channel && latest.key() == reading.key())
+        {
+            Some(latest) => *latest = *reading,
+            None => self.readings.push(*reading),
+        }
+
+        if let Some(sensor) = self.sensor(reading.channel) {
+            sensor.last_reading_ms = Some(reading.timestamp_ms);
+            sensor.consecutive_failures = 0;
+        }
+    }
+
+    pub fn failed(&mut self, channel: ChannelId, error: Error) {
+        if let Some(sensor) = self.sensor(channel) {
+            sensor.consecutive_failures += 1;
+            sensor.total_failures += 1;
+            sensor.last_error = Some(error);
+        }
+    }
+
+    fn sensor(&mut self, channel: ChannelId) -> Option<&mut SensorStatus> {
+        self.sensors
+            .iter_mut()
+            .find(|sensor| sensor.channel == channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INA: ChannelId = ChannelId::new("ina237", "a");
+    const SHT: ChannelId = ChannelId::new("sht40", "inside");
+
+    fn status() -> Status {
+        Status::new([
+            (INA, &[(Quantity::Voltage, Unit::Millivolt)][..]),
+            (SHT, &[(Quantity::Temperature, Unit::MilliCelsius)][..]),
+        ])
+    }
+
+    #[test]
+    fn latest_reading_replaces_older() {
+        let mut status = status();
+
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_000,
+            10,
+        ));
+        status.record(&Reading::new(
+            SHT,
+            Quantity::Temperature,
+            Unit::MilliCelsius,
+            21_500,
+            20,
+        ));
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_100,
+            30,
+        ));
+
+        assert_eq!(2, status.readings().len());
+        assert_eq!(12_100, status.readings()[0].value);
+        assert_eq!(Some(30), status.sensors()[0].last_reading_ms);
+    }
+
+    #[test]
+    fn health_follows_reads() {
+        let mut status = status();
+
+        assert_eq!(Health::Waiting, status.sensors()[0].health());
+
+        status.failed(INA, Error::NotReady);
+        status.failed(INA, Error::Crc);
+
+        assert_eq!(Health::Failing, status.sensors()[0].health());
+        assert_eq!(2, status.sensors()[0].consecutive_failures);
+        assert_eq!(Some(Error::Crc), status.sensors()[0].last_error);
+
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_000,
+            10,
+        ));
+
+        assert_eq!(Health::Ok, status.sensors()[0].health());
+        assert_eq!(2, status.sensors()[0].total_failures);
+        assert_eq!(Health::Waiting, status.sensors()[1].health());
+    }
+}
@@ -0,0 +1,278 @@
+//! Serves the bench API from a thread over a real socket, with the test standing in for the
+//! firmware's main loop.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use device_config::config::{DeviceConfig, Network};
+use device_config::storage::MemoryStorage;
+use device_config::store::ConfigStore;
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use sensor_core::sensor::Error;
+use sensor_http::api::{Api, SharedDevice};
+use sensor_http::server;
+use sensor_http::status::Status;
+
+const INA: ChannelId = ChannelId::new("ina237", "a");
+const SHT: ChannelId = ChannelId::new("sht40", "inside");
+
+struct Bench {
+    status: Arc<Mutex<Status>>,
+    store: Arc<Mutex<ConfigStore<MemoryStorage>>>,
+    address: SocketAddr,
+    server: JoinHandle<()>,
+}
+
+impl Bench {
+    ///Serves `requests` connections, then stops
+    fn start(requests: usize) -> Bench {
+        let status = Arc::new(Mutex::new(Status::new([
+            (
+                INA,
+                &[
+                    (Quantity::Voltage, Unit::Millivolt),
+                    (Quantity::Current, Unit::Microamp),
+                ][..],
+            ),
+            (SHT, &[(Quantity::Temperature, Unit::MilliCelsius)][..]),
+        ])));
+
+        let mut config = DeviceConfig {
+            device_id: "bench-1".to_string(),
+            ..Default::default()
+        };
+        config.network.networks.push(Network {
+            ssid: "Shed".to_string(),
+            psk: "hunter22".to_string(),
+        });
+        config.mqtt.url = "mqtt://broker.local:1883".to_string();
+        config.mqtt.password = "secret".to_string();
+
+        let mut store = ConfigStore::new(MemoryStorage::new());
+        store.save(&config).unwrap();
+        let store = Arc::new(Mutex::new(store));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut api = Api::new(SharedDevice::new(status.clone(), store.clone()));
+
+        let server = thread::spawn(move || {
+            let mut served = 0;
+
+            server::serve(
+                &listener,
+                &mut api,
+                |_| {
+                    served += 1;
+                    served == requests
+                },
+                |error| panic!("connection failed: {}", error),
+            );
+        });
+
+        Bench {
+            status,
+            store,
+            address,
+            server,
+        }
+    }
+
+    fn request(&self, raw: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(self.address).unwrap();
+
+        stream.write_all(raw.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    fn get(&self, path: &str) -> (String, String) {
+        self.request(&format!("GET {} HTTP/1.1\r\nHost: bench\r\n\r\n", path))
+    }
+
+    fn put(&self, path: &str, body: &str) -> (String, String) {
+        self.request(&format!(
+            "PUT {} HTTP/1.1\r\nHost: bench\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        ))
+    }
+
+    fn stop(self) -> ConfigStore<MemoryStorage> {
+        self.server.join().unwrap();
+
+        let store = Arc::try_unwrap(self.store).ok().unwrap();
+
+        store.into_inner().unwrap()
+    }
+}
+
+#[test]
+fn readings_are_latest_values() {
+    let bench = Bench::start(2);
+
+    assert_eq!(
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            r#"{"readings":[]}"#.to_string()
+        ),
+        bench.get("/api/readings")
+    );
+
+    {
+        let mut status = bench.status.lock().unwrap();
+
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_000,
+            100,
+        ));
+        status.record(&Reading::new(
+            INA,
+            Quantity::Current,
+            Unit::Microamp,
+            250_000,
+            100,
+        ));
+        status.record(&Reading::new(
+            SHT,
+            Quantity::Temperature,
+            Unit::MilliCelsius,
+            21_500,
+            150,
+        ));
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_034,
+            200,
+        ));
+    }
+
+    let (_, body) = bench.get("/api/readings");
+
+    assert_eq!(
+        concat!(
+            r#"{"readings":["#,
+            r#"{"channel":"ina237/a","key":"voltage_mv","value":12034,"scaled":12.034,"unit":"V","timestamp_ms":200},"#,
+            r#"{"channel":"ina237/a","key":"current_ua","value":250000,"scaled":0.25,"unit":"A","timestamp_ms":100},"#,
+            r#"{"channel":"sht40/inside","key":"temperature_mc","value":21500,"scaled":21.5,"unit":"°C","timestamp_ms":150}"#,
+            r#"]}"#
+        ),
+        body
+    );
+
+    bench.stop();
+}
+
+#[test]
+fn sensors_report_health() {
+    let bench = Bench::start(1);
+
+    {
+        let mut status = bench.status.lock().unwrap();
+
+        status.record(&Reading::new(
+            INA,
+            Quantity::Voltage,
+            Unit::Millivolt,
+            12_000,
+            100,
+        ));
+        status.failed(SHT, Error::Crc);
+    }
+
+    let (_, body) = bench.get("/api/sensors");
+
+    assert_eq!(
+        concat!(
+            r#"{"sensors":["#,
+            r#"{"channel":"ina237/a","keys":["voltage_mv","current_ua"],"health":"ok","last_reading_ms":100,"consecutive_failures":0,"total_failures":0,"last_error":null},"#,
+            r#"{"channel":"sht40/inside","keys":["temperature_mc"],"health":"failing","last_reading_ms":null,"consecutive_failures":1,"total_failures":1,"last_error":"Crc"}"#,
+            r#"]}"#
+        ),
+        body
+    );
+
+    bench.stop();
+}
+
+#[test]
+fn config_is_read_without_secrets() {
+    let bench = Bench::start(1);
+
+    let (status, body) = bench.get("/api/config");
+
+    assert_eq!("HTTP/1.1 200 OK", status);
+    assert!(
+        body.starts_with(r#"{"device_id":"bench-1","networks":[{"ssid":"Shed","has_psk":true}]"#)
+    );
+    assert!(!body.contains("hunter22"));
+    assert!(!body.contains("secret"));
+
+    bench.stop();
+}
+
+#[test]
+fn config_is_updated_and_stored() {
+    let bench = Bench::start(3);
+
+    let (status, body) = bench.put(
+        "/api/config",
+        r#"{"sampling":{"power_interval_ms":250},"networks":[{"ssid":"House","psk":"house-psk"},{"ssid":"Shed"}]}"#,
+    );
+
+    assert_eq!("HTTP/1.1 200 OK", status);
+    assert!(body.contains(r#""power_interval_ms":250"#));
+
+    let (status, body) = bench.put("/api/config", r#"{"mqtt":{"url":"ftp://nope"}}"#);
+
+    assert_eq!("HTTP/1.1 422 Unprocessable Entity", status);
+    assert_eq!(
+        r#"{"error":"use mqtt://host[:port] or mqtts://host[:port]","field":"mqtt.url"}"#,
+        body
+    );
+
+    let (status, body) = bench.put("/api/config", "{\"device_id\":");
+
+    assert_eq!("HTTP/1.1 400 Bad Request", status);
+    assert_eq!(r#"{"error":"invalid JSON"}"#, body);
+
+    let (stored, _) = bench.stop().load().unwrap();
+
+    assert_eq!(250, stored.sampling.power_interval_ms);
+    assert_eq!("mqtt://broker.local:1883", stored.mqtt.url);
+    assert_eq!("secret", stored.mqtt.password);
+    assert_eq!("House", stored.network.networks[0].ssid);
+    assert_eq!("hunter22", stored.network.networks[1].psk);
+}
+
+#[test]
+fn dashboard_and_unknown_routes() {
+    let bench = Bench::start(3);
+
+    let (status, body) = bench.get("/");
+
+    assert_eq!("HTTP/1.1 200 OK", status);
+    assert!(body.contains("fetch(\"/api/readings\")"));
+
+    assert_eq!("HTTP/1.1 404 Not Found", bench.get("/api/nothing").0);
+    assert_eq!(
+        "HTTP/1.1 405 Method Not Allowed",
+        bench.put("/api/readings", "{}").0
+    );
+
+    bench.stop();
+}
@@ -0,0 +1,178 @@
+//! Drives the provisioning portal through a real socket, the way a phone on the setup network
+//! would.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use device_config::config::DeviceConfig;
+use device_config::storage::MemoryStorage;
+use device_config::store::ConfigStore;
+use sensor_http::portal::{Portal, ScannedNetwork};
+use sensor_http::server;
+
+///Serves `portal` until a config is saved, returning it with everything each client received
+fn run<F>(portal: Portal<MemoryStorage>, client: F) -> (Portal<MemoryStorage>, Vec<String>)
+where
+    F: FnOnce(&dyn Fn(&str) -> String) -> Vec<String>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let mut portal = portal;
+
+        server::serve(
+            &listener,
+            &mut portal,
+            |portal| portal.is_saved(),
+            |error| panic!("connection failed: {}", error),
+        );
+
+        portal
+    });
+
+    let request = move |raw: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+
+        stream.write_all(raw.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        response
+    };
+
+    let responses = client(&request);
+
+    (server.join().unwrap(), responses)
+}
+
+fn portal() -> Portal<MemoryStorage> {
+    let mut portal = Portal::new(
+        ConfigStore::new(MemoryStorage::new()),
+        DeviceConfig::default(),
+    );
+
+    portal.set_networks(vec![
+        ScannedNetwork {
+            ssid: "House".to_string(),
+            rssi: -71,
+        },
+        ScannedNetwork {
+            ssid: "Shed <2.4>".to_string(),
+            rssi: -48,
+        },
+        ScannedNetwork {
+            ssid: "House".to_string(),
+            rssi: -80,
+        },
+        ScannedNetwork {
+            ssid: String::new(),
+            rssi: -30,
+        },
+    ]);
+
+    portal
+}
+
+fn post(body: &str) -> String {
+    format!(
+        "POST /save HTTP/1.1\r\nHost: 192.168.71.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+const VALID: &str = "ssid=Shed+%3C2.4%3E&psk=hunter22&mqtt_url=mqtt%3A%2F%2Fbroker.local%3A1883\
+                     &mqtt_user=sensor&mqtt_password=secret&device_name=greenhouse-1";
+
+#[test]
+fn setup_form_lists_scanned_networks() {
+    let (_, responses) = run(portal(), |request| {
+        vec![
+            request("GET / HTTP/1.1\r\nHost: 192.168.71.1\r\n\r\n"),
+            request(&post(VALID)),
+        ]
+    });
+
+    let page = &responses[0];
+
+    assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(page.contains("Content-Type: text/html"));
+    assert!(page.contains("<option value=\"Shed &lt;2.4&gt;\">-48 dBm</option>\n<option value=\"House\">-71 dBm</option>\n</datalist>"));
+    assert!(page.contains("name=\"device_name\" type=\"text\" value=\"esp32-sensor\""));
+}
+
+#[test]
+fn connectivity_checks_are_redirected() {
+    let (_, responses) = run(portal(), |request| {
+        vec![
+            request("GET /generate_204 HTTP/1.1\r\nHost: connectivitycheck.gstatic.com\r\n\r\n"),
+            request("GET /hotspot-detect.html HTTP/1.1\r\nHost: captive.apple.com\r\n\r\n"),
+            request(&post(VALID)),
+        ]
+    });
+
+    for response in &responses[..2] {
+        assert!(response.starts_with("HTTP/1.1 302 Found\r\n"));
+        assert!(response.contains("Location: /\r\n"));
+    }
+}
+
+#[test]
+fn invalid_form_is_shown_again_with_errors() {
+    let (portal, responses) = run(portal(), |request| {
+        vec![
+            request(&post(
+                "ssid=Shed&psk=short&mqtt_url=broker.local&device_name=green+house",
+            )),
+            request(&post(VALID)),
+        ]
+    });
+
+    let page = &responses[0];
+
+    assert!(page.starts_with("HTTP/1.1 422 Unprocessable Entity\r\n"));
+    assert!(page.contains("Passwords are 8 to 63 characters"));
+    assert!(page.contains("Use mqtt://host[:port]"));
+    assert!(page.contains("value=\"green house\""));
+    assert!(!page.contains("short"));
+
+    //Only the second, valid post was saved
+    assert_eq!("greenhouse-1", portal.config().device_id);
+}
+
+#[test]
+fn valid_form_is_saved() {
+    let (portal, responses) = run(portal(), |request| vec![request(&post(VALID))]);
+
+    assert!(responses[0].starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(responses[0].contains("join <b>Shed &lt;2.4&gt;</b>"));
+    assert!(portal.is_saved());
+
+    let (stored, _) = portal.destroy().load().unwrap();
+
+    assert!(stored.is_provisioned());
+    assert_eq!("Shed <2.4>", stored.network.networks[0].ssid);
+    assert_eq!("hunter22", stored.network.networks[0].psk);
+    assert_eq!("mqtt://broker.local:1883", stored.mqtt.url);
+    assert_eq!("sensor", stored.mqtt.user);
+    assert_eq!("secret", stored.mqtt.password);
+    assert_eq!("greenhouse-1", stored.device_id);
+}
+
+#[test]
+fn malformed_requests_are_rejected() {
+    let (_, responses) = run(portal(), |request| {
+        vec![
+            request("GARBAGE\r\n\r\n"),
+            request("DELETE / HTTP/1.1\r\n\r\n"),
+            request(&post(VALID)),
+        ]
+    });
+
+    assert!(responses[0].starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(responses[1].starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
@@ -0,0 +1,274 @@
+//! Online/offline availability on the status topic.  The client is configured with a retained
+//! last will of `offline`; every (re)connect publishes a retained `online` and replays the retained
+//! configuration messages (e.g. Home Assistant discovery), since the broker may have restarted and
+//! lost them.  Lost connections are retried with exponential backoff.
+//!
+//! `Availability` only tracks state.  The caller feeds it connection events and the clock, and acts
+//! on the returned `Action`.
+
+use alloc::string::String;
+
+pub use sensor_core::backoff::Backoff;
+
+use crate::client::{MqttClient, QoS};
+use crate::topics::Topics;
+
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+///The last will to configure on the client before connecting
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LastWill<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum State {
+    ///Waiting for the connection attempt to finish
+    Connecting { failures: u32 },
+    Online,
+    ///Waiting to retry
+    Backoff { failures: u32, retry_at_ms: u64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    ///Connected: call `announce`
+    Announce,
+    ///Start a new connection attempt
+    Reconnect,
+}
+
+pub struct Availability {
+    topic: String,
+    backoff: Backoff,
+    state: State,
+    connects: u32,
+}
+
+impl Availability {
+    ///Starts in `Connecting`, expecting the caller to make the first connection attempt
+    pub fn new(topics: &Topics, backoff: Backoff) -> Availability {
+        Availability {
+            topic: topics.status(),
+            backoff,
+            state: State::Connecting { failures: 0 },
+            connects: 0,
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn last_will(&self) -> LastWill<'_> {
+        LastWill {
+            topic: &self.topic,
+            payload: OFFLINE.as_bytes(),
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.state == State::Online
+    }
+
+    ///Successful connections so far, including the first
+    pub fn connects(&self) -> u32 {
+        self.connects
+    }
+
+    pub fn handle(&mut self, event: ConnectionEvent, now_ms: u64) -> Action {
+        match (self.state, event) {
+            (State::Online, ConnectionEvent::Connected) => Action::None,
+            (_, ConnectionEvent::Connected) => {
+                self.state = State::Online;
+                self.connects += 1;
+
+                Action::Announce
+            }
+            (State::Online, ConnectionEvent::Disconnected) => {
+                self.retry_later(1, now_ms);
+
+                Action::None
+            }
+            (State::Connecting { failures }, ConnectionEvent::Disconnected) => {
+                self.retry_later(failures + 1, now_ms);
+
+                Action::None
+            }
+            (State::Backoff { .. }, ConnectionEvent::Disconnected) => Action::None,
+        }
+    }
+
+    ///Returns `Reconnect` once the backoff has elapsed
+    pub fn poll(&mut self, now_ms: u64) -> Action {
+        match self.state {
+            State::Backoff {
+                failures,
+                retry_at_ms,
+            } if now_ms >= retry_at_ms => {
+                self.state = State::Connecting { failures };
+
+                Action::Reconnect
+            }
+            _ => Action::None,
+        }
+    }
+
+    ///Time until `poll` next has something to do
+    pub fn wait_ms(&self, now_ms: u64) -> Option<u64> {
+        match self.state {
+            State::Backoff { retry_at_ms, .. } => Some(retry_at_ms.saturating_sub(now_ms)),
+            _ => None,
+        }
+    }
+
+    ///Publishes `online` then runs `replay` to republish retained configuration
+    pub fn announce<C, F>(&mut self, client: &mut C, replay: F) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+        F: FnOnce(&mut C) -> Result<(), C::Error>,
+    {
+        client.publish(&self.topic, QoS::AtLeastOnce, true, ONLINE.as_bytes())?;
+
+        replay(client)
+    }
+
+    ///Publishes `offline` ahead of a deliberate disconnect or reboot, which the broker doesn't
+    ///treat as a reason to send the last will
+    pub fn go_offline<C>(&mut self, client: &mut C) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+    {
+        client.publish(&self.topic, QoS::AtLeastOnce, true, OFFLINE.as_bytes())
+    }
+
+    fn retry_later(&mut self, failures: u32, now_ms: u64) {
+        self.state = State::Backoff {
+            failures,
+            retry_at_ms: now_ms + self.backoff.delay_ms(failures),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeBroker;
+    use crate::topics::DEFAULT_PREFIX;
+
+    fn availability() -> Availability {
+        Availability::new(&Topics::new(DEFAULT_PREFIX, "esp123"), Backoff::default())
+    }
+
+    #[test]
+    fn last_will_is_retained_offline_on_status() {
+        let under_test = availability();
+
+        assert_eq!(
+            LastWill {
+                topic: "sensor/esp123/status",
+                payload: b"offline",
+                qos: QoS::AtLeastOnce,
+                retain: true,
+            },
+            under_test.last_will()
+        );
+    }
+
+    #[test]
+    fn failed_attempts_back_off_exponentially() {
+        let mut under_test = availability();
+
+        assert_eq!(Action::None, under_test.handle(ConnectionEvent::Disconnected, 0));
+        assert_eq!(Some(1_000), under_test.wait_ms(0));
+        assert_eq!(Action::None, under_test.poll(999));
+        assert_eq!(Action::Reconnect, under_test.poll(1_000));
+        assert_eq!(State::Connecting { failures: 1 }, under_test.state());
+
+        under_test.handle(ConnectionEvent::Disconnected, 1_500);
+
+        assert_eq!(Some(2_000), under_test.wait_ms(1_500));
+
+        //Repeated events while waiting don't restart the backoff
+        under_test.handle(ConnectionEvent::Disconnected, 2_000);
+
+        assert_eq!(Action::None, under_test.poll(3_499));
+        assert_eq!(Action::Reconnect, under_test.poll(3_500));
+    }
+
+    #[test]
+    fn connecting_resets_backoff() {
+        let mut under_test = availability();
+
+        under_test.handle(ConnectionEvent::Disconnected, 0);
+        under_test.poll(1_000);
+        under_test.handle(ConnectionEvent::Disconnected, 1_000);
+        under_test.poll(3_000);
+
+        assert_eq!(Action::Announce, under_test.handle(ConnectionEvent::Connected, 3_100));
+        assert!(under_test.is_online());
+        assert_eq!(Action::None, under_test.handle(ConnectionEvent::Connected, 3_200));
+
+        under_test.handle(ConnectionEvent::Disconnected, 10_000);
+
+        assert_eq!(Some(1_000), under_test.wait_ms(10_000));
+    }
+
+    #[test]
+    fn reconnect_announces_and_replays_after_last_will() {
+        let broker = FakeBroker::new();
+        let mut client = broker.client();
+        let mut under_test = availability();
+
+        let will = under_test.last_will();
+        client.set_last_will(will.topic, will.qos, will.retain, will.payload);
+
+        let replay = |client: &mut crate::fake::FakeClient| {
+            client.publish("homeassistant/sensor/esp123/x/config", QoS::AtLeastOnce, true, b"{}")
+        };
+
+        assert_eq!(Action::Announce, under_test.handle(ConnectionEvent::Connected, 0));
+        under_test.announce(&mut client, replay).unwrap();
+
+        assert_eq!(Some(b"online".to_vec()), broker.retained("sensor/esp123/status"));
+
+        broker.set_connected(false);
+        under_test.handle(ConnectionEvent::Disconnected, 100);
+
+        assert_eq!(Some(b"offline".to_vec()), broker.retained("sensor/esp123/status"));
+
+        broker.set_connected(true);
+
+        assert_eq!(Action::Reconnect, under_test.poll(1_100));
+        assert_eq!(Action::Announce, under_test.handle(ConnectionEvent::Connected, 1_200));
+        under_test.announce(&mut client, replay).unwrap();
+
+        assert_eq!(Some(b"online".to_vec()), broker.retained("sensor/esp123/status"));
+        assert_eq!(2, under_test.connects());
+        assert_eq!(
+            2,
+            broker
+                .published()
+                .iter()
+                .filter(|message| message.topic.starts_with("homeassistant/"))
+                .count()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QoS {
+    AtMostOnce = 0,
+    AtLeastOnce = 1,
+}
+
+///The parts of an MQTT client the publisher needs.  Implemented over `EspMqttClient` on the
+///device and by `fake::FakeClient` on the host.
+pub trait MqttClient {
+    type Error: core::fmt::Debug;
+
+    fn publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<(), Self::Error>;
+
+    fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<(), Self::Error>;
+}
+
+///Matches an MQTT topic against a subscription filter with `+` and `#` wildcards
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(filter_level), Some(topic_level)) if filter_level == topic_level => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_topic_matches() {
+        assert!(topic_matches("sensor/esp123/status", "sensor/esp123/status"));
+        assert!(!topic_matches("sensor/esp123/status", "sensor/esp123"));
+        assert!(!topic_matches("sensor/esp123", "sensor/esp123/status"));
+    }
+
+    #[test]
+    fn wildcards_match() {
+        assert!(topic_matches("sensor/+/status", "sensor/esp123/status"));
+        assert!(!topic_matches("sensor/+/status", "sensor/esp123/ina237/status"));
+        assert!(topic_matches("sensor/esp123/#", "sensor/esp123/ina237/a/current_ua"));
+        assert!(topic_matches("sensor/esp123/cmd/#", "sensor/esp123/cmd"));
+        assert!(topic_matches("#", "anything/at/all"));
+    }
+}
@@ -0,0 +1,423 @@
+//! Remote commands on `<prefix>/<device>/cmd/<name>`.  The payload is empty or a flat JSON object
+//! of arguments; an `id` argument is echoed back so the sender can match the result, which is
+//! published to `<prefix>/<device>/cmd/result`:
+//!
+//! `{"id":"42","command":"relay","ok":false,"error":"bad argument: relay"}`
+//!
+//! | Command     | Arguments                                                  |
+//! |-------------|------------------------------------------------------------|
+//! | `tare`      | `sensor` (optional)                                        |
+//! | `calibrate` | `sensor` (optional)                                        |
+//! | `interval`  | `sensor`, `ms`                                             |
+//! | `relay`     | `relay` (1 or 2), `on` (bool)                              |
+//! | `heater`    | `sensor` (optional), `mw` (200, 110, 20), `ms` (1000, 100) |
+//! | `reboot`    |                                                            |
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use telemetry::json::{self, push_string, Value};
+
+use crate::client::{MqttClient, QoS};
+use crate::topics::Topics;
+
+pub const RESULT: &str = "result";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    ///Zero the load cell at its current reading
+    Tare { sensor: Option<String> },
+    ///Rerun the ADC's internal offset calibration
+    Calibrate { sensor: Option<String> },
+    SetInterval { sensor: String, interval_ms: u64 },
+    Relay { relay: u8, on: bool },
+    Heater {
+        sensor: Option<String>,
+        power_mw: u16,
+        duration_ms: u16,
+    },
+    Reboot,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    UnknownCommand,
+    ///Payload isn't a flat JSON object
+    Malformed,
+    ///Argument missing or out of range
+    BadArgument(&'static str),
+    UnknownSensor,
+    ///The device couldn't carry out the command
+    Failed,
+}
+
+impl CommandError {
+    pub fn message(&self) -> String {
+        match self {
+            CommandError::UnknownCommand => "unknown command".to_string(),
+            CommandError::Malformed => "malformed payload".to_string(),
+            CommandError::BadArgument(name) => alloc::format!("bad argument: {}", name),
+            CommandError::UnknownSensor => "unknown sensor".to_string(),
+            CommandError::Failed => "failed".to_string(),
+        }
+    }
+}
+
+///Carries out commands on the device
+pub trait CommandHandler {
+    fn handle(&mut self, command: &Command) -> Result<(), CommandError>;
+}
+
+impl<F> CommandHandler for F
+where
+    F: FnMut(&Command) -> Result<(), CommandError>,
+{
+    fn handle(&mut self, command: &Command) -> Result<(), CommandError> {
+        self(command)
+    }
+}
+
+///A command as received
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    ///Correlation id, echoed in the result
+    pub id: Option<String>,
+    pub name: String,
+    pub command: Result<Command, CommandError>,
+}
+
+///A request and what came of it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handled {
+    pub request: Request,
+    pub outcome: Result<(), CommandError>,
+}
+
+pub struct Commands {
+    base: String,
+}
+
+impl Commands {
+    pub fn new(topics: &Topics) -> Commands {
+        Commands {
+            base: topics.child("cmd"),
+        }
+    }
+
+    ///`<prefix>/<device>/cmd/#`
+    pub fn filter(&self) -> String {
+        alloc::format!("{}/#", self.base)
+    }
+
+    pub fn result_topic(&self) -> String {
+        alloc::format!("{}/{}", self.base, RESULT)
+    }
+
+    pub fn subscribe<C>(&self, client: &mut C) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+    {
+        client.subscribe(&self.filter(), QoS::AtLeastOnce)
+    }
+
+    ///None if the topic isn't a command, including our own results
+    pub fn parse(&self, topic: &str, payload: &[u8]) -> Option<Request> {
+        let name = topic.strip_prefix(self.base.as_str())?.strip_prefix('/')?;
+
+        if name == RESULT || name.is_empty() {
+            return None;
+        }
+
+        let args = core::str::from_utf8(payload).ok().and_then(arguments);
+
+        let Some(args) = args else {
+            return Some(Request {
+                id: None,
+                name: name.to_string(),
+                command: Err(CommandError::Malformed),
+            });
+        };
+
+        let id = match args.get("id") {
+            Some(Value::String(id)) => Some(id.clone()),
+            Some(id) => id.as_i64().map(|id| id.to_string()),
+            None => None,
+        };
+
+        Some(Request {
+            id,
+            name: name.to_string(),
+            command: command(name, &args),
+        })
+    }
+
+    ///Parses a message, runs it through `handler` and publishes the result.  Returns None if the
+    ///message wasn't a command.
+    pub fn dispatch<C, H>(
+        &self,
+        client: &mut C,
+        handler: &mut H,
+        topic: &str,
+        payload: &[u8],
+    ) -> Result<Option<Handled>, C::Error>
+    where
+        C: MqttClient,
+        H: CommandHandler + ?Sized,
+    {
+        let Some(request) = self.parse(topic, payload) else {
+            return Ok(None);
+        };
+
+        let outcome = match &request.command {
+            Ok(command) => handler.handle(command),
+            Err(error) => Err(error.clone()),
+        };
+
+        client.publish(
+            &self.result_topic(),
+            QoS::AtLeastOnce,
+            false,
+            result_payload(&request, &outcome).as_bytes(),
+        )?;
+
+        Ok(Some(Handled { request, outcome }))
+    }
+}
+
+///An empty payload, or a JSON object of arguments
+fn arguments(text: &str) -> Option<Value> {
+    if text.trim().is_empty() {
+        return Some(Value::Object(Vec::new()));
+    }
+
+    json::parse(text).filter(|value| matches!(value, Value::Object(_)))
+}
+
+fn int(args: &Value, name: &str) -> Option<i64> {
+    args.get(name).and_then(Value::as_i64)
+}
+
+fn command(name: &str, args: &Value) -> Result<Command, CommandError> {
+    let sensor = match args.get("sensor") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(sensor)) => Some(sensor.clone()),
+        Some(_) => return Err(CommandError::BadArgument("sensor")),
+    };
+
+    match name {
+        "tare" => Ok(Command::Tare { sensor }),
+        "calibrate" => Ok(Command::Calibrate { sensor }),
+        "interval" => Ok(Command::SetInterval {
+            sensor: sensor.ok_or(CommandError::BadArgument("sensor"))?,
+            interval_ms: int(args, "ms")
+                .filter(|ms| *ms > 0)
+                .ok_or(CommandError::BadArgument("ms"))? as u64,
+        }),
+        "relay" => Ok(Command::Relay {
+            relay: int(args, "relay")
+                .filter(|relay| (1..=2).contains(relay))
+                .ok_or(CommandError::BadArgument("relay"))? as u8,
+            on: match args.get("on") {
+                Some(Value::Bool(on)) => *on,
+                _ => return Err(CommandError::BadArgument("on")),
+            },
+        }),
+        "heater" => Ok(Command::Heater {
+            sensor,
+            power_mw: int(args, "mw")
+                .filter(|mw| [200, 110, 20].contains(mw))
+                .ok_or(CommandError::BadArgument("mw"))? as u16,
+            duration_ms: int(args, "ms")
+                .filter(|ms| [1000, 100].contains(ms))
+                .ok_or(CommandError::BadArgument("ms"))? as u16,
+        }),
+        "reboot" => Ok(Command::Reboot),
+        _ => Err(CommandError::UnknownCommand),
+    }
+}
+
+fn result_payload(request: &Request, outcome: &Result<(), CommandError>) -> String {
+    let mut output = String::from("{\"id\":");
+
+    match &request.id {
+        Some(id) => push_string(&mut output, id),
+        None => output.push_str("null"),
+    }
+
+    output.push_str(",\"command\":");
+    push_string(&mut output, &request.name);
+
+    match outcome {
+        Ok(()) => output.push_str(",\"ok\":true}"),
+        Err(error) => {
+            output.push_str(",\"ok\":false,\"error\":");
+            push_string(&mut output, &error.message());
+            output.push('}');
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeBroker;
+    use crate::topics::DEFAULT_PREFIX;
+
+    fn commands() -> Commands {
+        Commands::new(&Topics::new(DEFAULT_PREFIX, "esp123"))
+    }
+
+    fn parse(name: &str, payload: &str) -> Request {
+        commands()
+            .parse(&alloc::format!("sensor/esp123/cmd/{}", name), payload.as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(Ok(Command::Tare { sensor: None }), parse("tare", "").command);
+        assert_eq!(
+            Ok(Command::Calibrate {
+                sensor: Some("nau7802/a".to_string())
+            }),
+            parse("calibrate", r#"{"sensor":"nau7802/a"}"#).command
+        );
+        assert_eq!(
+            Ok(Command::SetInterval {
+                sensor: "sht40/inside".to_string(),
+                interval_ms: 30_000
+            }),
+            parse("interval", r#"{ "sensor" : "sht40/inside", "ms" : 30000 }"#).command
+        );
+        assert_eq!(
+            Ok(Command::Relay { relay: 2, on: true }),
+            parse("relay", r#"{"relay":2,"on":true}"#).command
+        );
+        assert_eq!(
+            Ok(Command::Heater {
+                sensor: None,
+                power_mw: 110,
+                duration_ms: 100
+            }),
+            parse("heater", r#"{"mw":110,"ms":100,"sensor":null}"#).command
+        );
+        assert_eq!(Ok(Command::Reboot), parse("reboot", "{}").command);
+    }
+
+    #[test]
+    fn bad_commands_are_reported() {
+        assert_eq!(Err(CommandError::UnknownCommand), parse("selfdestruct", "").command);
+        assert_eq!(Err(CommandError::Malformed), parse("relay", "{\"relay\":1,").command);
+        assert_eq!(Err(CommandError::Malformed), parse("relay", "on").command);
+        assert_eq!(
+            Err(CommandError::BadArgument("relay")),
+            parse("relay", r#"{"relay":3,"on":true}"#).command
+        );
+        assert_eq!(
+            Err(CommandError::BadArgument("on")),
+            parse("relay", r#"{"relay":1,"on":"yes"}"#).command
+        );
+        assert_eq!(
+            Err(CommandError::BadArgument("mw")),
+            parse("heater", r#"{"mw":500,"ms":100}"#).command
+        );
+        assert_eq!(Err(CommandError::BadArgument("sensor")), parse("interval", r#"{"ms":5}"#).command);
+    }
+
+    #[test]
+    fn id_is_taken_from_string_or_number() {
+        assert_eq!(Some("abc".to_string()), parse("reboot", r#"{"id":"abc"}"#).id);
+        assert_eq!(Some("17".to_string()), parse("reboot", r#"{"id":17}"#).id);
+        assert_eq!(None, parse("reboot", "").id);
+    }
+
+    #[test]
+    fn other_topics_are_ignored() {
+        let under_test = commands();
+
+        assert_eq!(None, under_test.parse("sensor/esp123/cmd/result", b"{}"));
+        assert_eq!(None, under_test.parse("sensor/esp123/status", b"online"));
+        assert_eq!(None, under_test.parse("sensor/esp1234/cmd/reboot", b""));
+    }
+
+    #[test]
+    fn dispatch_acknowledges_with_correlation_id() {
+        let broker = FakeBroker::new();
+        let mut device = broker.client();
+        let under_test = commands();
+
+        under_test.subscribe(&mut device).unwrap();
+
+        broker.inject("sensor/esp123/cmd/relay", br#"{"id":"7","relay":1,"on":false}"#);
+        broker.inject("sensor/esp123/cmd/tare", br#"{"id":"8","sensor":"nau7802/b"}"#);
+
+        let mut handled = Vec::new();
+        let mut handler = |command: &Command| {
+            handled.push(command.clone());
+
+            match command {
+                Command::Tare { .. } => Err(CommandError::UnknownSensor),
+                _ => Ok(()),
+            }
+        };
+
+        for message in device.received() {
+            under_test
+                .dispatch(&mut device, &mut handler, &message.topic, &message.payload)
+                .unwrap();
+        }
+
+        //Our own results come back through the subscription and are ignored
+        for message in device.received() {
+            assert_eq!(
+                None,
+                under_test
+                    .dispatch(&mut device, &mut handler, &message.topic, &message.payload)
+                    .unwrap()
+            );
+        }
+
+        assert_eq!(2, handled.len());
+
+        let results: Vec<String> = broker
+            .published()
+            .into_iter()
+            .filter(|message| message.topic == "sensor/esp123/cmd/result")
+            .map(|message| String::from_utf8(message.payload).unwrap())
+            .collect();
+
+        assert_eq!(
+            vec![
+                r#"{"id":"7","command":"relay","ok":true}"#.to_string(),
+                r#"{"id":"8","command":"tare","ok":false,"error":"unknown sensor"}"#.to_string(),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn malformed_command_is_acknowledged_without_handling() {
+        let broker = FakeBroker::new();
+        let mut device = broker.client();
+        let mut calls = 0;
+
+        let handled = commands()
+            .dispatch(
+                &mut device,
+                &mut |_: &Command| {
+                    calls += 1;
+                    Ok(())
+                },
+                "sensor/esp123/cmd/heater",
+                b"{\"mw\":200}",
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!("heater", handled.request.name);
+        assert_eq!(Err(CommandError::BadArgument("ms")), handled.outcome);
+        assert_eq!(0, calls);
+    }
+}
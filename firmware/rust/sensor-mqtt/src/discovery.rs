@@ -0,0 +1,278 @@
+//! Home Assistant MQTT discovery.  Each channel/quantity pair becomes one `sensor` entity with a
+//! retained config message on `homeassistant/sensor/<node>/<object>/config`, where `<object>` is
+//! `<sensor>_<instance>_<key>`.  Values stay integers on the wire; `value_template` scales them to
+//! the unit Home Assistant expects.
+
+use alloc::string::String;
+
+use sensor_core::reading::{ChannelId, Key, Quantity, Unit};
+use sensor_core::sensor::Sensor;
+use telemetry::json::push_string;
+
+use crate::client::{MqttClient, QoS};
+use crate::topics::Topics;
+
+pub const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+pub const MODEL: &str = "esp32_sensor_board";
+
+///How a quantity is presented in Home Assistant
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EntityClass {
+    pub device_class: Option<&'static str>,
+    pub unit_of_measurement: Option<&'static str>,
+    ///Divides the published integer into `unit_of_measurement`
+    pub scale: i32,
+    ///Diagnostic entities are hidden from the default dashboard
+    pub diagnostic: bool,
+}
+
+impl EntityClass {
+    pub fn of(quantity: Quantity, unit: Unit) -> EntityClass {
+        let (device_class, unit_of_measurement, diagnostic) = match quantity {
+            Quantity::Voltage => (Some("voltage"), Some("V"), false),
+            Quantity::ShuntVoltage => (Some("voltage"), Some("mV"), true),
+            Quantity::Current => (Some("current"), Some("mA"), false),
+            Quantity::Power => (Some("power"), Some("W"), false),
+            Quantity::Temperature => (Some("temperature"), Some("°C"), false),
+            Quantity::RelativeHumidity => (Some("humidity"), Some("%"), false),
+            Quantity::Weight => (Some("weight"), Some("g"), false),
+            Quantity::RawCounts => (None, None, true),
+        };
+
+        //uA -> mA and uV -> mV are 1000, not the 1_000_000 the unit divisor gives for A and V
+        let scale = match unit {
+            Unit::Microamp | Unit::Microvolt => 1_000,
+            unit => unit.divisor(),
+        };
+
+        EntityClass {
+            device_class,
+            unit_of_measurement,
+            scale,
+            diagnostic,
+        }
+    }
+}
+
+pub struct Discovery {
+    prefix: String,
+    node_id: String,
+    device_name: String,
+    topics: Topics,
+}
+
+impl Discovery {
+    ///`node_id` is normally the device id; characters Home Assistant doesn't accept in ids are
+    ///replaced with `_`.  State and availability topics come from `topics`.
+    pub fn new(prefix: &str, node_id: &str, topics: Topics) -> Discovery {
+        Discovery {
+            prefix: String::from(prefix.trim_end_matches('/')),
+            node_id: sanitize(node_id),
+            device_name: String::from(node_id),
+            topics,
+        }
+    }
+
+    ///Name shown for the device in Home Assistant.  Defaults to the node id.
+    pub fn set_device_name(&mut self, name: &str) {
+        self.device_name = String::from(name);
+    }
+
+    pub fn object_id(&self, channel: ChannelId, key: Key) -> String {
+        sanitize(&alloc::format!("{}_{}_{}", channel.sensor, channel.instance, key))
+    }
+
+    ///`<prefix>/sensor/<node>/<object>/config`
+    pub fn config_topic(&self, channel: ChannelId, key: Key) -> String {
+        alloc::format!(
+            "{}/sensor/{}/{}/config",
+            self.prefix,
+            self.node_id,
+            self.object_id(channel, key)
+        )
+    }
+
+    pub fn config_payload(&self, channel: ChannelId, key: Key) -> String {
+        let Key(quantity, unit) = key;
+        let class = EntityClass::of(quantity, unit);
+        let object_id = self.object_id(channel, key);
+
+        let mut output = String::new();
+
+        output.push('{');
+        push_field(&mut output, "name", &alloc::format!("{} {} {}", channel.sensor, channel.instance, quantity.name()));
+        output.push(',');
+        push_field(&mut output, "unique_id", &alloc::format!("{}_{}", self.node_id, object_id));
+        output.push(',');
+        push_field(&mut output, "object_id", &alloc::format!("{}_{}", self.node_id, object_id));
+        output.push(',');
+        push_field(&mut output, "state_topic", &self.topics.value(channel, key));
+        output.push(',');
+        push_field(&mut output, "availability_topic", &self.topics.status());
+
+        if let Some(device_class) = class.device_class {
+            output.push(',');
+            push_field(&mut output, "device_class", device_class);
+        }
+
+        if let Some(unit_of_measurement) = class.unit_of_measurement {
+            output.push(',');
+            push_field(&mut output, "unit_of_measurement", unit_of_measurement);
+        }
+
+        output.push(',');
+        push_field(&mut output, "state_class", "measurement");
+
+        if class.scale != 1 {
+            output.push(',');
+            push_field(
+                &mut output,
+                "value_template",
+                &alloc::format!("{{{{ (value | float / {}) | round(3) }}}}", class.scale),
+            );
+        }
+
+        if class.diagnostic {
+            output.push(',');
+            push_field(&mut output, "entity_category", "diagnostic");
+        }
+
+        output.push_str(",\"device\":{\"identifiers\":[");
+        push_string(&mut output, &self.node_id);
+        output.push_str("],");
+        push_field(&mut output, "name", &self.device_name);
+        output.push(',');
+        push_field(&mut output, "model", MODEL);
+        output.push_str("}}");
+
+        output
+    }
+
+    ///Publishes retained configs for every quantity of every channel.  Stops at the first failure.
+    pub fn publish<C, I>(&self, client: &mut C, channels: I) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+        I: IntoIterator<Item = (ChannelId, &'static [(Quantity, Unit)])>,
+    {
+        for (channel, quantities) in channels {
+            for &(quantity, unit) in quantities {
+                let key = Key(quantity, unit);
+
+                client.publish(
+                    &self.config_topic(channel, key),
+                    QoS::AtLeastOnce,
+                    true,
+                    self.config_payload(channel, key).as_bytes(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn publish_sensors<C>(&self, client: &mut C, sensors: &[&mut dyn Sensor]) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+    {
+        self.publish(client, sensors.iter().map(|sensor| (sensor.channel(), sensor.quantities())))
+    }
+
+    ///Publishes empty retained configs, which removes the entities from Home Assistant
+    pub fn remove<C, I>(&self, client: &mut C, channels: I) -> Result<(), C::Error>
+    where
+        C: MqttClient,
+        I: IntoIterator<Item = (ChannelId, &'static [(Quantity, Unit)])>,
+    {
+        for (channel, quantities) in channels {
+            for &(quantity, unit) in quantities {
+                client.publish(
+                    &self.config_topic(channel, Key(quantity, unit)),
+                    QoS::AtLeastOnce,
+                    true,
+                    &[],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn sanitize(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+fn push_field(output: &mut String, name: &str, value: &str) {
+    push_string(output, name);
+    output.push(':');
+    push_string(output, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeBroker;
+    use crate::topics::DEFAULT_PREFIX;
+
+    const INA_A: ChannelId = ChannelId::new("ina237", "a");
+
+    fn discovery() -> Discovery {
+        Discovery::new(DEFAULT_DISCOVERY_PREFIX, "esp123", Topics::new(DEFAULT_PREFIX, "esp123"))
+    }
+
+    #[test]
+    fn config_topic_uses_node_and_object() {
+        assert_eq!(
+            "homeassistant/sensor/esp123/ina237_a_current_ua/config",
+            discovery().config_topic(INA_A, Key(Quantity::Current, Unit::Microamp))
+        );
+    }
+
+    #[test]
+    fn ids_are_sanitized() {
+        let under_test = Discovery::new("homeassistant/", "esp.123", Topics::new(DEFAULT_PREFIX, "esp.123"));
+
+        assert_eq!(
+            "homeassistant/sensor/esp_123/sht40_in_side_temperature_mc/config",
+            under_test.config_topic(
+                ChannelId::new("sht40", "in side"),
+                Key(Quantity::Temperature, Unit::MilliCelsius)
+            )
+        );
+    }
+
+    #[test]
+    fn raw_counts_have_no_class_or_template() {
+        let payload = discovery().config_payload(
+            ChannelId::new("nau7802", "a"),
+            Key(Quantity::RawCounts, Unit::Counts),
+        );
+
+        assert!(!payload.contains("device_class"));
+        assert!(!payload.contains("value_template"));
+        assert!(payload.contains("\"entity_category\":\"diagnostic\""));
+    }
+
+    #[test]
+    fn configs_are_retained_and_removable() {
+        let broker = FakeBroker::new();
+        let mut client = broker.client();
+
+        let channels = [(INA_A, &[(Quantity::Voltage, Unit::Millivolt), (Quantity::Current, Unit::Microamp)][..])];
+
+        discovery().publish(&mut client, channels).unwrap();
+
+        assert_eq!(2, broker.published().len());
+        assert!(broker
+            .published()
+            .iter()
+            .all(|message| message.retain && message.qos == QoS::AtLeastOnce));
+
+        discovery().remove(&mut client, channels).unwrap();
+
+        assert_eq!(None, broker.retained("homeassistant/sensor/esp123/ina237_a_voltage_mv/config"));
+    }
+}
@@ -0,0 +1,219 @@
+//! In-process broker for host tests.  Keeps retained messages, honours `+` / `#` subscriptions and
+//! can be disconnected to simulate network loss.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::client::{topic_matches, MqttClient, QoS};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FakeError {
+    Disconnected,
+}
+
+#[derive(Default)]
+struct Broker {
+    connected: bool,
+    published: Vec<Message>,
+    retained: Vec<Message>,
+    ///(client, filter)
+    subscriptions: Vec<(usize, String)>,
+    ///(client, message)
+    inboxes: Vec<(usize, Message)>,
+    ///(client, will), published when the connection drops
+    wills: Vec<(usize, Message)>,
+    clients: usize,
+}
+
+#[derive(Clone)]
+pub struct FakeBroker {
+    broker: Rc<RefCell<Broker>>,
+}
+
+impl FakeBroker {
+    pub fn new() -> FakeBroker {
+        FakeBroker {
+            broker: Rc::new(RefCell::new(Broker {
+                connected: true,
+                ..Broker::default()
+            })),
+        }
+    }
+
+    pub fn client(&self) -> FakeClient {
+        let mut broker = self.broker.borrow_mut();
+
+        broker.clients += 1;
+
+        FakeClient {
+            id: broker.clients,
+            broker: self.broker.clone(),
+        }
+    }
+
+    ///While disconnected every publish and subscribe fails.  Dropping the connection publishes
+    ///each client's last will, as a broker does when a client goes away without disconnecting.
+    pub fn set_connected(&self, connected: bool) {
+        let mut broker = self.broker.borrow_mut();
+
+        if broker.connected && !connected {
+            let wills: Vec<Message> = broker.wills.iter().map(|(_, will)| will.clone()).collect();
+
+            for will in wills {
+                broker.route(will);
+            }
+        }
+
+        broker.connected = connected;
+    }
+
+    ///Every message accepted, in order
+    pub fn published(&self) -> Vec<Message> {
+        self.broker.borrow().published.clone()
+    }
+
+    pub fn retained(&self, topic: &str) -> Option<Vec<u8>> {
+        self.broker
+            .borrow()
+            .retained
+            .iter()
+            .find(|message| message.topic == topic)
+            .map(|message| message.payload.clone())
+    }
+
+    ///Publishes as another client would
+    pub fn inject(&self, topic: &str, payload: &[u8]) {
+        self.broker.borrow_mut().route(Message {
+            topic: topic.to_string(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            payload: payload.to_vec(),
+        });
+    }
+}
+
+impl Default for FakeBroker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Broker {
+    fn route(&mut self, message: Message) {
+        for (client, filter) in &self.subscriptions {
+            if topic_matches(filter, &message.topic) {
+                self.inboxes.push((*client, message.clone()));
+            }
+        }
+
+        if message.retain {
+            self.retained.retain(|retained| retained.topic != message.topic);
+
+            //An empty retained message clears the topic
+            if !message.payload.is_empty() {
+                self.retained.push(message.clone());
+            }
+        }
+
+        self.published.push(message);
+    }
+}
+
+pub struct FakeClient {
+    id: usize,
+    broker: Rc<RefCell<Broker>>,
+}
+
+impl FakeClient {
+    ///Registers the message the broker publishes if this client's connection drops
+    pub fn set_last_will(&mut self, topic: &str, qos: QoS, retain: bool, payload: &[u8]) {
+        let mut broker = self.broker.borrow_mut();
+        let id = self.id;
+
+        broker.wills.retain(|(client, _)| *client != id);
+        broker.wills.push((
+            id,
+            Message {
+                topic: topic.to_string(),
+                qos,
+                retain,
+                payload: payload.to_vec(),
+            },
+        ));
+    }
+
+    ///Takes the messages delivered to this client's subscriptions
+    pub fn received(&mut self) -> Vec<Message> {
+        let mut broker = self.broker.borrow_mut();
+        let id = self.id;
+
+        let (mine, others) = broker
+            .inboxes
+            .drain(..)
+            .partition(|(client, _)| *client == id);
+
+        broker.inboxes = others;
+
+        mine.into_iter().map(|(_, message)| message).collect()
+    }
+}
+
+impl MqttClient for FakeClient {
+    type Error = FakeError;
+
+    fn publish(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &[u8],
+    ) -> Result<(), Self::Error> {
+        let mut broker = self.broker.borrow_mut();
+
+        if !broker.connected {
+            return Err(FakeError::Disconnected);
+        }
+
+        broker.route(Message {
+            topic: topic.to_string(),
+            qos,
+            retain,
+            payload: payload.to_vec(),
+        });
+
+        Ok(())
+    }
+
+    fn subscribe(&mut self, topic: &str, _qos: QoS) -> Result<(), Self::Error> {
+        let mut broker = self.broker.borrow_mut();
+
+        if !broker.connected {
+            return Err(FakeError::Disconnected);
+        }
+
+        broker.subscriptions.push((self.id, topic.to_string()));
+
+        let retained: Vec<Message> = broker
+            .retained
+            .iter()
+            .filter(|message| topic_matches(topic, &message.topic))
+            .cloned()
+            .collect();
+
+        for message in retained {
+            broker.inboxes.push((self.id, message));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+#![deny(unsafe_code)]
+#![cfg_attr(not(any(test, feature = "fake")), no_std)]
+
+extern crate alloc;
+
+pub mod availability;
+pub mod client;
+pub mod commands;
+pub mod discovery;
+#[cfg(any(test, feature = "fake"))]
+pub mod fake;
+pub mod publisher;
+pub mod topics;
@@ -0,0 +1,248 @@
+use alloc::string::ToString;
+
+use sensor_core::reading::Reading;
+use telemetry::snapshot::Snapshot;
+
+use crate::client::{MqttClient, QoS};
+use crate::topics::Topics;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PublishConfig {
+    pub qos: QoS,
+    ///Retain per-reading messages so new subscribers get the last value straight away
+    pub retain: bool,
+    ///Publish each reading to its own topic
+    pub per_reading: bool,
+    ///Publish the encoded snapshot to the telemetry topic
+    pub snapshot: bool,
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        Self {
+            qos: QoS::AtMostOnce,
+            retain: true,
+            per_reading: true,
+            snapshot: false,
+        }
+    }
+}
+
+pub struct Publisher<C> {
+    client: C,
+    topics: Topics,
+    config: PublishConfig,
+}
+
+impl<C> Publisher<C>
+where
+    C: MqttClient,
+{
+    pub fn new(client: C, topics: Topics, config: PublishConfig) -> Publisher<C> {
+        Publisher {
+            client,
+            topics,
+            config,
+        }
+    }
+
+    pub fn client(&mut self) -> &mut C {
+        &mut self.client
+    }
+
+    pub fn topics(&self) -> &Topics {
+        &self.topics
+    }
+
+    pub fn destroy(self) -> C {
+        self.client
+    }
+
+    ///Publishes the value as a decimal integer in the unit named by the topic
+    pub fn publish_reading(&mut self, reading: &Reading) -> Result<(), C::Error> {
+        let topic = self.topics.reading(reading);
+        let payload = reading.value.to_string();
+
+        self.client.publish(
+            &topic,
+            self.config.qos,
+            self.config.retain,
+            payload.as_bytes(),
+        )
+    }
+
+    ///Publishes according to `PublishConfig`.  Stops at the first failure.
+    pub fn publish_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), C::Error> {
+        if self.config.per_reading {
+            for reading in snapshot.readings {
+                self.publish_reading(reading)?;
+            }
+        }
+
+        if self.config.snapshot {
+            let topic = self.topics.telemetry();
+
+            self.client
+                .publish(&topic, self.config.qos, false, &snapshot.encode())?;
+        }
+
+        Ok(())
+    }
+
+    ///Publishes readings taken earlier, e.g. while offline, to the telemetry topic whatever the
+    ///config, so each keeps its timestamp.  Never retained and never on the per-reading topics,
+    ///where old values would pass for current ones.
+    pub fn publish_backlog(&mut self, snapshot: &Snapshot) -> Result<(), C::Error> {
+        let topic = self.topics.telemetry();
+
+        self.client
+            .publish(&topic, self.config.qos, false, &snapshot.encode())
+    }
+
+    pub fn publish_status(&mut self, status: &str) -> Result<(), C::Error> {
+        let topic = self.topics.status();
+
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, true, status.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeBroker;
+    use crate::topics::DEFAULT_PREFIX;
+    use sensor_core::reading::{ChannelId, Quantity, Unit};
+
+    const INA_A: ChannelId = ChannelId::new("ina237", "a");
+
+    fn readings() -> [Reading; 2] {
+        [
+            Reading::new(INA_A, Quantity::Voltage, Unit::Millivolt, 5_012, 10),
+            Reading::new(INA_A, Quantity::Current, Unit::Microamp, -250, 10),
+        ]
+    }
+
+    #[test]
+    fn readings_are_published_retained_per_topic() {
+        let broker = FakeBroker::new();
+
+        let mut under_test = Publisher::new(
+            broker.client(),
+            Topics::new(DEFAULT_PREFIX, "esp123"),
+            PublishConfig::default(),
+        );
+
+        let readings = readings();
+
+        under_test
+            .publish_snapshot(&Snapshot::new("esp123", 10, &readings))
+            .unwrap();
+
+        assert_eq!(
+            Some(b"-250".to_vec()),
+            broker.retained("sensor/esp123/ina237/a/current_ua")
+        );
+        assert_eq!(
+            Some(b"5012".to_vec()),
+            broker.retained("sensor/esp123/ina237/a/voltage_mv")
+        );
+        assert!(broker
+            .published()
+            .iter()
+            .all(|message| message.qos == QoS::AtMostOnce));
+    }
+
+    #[test]
+    fn late_subscriber_receives_last_value() {
+        let broker = FakeBroker::new();
+
+        let mut under_test = Publisher::new(
+            broker.client(),
+            Topics::new(DEFAULT_PREFIX, "esp123"),
+            PublishConfig::default(),
+        );
+
+        let readings = readings();
+
+        under_test.publish_reading(&readings[1]).unwrap();
+
+        let mut subscriber = broker.client();
+        subscriber.subscribe("sensor/esp123/+/+/+", QoS::AtLeastOnce).unwrap();
+
+        let received = subscriber.received();
+
+        assert_eq!(1, received.len());
+        assert_eq!("sensor/esp123/ina237/a/current_ua", received[0].topic);
+        assert!(received[0].retain);
+    }
+
+    #[test]
+    fn snapshot_and_qos_follow_config() {
+        let broker = FakeBroker::new();
+
+        let mut under_test = Publisher::new(
+            broker.client(),
+            Topics::new(DEFAULT_PREFIX, "esp123"),
+            PublishConfig {
+                qos: QoS::AtLeastOnce,
+                retain: false,
+                per_reading: false,
+                snapshot: true,
+            },
+        );
+
+        let readings = readings();
+        let snapshot = Snapshot::new("esp123", 10, &readings);
+
+        under_test.publish_snapshot(&snapshot).unwrap();
+
+        let published = broker.published();
+
+        assert_eq!(1, published.len());
+        assert_eq!("sensor/esp123/telemetry", published[0].topic);
+        assert_eq!(QoS::AtLeastOnce, published[0].qos);
+        assert_eq!(snapshot.encode(), published[0].payload);
+        assert_eq!(None, broker.retained("sensor/esp123/ina237/a/current_ua"));
+    }
+
+    #[test]
+    fn backlog_keeps_timestamps_off_retained_topics() {
+        let broker = FakeBroker::new();
+
+        let mut under_test = Publisher::new(
+            broker.client(),
+            Topics::new(DEFAULT_PREFIX, "esp123"),
+            PublishConfig::default(),
+        );
+
+        let readings = readings();
+        let snapshot = Snapshot::new("esp123", 10, &readings[..1]);
+
+        under_test.publish_backlog(&snapshot).unwrap();
+
+        let published = broker.published();
+
+        assert_eq!(1, published.len());
+        assert_eq!("sensor/esp123/telemetry", published[0].topic);
+        assert!(!published[0].retain);
+        assert_eq!(snapshot.encode(), published[0].payload);
+        assert_eq!(None, broker.retained("sensor/esp123/ina237/a/voltage_mv"));
+    }
+
+    #[test]
+    fn failed_publish_is_reported() {
+        let broker = FakeBroker::new();
+
+        let mut under_test = Publisher::new(
+            broker.client(),
+            Topics::new(DEFAULT_PREFIX, "esp123"),
+            PublishConfig::default(),
+        );
+
+        broker.set_connected(false);
+
+        assert!(under_test.publish_status("online").is_err());
+        assert!(broker.published().is_empty());
+    }
+}
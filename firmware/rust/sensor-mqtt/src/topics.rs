@@ -0,0 +1,71 @@
+use alloc::format;
+use alloc::string::String;
+
+use sensor_core::reading::{ChannelId, Key, Reading};
+
+pub const DEFAULT_PREFIX: &str = "sensor";
+
+///Builds topics under `<prefix>/<device>`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topics {
+    base: String,
+}
+
+impl Topics {
+    pub fn new(prefix: &str, device_id: &str) -> Topics {
+        Topics {
+            base: format!("{}/{}", prefix.trim_end_matches('/'), device_id),
+        }
+    }
+
+    ///`<prefix>/<device>`
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    ///`<prefix>/<device>/<sensor>/<instance>/<key>`, e.g. `sensor/esp123/ina237/a/current_ua`
+    pub fn reading(&self, reading: &Reading) -> String {
+        self.value(reading.channel, reading.key())
+    }
+
+    ///Same topic as `reading`, for when there is no reading yet
+    pub fn value(&self, channel: ChannelId, key: Key) -> String {
+        format!("{}/{}/{}", self.base, channel, key)
+    }
+
+    ///`<prefix>/<device>/telemetry`, for whole snapshots
+    pub fn telemetry(&self) -> String {
+        self.child("telemetry")
+    }
+
+    ///`<prefix>/<device>/status`
+    pub fn status(&self) -> String {
+        self.child("status")
+    }
+
+    pub fn child(&self, path: &str) -> String {
+        format!("{}/{}", self.base, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sensor_core::reading::{ChannelId, Quantity, Unit};
+
+    #[test]
+    fn reading_topic_includes_channel_and_key() {
+        let under_test = Topics::new(DEFAULT_PREFIX, "esp123");
+
+        let reading = Reading::new(ChannelId::new("ina237", "a"), Quantity::Current, Unit::Microamp, 1, 0);
+
+        assert_eq!("sensor/esp123/ina237/a/current_ua", under_test.reading(&reading));
+    }
+
+    #[test]
+    fn prefix_trailing_slash_is_ignored() {
+        let under_test = Topics::new("home/garage/", "esp123");
+
+        assert_eq!("home/garage/esp123/status", under_test.status());
+    }
+}
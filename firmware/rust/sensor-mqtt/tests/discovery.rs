@@ -0,0 +1,74 @@
+//! Compares the generated Home Assistant discovery configs against `tests/fixtures/discovery`, one
+//! file per entity.  Regenerate with `UPDATE_FIXTURES=1 cargo test` after an intentional change.
+
+use std::path::PathBuf;
+
+use sensor_core::reading::{ChannelId, Key, Quantity, Unit};
+use sensor_core::scan::Device;
+use sensor_mqtt::discovery::{Discovery, DEFAULT_DISCOVERY_PREFIX};
+use sensor_mqtt::topics::{Topics, DEFAULT_PREFIX};
+
+fn discovery() -> Discovery {
+    let mut discovery = Discovery::new(
+        DEFAULT_DISCOVERY_PREFIX,
+        "esp123",
+        Topics::new(DEFAULT_PREFIX, "esp123"),
+    );
+
+    discovery.set_device_name("Greenhouse bench");
+
+    discovery
+}
+
+fn check(channel: ChannelId, quantity: Quantity, unit: Unit) {
+    let under_test = discovery();
+    let key = Key(quantity, unit);
+
+    let object_id = under_test.object_id(channel, key);
+    let result = under_test.config_payload(channel, key);
+
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "discovery",
+        &format!("{}.json", object_id),
+    ]
+    .iter()
+    .collect();
+
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::write(&path, &result).unwrap();
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap();
+
+    assert_eq!(expected, result, "{} differs from fixture", object_id);
+}
+
+fn check_device(device: Device, instance: &'static str) {
+    for &(quantity, unit) in device.quantities() {
+        check(ChannelId::new(device.name(), instance), quantity, unit);
+    }
+}
+
+#[test]
+fn ina237_matches_fixtures() {
+    check_device(
+        Device::Ina237 {
+            manufacturer_id: 0x5449,
+        },
+        "a",
+    );
+}
+
+#[test]
+fn sht40_matches_fixtures() {
+    check_device(Device::Sht4x { serial_number: 1 }, "inside");
+}
+
+#[test]
+fn nau7802_matches_fixtures() {
+    check_device(Device::Nau7802 { revision: 0xF }, "a");
+    check(ChannelId::new("nau7802", "a"), Quantity::Weight, Unit::Milligram);
+}
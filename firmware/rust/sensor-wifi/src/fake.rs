@@ -0,0 +1,105 @@
+//! Scriptable radio for host tests.  Scans return `visible`, connecting succeeds to any visible
+//! access point not in `refuse`, and the link can be dropped or weakened to simulate moving around.
+
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::wifi::{AccessPoint, Bssid, Wifi};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FakeError {
+    ScanFailed,
+    NotFound,
+    Refused,
+}
+
+#[derive(Default)]
+pub struct FakeWifi {
+    pub visible: Vec<AccessPoint>,
+    ///Access points that reject every attempt
+    pub refuse: Vec<Bssid>,
+    pub fail_scans: bool,
+    pub connected: Option<AccessPoint>,
+    ///(bssid, psk) of every connection attempt
+    pub attempts: Vec<(Bssid, String)>,
+    pub scans: u32,
+}
+
+impl FakeWifi {
+    pub fn new(visible: Vec<AccessPoint>) -> FakeWifi {
+        FakeWifi {
+            visible,
+            ..FakeWifi::default()
+        }
+    }
+
+    ///The access point goes away without the station asking
+    pub fn drop_link(&mut self) {
+        if let Some(ap) = self.connected.take() {
+            self.visible.retain(|visible| visible.bssid != ap.bssid);
+        }
+    }
+
+    ///Changes the signal of the current link, as seen by `rssi` and scans
+    pub fn set_rssi(&mut self, rssi: i8) {
+        let Some(ap) = self.connected.as_mut() else {
+            return;
+        };
+
+        ap.rssi = rssi;
+
+        for visible in self
+            .visible
+            .iter_mut()
+            .filter(|visible| visible.bssid == ap.bssid)
+        {
+            visible.rssi = rssi;
+        }
+    }
+}
+
+impl Wifi for FakeWifi {
+    type Error = FakeError;
+
+    fn scan(&mut self) -> Result<Vec<AccessPoint>, Self::Error> {
+        self.scans += 1;
+
+        if self.fail_scans {
+            return Err(FakeError::ScanFailed);
+        }
+
+        Ok(self.visible.clone())
+    }
+
+    fn connect(&mut self, ap: &AccessPoint, psk: &str) -> Result<(), Self::Error> {
+        self.attempts.push((ap.bssid, psk.to_string()));
+
+        let visible = self
+            .visible
+            .iter()
+            .find(|visible| visible.bssid == ap.bssid)
+            .ok_or(FakeError::NotFound)?;
+
+        if self.refuse.contains(&ap.bssid) {
+            return Err(FakeError::Refused);
+        }
+
+        self.connected = Some(visible.clone());
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        self.connected = None;
+
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected.is_some()
+    }
+
+    fn rssi(&mut self) -> Option<i8> {
+        self.connected.as_ref().map(|ap| ap.rssi)
+    }
+}
@@ -0,0 +1,9 @@
+#![deny(unsafe_code)]
+#![cfg_attr(not(any(test, feature = "fake")), no_std)]
+
+extern crate alloc;
+
+#[cfg(any(test, feature = "fake"))]
+pub mod fake;
+pub mod manager;
+pub mod wifi;
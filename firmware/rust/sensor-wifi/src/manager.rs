@@ -0,0 +1,477 @@
+//! Keeps the station connected to the best of the configured networks.
+//!
+//! Each attempt scans, then tries the known access points in order: networks in the configured
+//! order of preference, the strongest access point of each network first.  Networks only seen
+//! below `min_rssi` go to the back of the list.  When every candidate fails the manager backs off
+//! before scanning again.
+//!
+//! Once connected the link is checked every `check_interval_ms`, reporting its RSSI.  A link weaker
+//! than `roam_below` triggers a scan, and the station moves to a known access point (of the same or
+//! a more preferred network) that is at least `roam_margin` dB stronger.
+//!
+//! Like `Availability` in `sensor-mqtt`, the manager is driven by the caller: call `poll` when
+//! `wait_ms` has elapsed and `disconnected` on the radio's disconnect events.
+
+use alloc::vec::Vec;
+
+use device_config::config::Network;
+use sensor_core::backoff::Backoff;
+
+use crate::wifi::{AccessPoint, Wifi};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ManagerConfig {
+    pub backoff: Backoff,
+    ///Access points weaker than this are only tried after all others, dBm
+    pub min_rssi: i8,
+    ///Look for a better access point when the link is weaker than this, dBm
+    pub roam_below: i8,
+    ///How much stronger another access point must be to move to it, dB
+    pub roam_margin: i8,
+    pub check_interval_ms: u64,
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        Self {
+            backoff: Backoff {
+                initial_ms: 2_000,
+                max_ms: 120_000,
+            },
+            min_rssi: -85,
+            roam_below: -75,
+            roam_margin: 8,
+            check_interval_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum State {
+    ///Waiting to scan and connect
+    Disconnected { failures: u32, retry_at_ms: u64 },
+    Connected {
+        ///Index into the configured networks
+        network: usize,
+        ap: AccessPoint,
+        check_at_ms: u64,
+    },
+}
+
+///What a call changed, for the caller to log or report
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Update {
+    None,
+    ///Joined a network, or moved to a stronger access point when `roamed`
+    Connected {
+        network: usize,
+        roamed: bool,
+    },
+    ///No candidate could be joined; the next attempt is in `retry_in_ms`
+    Failed {
+        failures: u32,
+        retry_in_ms: u64,
+    },
+    ///The link dropped; reconnecting on the next poll
+    Lost,
+    ///Periodic signal report of the current link
+    Rssi(i8),
+}
+
+pub struct Manager {
+    networks: Vec<Network>,
+    config: ManagerConfig,
+    state: State,
+}
+
+impl Manager {
+    ///`networks` most preferred first.  Starts disconnected with the first attempt due immediately.
+    pub fn new(networks: Vec<Network>, config: ManagerConfig) -> Manager {
+        Manager {
+            networks,
+            config,
+            state: State::Disconnected {
+                failures: 0,
+                retry_at_ms: 0,
+            },
+        }
+    }
+
+    pub fn networks(&self) -> &[Network] {
+        &self.networks
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self.state, State::Connected { .. })
+    }
+
+    ///Access point of the current link, with the RSSI of the last check
+    pub fn connection(&self) -> Option<&AccessPoint> {
+        match &self.state {
+            State::Connected { ap, .. } => Some(ap),
+            State::Disconnected { .. } => None,
+        }
+    }
+
+    pub fn rssi(&self) -> Option<i8> {
+        self.connection().map(|ap| ap.rssi)
+    }
+
+    ///Time until `poll` has something to do
+    pub fn wait_ms(&self, now_ms: u64) -> u64 {
+        match self.state {
+            State::Disconnected { retry_at_ms, .. } => retry_at_ms.saturating_sub(now_ms),
+            State::Connected { check_at_ms, .. } => check_at_ms.saturating_sub(now_ms),
+        }
+    }
+
+    pub fn poll<W: Wifi>(&mut self, wifi: &mut W, now_ms: u64) -> Update {
+        match self.state {
+            State::Disconnected {
+                failures,
+                retry_at_ms,
+            } if now_ms >= retry_at_ms => self.join(wifi, now_ms, failures),
+            State::Connected { check_at_ms, .. } if now_ms >= check_at_ms => {
+                self.check(wifi, now_ms)
+            }
+            _ => Update::None,
+        }
+    }
+
+    ///Call on the radio's disconnect event.  Events left over from a roam, when the radio has
+    ///already joined the new access point, are ignored.
+    pub fn disconnected<W: Wifi>(&mut self, wifi: &mut W, now_ms: u64) -> Update {
+        if !self.is_connected() || wifi.is_connected() {
+            return Update::None;
+        }
+
+        self.lost(now_ms)
+    }
+
+    fn join<W: Wifi>(&mut self, wifi: &mut W, now_ms: u64, failures: u32) -> Update {
+        let Ok(seen) = wifi.scan() else {
+            return self.fail(now_ms, failures);
+        };
+
+        for (network, ap) in self.candidates(&seen) {
+            if wifi.connect(&ap, &self.networks[network].psk).is_ok() {
+                self.connected(network, ap, now_ms);
+
+                return Update::Connected {
+                    network,
+                    roamed: false,
+                };
+            }
+        }
+
+        self.fail(now_ms, failures)
+    }
+
+    fn check<W: Wifi>(&mut self, wifi: &mut W, now_ms: u64) -> Update {
+        let Some(rssi) = wifi.rssi().filter(|_| wifi.is_connected()) else {
+            return self.lost(now_ms);
+        };
+
+        let State::Connected {
+            network,
+            ap,
+            check_at_ms,
+        } = &mut self.state
+        else {
+            return Update::None;
+        };
+
+        ap.rssi = rssi;
+        *check_at_ms = now_ms + self.config.check_interval_ms;
+
+        if rssi >= self.config.roam_below {
+            return Update::Rssi(rssi);
+        }
+
+        let (current_network, current_bssid) = (*network, ap.bssid);
+
+        let Ok(seen) = wifi.scan() else {
+            return Update::Rssi(rssi);
+        };
+
+        let better = self
+            .candidates(&seen)
+            .into_iter()
+            .filter(|(network, ap)| {
+                *network <= current_network
+                    && ap.bssid != current_bssid
+                    && ap.rssi as i16 >= rssi as i16 + self.config.roam_margin as i16
+            })
+            .max_by_key(|(_, ap)| ap.rssi);
+
+        let Some((network, ap)) = better else {
+            return Update::Rssi(rssi);
+        };
+
+        let _ = wifi.disconnect();
+
+        if wifi.connect(&ap, &self.networks[network].psk).is_err() {
+            return self.lost(now_ms);
+        }
+
+        self.connected(network, ap, now_ms);
+
+        Update::Connected {
+            network,
+            roamed: true,
+        }
+    }
+
+    ///Known access points in the order to try them
+    fn candidates(&self, seen: &[AccessPoint]) -> Vec<(usize, AccessPoint)> {
+        let mut candidates: Vec<_> = seen
+            .iter()
+            .filter_map(|ap| {
+                let network = self
+                    .networks
+                    .iter()
+                    .position(|network| network.ssid == ap.ssid)?;
+
+                Some((network, ap.clone()))
+            })
+            .collect();
+
+        candidates.sort_by_key(|(network, ap)| {
+            (
+                ap.rssi < self.config.min_rssi,
+                *network,
+                core::cmp::Reverse(ap.rssi),
+            )
+        });
+
+        candidates
+    }
+
+    fn connected(&mut self, network: usize, ap: AccessPoint, now_ms: u64) {
+        self.state = State::Connected {
+            network,
+            ap,
+            check_at_ms: now_ms + self.config.check_interval_ms,
+        };
+    }
+
+    fn fail(&mut self, now_ms: u64, failures: u32) -> Update {
+        let failures = failures + 1;
+        let retry_in_ms = self.config.backoff.delay_ms(failures);
+
+        self.state = State::Disconnected {
+            failures,
+            retry_at_ms: now_ms + retry_in_ms,
+        };
+
+        Update::Failed {
+            failures,
+            retry_in_ms,
+        }
+    }
+
+    fn lost(&mut self, now_ms: u64) -> Update {
+        self.state = State::Disconnected {
+            failures: 0,
+            retry_at_ms: now_ms,
+        };
+
+        Update::Lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeWifi;
+
+    fn ap(ssid: &str, last: u8, rssi: i8) -> AccessPoint {
+        AccessPoint {
+            ssid: ssid.into(),
+            bssid: [0x02, 0, 0, 0, 0, last],
+            channel: 6,
+            rssi,
+        }
+    }
+
+    fn manager() -> Manager {
+        Manager::new(
+            vec![
+                Network {
+                    ssid: "Shed".into(),
+                    psk: "shed-psk".into(),
+                },
+                Network {
+                    ssid: "House".into(),
+                    psk: String::new(),
+                },
+            ],
+            ManagerConfig::default(),
+        )
+    }
+
+    #[test]
+    fn preferred_network_strongest_ap_first() {
+        let mut wifi = FakeWifi::new(vec![
+            ap("House", 1, -40),
+            ap("Shed", 2, -70),
+            ap("Shed", 3, -60),
+            ap("Neighbour", 4, -30),
+        ]);
+        let mut manager = manager();
+
+        assert_eq!(
+            Update::Connected {
+                network: 0,
+                roamed: false
+            },
+            manager.poll(&mut wifi, 0)
+        );
+        assert_eq!(
+            vec![([0x02, 0, 0, 0, 0, 3], "shed-psk".to_string())],
+            wifi.attempts
+        );
+        assert_eq!(Some(-60), manager.rssi());
+    }
+
+    #[test]
+    fn weak_preferred_network_is_tried_last() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -90), ap("House", 2, -65)]);
+        let mut manager = manager();
+
+        manager.poll(&mut wifi, 0);
+
+        assert_eq!("House", manager.connection().unwrap().ssid);
+    }
+
+    #[test]
+    fn falls_back_when_an_ap_refuses() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -50), ap("House", 2, -60)]);
+        wifi.refuse.push([0x02, 0, 0, 0, 0, 1]);
+        let mut manager = manager();
+
+        assert_eq!(
+            Update::Connected {
+                network: 1,
+                roamed: false
+            },
+            manager.poll(&mut wifi, 0)
+        );
+        assert_eq!(2, wifi.attempts.len());
+        assert_eq!("", wifi.attempts[1].1);
+    }
+
+    #[test]
+    fn failures_back_off_exponentially() {
+        let mut wifi = FakeWifi::new(vec![ap("Neighbour", 1, -30)]);
+        let mut manager = manager();
+
+        assert_eq!(
+            Update::Failed {
+                failures: 1,
+                retry_in_ms: 2_000
+            },
+            manager.poll(&mut wifi, 0)
+        );
+        assert_eq!(Update::None, manager.poll(&mut wifi, 1_999));
+        assert_eq!(1, wifi.scans);
+        assert_eq!(
+            Update::Failed {
+                failures: 2,
+                retry_in_ms: 4_000
+            },
+            manager.poll(&mut wifi, 2_000)
+        );
+        assert_eq!(4_000, manager.wait_ms(2_000));
+
+        wifi.visible.push(ap("House", 2, -60));
+
+        assert!(matches!(
+            manager.poll(&mut wifi, 6_000),
+            Update::Connected { network: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn disconnect_reconnects_immediately() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -50), ap("Shed", 2, -60)]);
+        let mut manager = manager();
+
+        manager.poll(&mut wifi, 0);
+
+        wifi.drop_link();
+
+        assert_eq!(Update::Lost, manager.disconnected(&mut wifi, 100));
+        assert_eq!(0, manager.wait_ms(100));
+        assert!(matches!(
+            manager.poll(&mut wifi, 100),
+            Update::Connected { .. }
+        ));
+        assert_eq!(2, manager.connection().unwrap().bssid[5]);
+    }
+
+    #[test]
+    fn stale_disconnect_is_ignored() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -50)]);
+        let mut manager = manager();
+
+        manager.poll(&mut wifi, 0);
+
+        assert_eq!(Update::None, manager.disconnected(&mut wifi, 100));
+        assert!(manager.is_connected());
+    }
+
+    #[test]
+    fn rssi_is_reported_and_loss_noticed_on_check() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -50)]);
+        let mut manager = manager();
+
+        manager.poll(&mut wifi, 0);
+
+        assert_eq!(Update::None, manager.poll(&mut wifi, 29_999));
+
+        wifi.set_rssi(-55);
+
+        assert_eq!(Update::Rssi(-55), manager.poll(&mut wifi, 30_000));
+        assert_eq!(Some(-55), manager.rssi());
+        assert_eq!(1, wifi.scans);
+
+        wifi.drop_link();
+
+        assert_eq!(Update::Lost, manager.poll(&mut wifi, 60_000));
+    }
+
+    #[test]
+    fn weak_link_roams_to_stronger_ap() {
+        let mut wifi = FakeWifi::new(vec![ap("Shed", 1, -60), ap("House", 3, -40)]);
+        let mut manager = manager();
+
+        manager.poll(&mut wifi, 0);
+
+        wifi.set_rssi(-80);
+        wifi.visible.push(ap("Shed", 2, -75));
+
+        //Not enough better
+        assert_eq!(Update::Rssi(-80), manager.poll(&mut wifi, 30_000));
+
+        wifi.visible.push(ap("Shed", 4, -65));
+
+        //A less preferred network is never roamed to, however strong
+        assert_eq!(
+            Update::Connected {
+                network: 0,
+                roamed: true
+            },
+            manager.poll(&mut wifi, 60_000)
+        );
+        assert_eq!([0x02, 0, 0, 0, 0, 4], manager.connection().unwrap().bssid);
+        assert_eq!(
+            Some([0x02, 0, 0, 0, 0, 4]),
+            wifi.connected.map(|ap| ap.bssid)
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub type Bssid = [u8; 6];
+
+///An access point seen in a scan
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: String,
+    pub bssid: Bssid,
+    pub channel: u8,
+    ///dBm
+    pub rssi: i8,
+}
+
+///The station side of a Wi-Fi radio
+pub trait Wifi {
+    type Error: core::fmt::Debug;
+
+    ///Access points currently in range
+    fn scan(&mut self) -> Result<Vec<AccessPoint>, Self::Error>;
+
+    ///Joins `ap`, returning once the link is up or the attempt has failed.  An empty `psk` joins an
+    ///open network.
+    fn connect(&mut self, ap: &AccessPoint, psk: &str) -> Result<(), Self::Error>;
+
+    fn disconnect(&mut self) -> Result<(), Self::Error>;
+
+    fn is_connected(&mut self) -> bool;
+
+    ///Signal of the current link, None when not connected
+    fn rssi(&mut self) -> Option<i8>;
+}
@@ -0,0 +1,57 @@
+///Sensirion CRC-8: polynomial 0x31, initial value 0xFF, no reflection, no final XOR
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0xFF;
+
+    for byte in data {
+        crc ^= byte;
+
+        for _ in 0..8 {
+            if crc & 0x80 != 0 {
+                crc = (crc << 1) ^ 0x31;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+///Validates a response made of 2 data bytes followed by their CRC, repeated.  Returns false if
+///any word fails its check or the buffer is not a whole number of words.
+pub fn validate(buffer: &[u8]) -> bool {
+    buffer.len() % 3 == 0
+        && buffer
+            .chunks_exact(3)
+            .all(|word| crc8(&word[0..2]) == word[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc8_matches_datasheet_example() {
+        assert_eq!(0x92, crc8(&[0xBE, 0xEF]));
+    }
+
+    #[test]
+    fn crc8_of_zero_word() {
+        assert_eq!(0x81, crc8(&[0x00, 0x00]));
+    }
+
+    #[test]
+    fn validate_accepts_correct_words() {
+        assert!(validate(&[0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]));
+    }
+
+    #[test]
+    fn validate_rejects_corrupt_word() {
+        assert!(!validate(&[0xBE, 0xEF, 0x92, 0x00, 0x01, 0x81]));
+    }
+
+    #[test]
+    fn validate_rejects_partial_word() {
+        assert!(!validate(&[0xBE, 0xEF]));
+    }
+}
@@ -0,0 +1,166 @@
+use libm::{expf, logf, roundf, sqrtf};
+
+use crate::types::Measurement;
+
+//Magnus coefficients used by Sensirion's dew point application note, valid -45C to 60C
+const MAGNUS_B: f32 = 17.62;
+const MAGNUS_C: f32 = 243.12;
+
+///Lowest humidity fed to the Magnus formula so ln() stays finite
+const MIN_HUMIDITY_PERCENT: f32 = 0.01;
+
+///Dew point in degrees C, Magnus formula
+pub fn dew_point_celsius(temperature_celsius: f32, humidity_percent: f32) -> f32 {
+    let rh = humidity_percent.clamp(MIN_HUMIDITY_PERCENT, 100.0);
+
+    let gamma = logf(rh / 100.0) + MAGNUS_B * temperature_celsius / (MAGNUS_C + temperature_celsius);
+
+    MAGNUS_C * gamma / (MAGNUS_B - gamma)
+}
+
+///Absolute humidity in g/m^3
+pub fn absolute_humidity_g_m3(temperature_celsius: f32, humidity_percent: f32) -> f32 {
+    let rh = humidity_percent.clamp(0.0, 100.0);
+
+    //Saturation vapour pressure in hPa
+    let saturation_hpa =
+        6.112 * expf(MAGNUS_B * temperature_celsius / (MAGNUS_C + temperature_celsius));
+
+    216.7 * (rh / 100.0 * saturation_hpa) / (273.15 + temperature_celsius)
+}
+
+///Heat index in degrees C, using the NOAA / NWS algorithm (Rothfusz regression with the Steadman
+///approximation for mild conditions)
+pub fn heat_index_celsius(temperature_celsius: f32, humidity_percent: f32) -> f32 {
+    let rh = humidity_percent.clamp(0.0, 100.0);
+    let t = temperature_celsius * 9.0 / 5.0 + 32.0;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+
+    let heat_index_f = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut hi = -42.379 + 2.049_015_2 * t + 10.143_332 * rh
+            - 0.224_755_42 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            hi -= ((13.0 - rh) / 4.0) * sqrtf((17.0 - (t - 95.0).abs()) / 17.0);
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            hi += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+        }
+
+        hi
+    };
+
+    (heat_index_f - 32.0) * 5.0 / 9.0
+}
+
+///Metrics derived from a temperature / humidity pair, in integer milli-units like `Measurement`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DerivedMetrics {
+    ///Dew point in millidegrees C
+    pub dew_point_mc: i32,
+    ///Absolute humidity in mg/m^3
+    pub absolute_humidity_mg_m3: i32,
+    ///Heat index in millidegrees C
+    pub heat_index_mc: i32,
+}
+
+impl DerivedMetrics {
+    pub fn from_measurement(measurement: &Measurement) -> DerivedMetrics {
+        let t = measurement.temperature_celsius();
+        let rh = measurement.humidity_percent();
+
+        DerivedMetrics {
+            dew_point_mc: to_milli(dew_point_celsius(t, rh)),
+            absolute_humidity_mg_m3: to_milli(absolute_humidity_g_m3(t, rh)),
+            heat_index_mc: to_milli(heat_index_celsius(t, rh)),
+        }
+    }
+}
+
+///Rounded to nearest, like the integer conversions in `Measurement`
+fn to_milli(value: f32) -> i32 {
+    roundf(value * 1000.0) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!(
+            (expected - actual).abs() < 0.05,
+            "expected {} got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn dew_point_reference_values() {
+        assert_close(13.85, dew_point_celsius(25.0, 50.0));
+        assert_close(-3.04, dew_point_celsius(0.0, 80.0));
+        assert_close(-16.31, dew_point_celsius(-10.0, 60.0));
+    }
+
+    #[test]
+    fn dew_point_at_saturation_equals_temperature() {
+        assert_close(21.0, dew_point_celsius(21.0, 100.0));
+    }
+
+    #[test]
+    fn dew_point_at_zero_humidity_is_finite() {
+        assert!(dew_point_celsius(25.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn absolute_humidity_reference_values() {
+        assert_close(11.48, absolute_humidity_g_m3(25.0, 50.0));
+        assert_close(3.88, absolute_humidity_g_m3(0.0, 80.0));
+        assert_close(23.59, absolute_humidity_g_m3(32.0, 70.0));
+    }
+
+    #[test]
+    fn heat_index_mild_conditions_use_simple_formula() {
+        assert_close(19.10, heat_index_celsius(20.0, 40.0));
+        assert_close(24.86, heat_index_celsius(25.0, 50.0));
+    }
+
+    #[test]
+    fn heat_index_reference_values() {
+        assert_close(40.41, heat_index_celsius(32.0, 70.0));
+        assert_close(48.27, heat_index_celsius(40.0, 40.0));
+    }
+
+    #[test]
+    fn heat_index_applies_low_humidity_adjustment() {
+        assert_close(31.92, heat_index_celsius(35.0, 10.0));
+    }
+
+    #[test]
+    fn heat_index_applies_high_humidity_adjustment() {
+        assert_close(40.77, heat_index_celsius(30.0, 90.0));
+    }
+
+    #[test]
+    fn derived_metrics_from_measurement() {
+        //25C, 56.5%RH
+        let result = DerivedMetrics::from_measurement(&Measurement::from_ticks(0x6666, 0x8000));
+
+        assert!((result.dew_point_mc - 15_750).abs() < 50);
+        assert!((result.absolute_humidity_mg_m3 - 12_980).abs() < 50);
+    }
+
+    #[test]
+    fn milli_units_are_rounded() {
+        assert_eq!(1_235, to_milli(1.2346));
+        assert_eq!(-1_235, to_milli(-1.2346));
+        assert_eq!(-3_041, to_milli(-3.0406));
+    }
+}
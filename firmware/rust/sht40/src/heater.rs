@@ -0,0 +1,110 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HeaterPower {
+    Mw200,
+    Mw110,
+    Mw20,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HeaterDuration {
+    Ms1000,
+    Ms100,
+}
+
+impl HeaterDuration {
+    pub fn ms(&self) -> u64 {
+        match self {
+            HeaterDuration::Ms1000 => 1000,
+            HeaterDuration::Ms100 => 100,
+        }
+    }
+}
+
+///Datasheet maximum heater duty cycle, in percent
+pub const MAX_DUTY_CYCLE_PERCENT: u64 = 10;
+
+///Tracks heater pulses so the heater stays within `MAX_DUTY_CYCLE_PERCENT`.  A pulse of length
+///`d` starting at `t` blocks the next pulse until `t + d * 100 / MAX_DUTY_CYCLE_PERCENT`.
+///
+///Times are milliseconds from any monotonic clock the caller chooses.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct HeaterGuard {
+    next_allowed_ms: Option<u64>,
+}
+
+impl HeaterGuard {
+    pub fn new() -> HeaterGuard {
+        HeaterGuard {
+            next_allowed_ms: None,
+        }
+    }
+
+    ///Earliest time a pulse may start, or None if the heater has not been used
+    pub fn next_allowed_ms(&self) -> Option<u64> {
+        self.next_allowed_ms
+    }
+
+    pub fn is_allowed(&self, now_ms: u64) -> bool {
+        match self.next_allowed_ms {
+            Some(next) => now_ms >= next,
+            None => true,
+        }
+    }
+
+    ///Records a pulse starting at `now_ms`.  Returns the earliest allowed start time as the error
+    ///if the pulse would exceed the duty cycle.
+    pub fn try_start(&mut self, duration: HeaterDuration, now_ms: u64) -> Result<(), u64> {
+        if let Some(next) = self.next_allowed_ms {
+            if now_ms < next {
+                return Result::Err(next);
+            }
+        }
+
+        self.next_allowed_ms = Some(now_ms + duration.ms() * 100 / MAX_DUTY_CYCLE_PERCENT);
+
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_pulse_is_allowed() {
+        let mut under_test = HeaterGuard::new();
+
+        assert!(under_test.is_allowed(0));
+        assert_eq!(Ok(()), under_test.try_start(HeaterDuration::Ms1000, 0));
+    }
+
+    #[test]
+    fn long_pulse_blocks_for_ten_seconds() {
+        let mut under_test = HeaterGuard::new();
+
+        under_test.try_start(HeaterDuration::Ms1000, 5_000).unwrap();
+
+        assert_eq!(Err(15_000), under_test.try_start(HeaterDuration::Ms100, 14_999));
+        assert_eq!(Ok(()), under_test.try_start(HeaterDuration::Ms100, 15_000));
+    }
+
+    #[test]
+    fn short_pulse_blocks_for_one_second() {
+        let mut under_test = HeaterGuard::new();
+
+        under_test.try_start(HeaterDuration::Ms100, 0).unwrap();
+
+        assert!(!under_test.is_allowed(999));
+        assert!(under_test.is_allowed(1_000));
+    }
+
+    #[test]
+    fn rejected_pulse_does_not_extend_cooldown() {
+        let mut under_test = HeaterGuard::new();
+
+        under_test.try_start(HeaterDuration::Ms100, 0).unwrap();
+        let _ = under_test.try_start(HeaterDuration::Ms1000, 500);
+
+        assert_eq!(Some(1_000), under_test.next_allowed_ms());
+    }
+}
@@ -1,18 +1,14 @@
 #![deny(unsafe_code)]
 #![cfg_attr(not(test), no_std)]
 
-use embedded_hal::i2c::I2c;
-
-
-
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub mod crc;
+#[cfg(feature = "derived")]
+pub mod derived;
+pub mod heater;
+pub mod saturation;
+#[cfg(feature = "sensor")]
+pub mod sensor;
+pub mod sht40;
+#[cfg(feature = "async")]
+pub mod sht40_async;
+pub mod types;
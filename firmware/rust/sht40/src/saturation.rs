@@ -0,0 +1,157 @@
+use crate::heater::{HeaterDuration, HeaterPower};
+use crate::types::Measurement;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Status {
+    Normal,
+    ///Humidity has been above the threshold for the configured number of readings
+    Saturated,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SaturationConfig {
+    ///Raw humidity, in thousandths of a %RH, at or above which a reading counts toward saturation
+    pub threshold_mrh: i32,
+    ///Consecutive readings at or above the threshold before reporting `Status::Saturated`.  0
+    ///disables saturation detection.
+    pub readings: u8,
+    ///Heater pulse used to recover when saturated.  None to only report.
+    pub recovery_heater: Option<(HeaterPower, HeaterDuration)>,
+}
+
+impl Default for SaturationConfig {
+    fn default() -> Self {
+        Self {
+            threshold_mrh: 98_000,
+            readings: 5,
+            recovery_heater: None,
+        }
+    }
+}
+
+///Counts consecutive high humidity readings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SaturationMonitor {
+    config: SaturationConfig,
+    consecutive: u8,
+}
+
+impl SaturationMonitor {
+    pub fn new(config: SaturationConfig) -> SaturationMonitor {
+        SaturationMonitor {
+            config,
+            consecutive: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SaturationConfig {
+        &self.config
+    }
+
+    pub fn update(&mut self, measurement: &Measurement) -> Status {
+        if measurement.humidity_mrh() >= self.config.threshold_mrh {
+            self.consecutive = self.consecutive.saturating_add(1);
+        } else {
+            self.consecutive = 0;
+        }
+
+        if self.config.readings > 0 && self.consecutive >= self.config.readings {
+            Status::Saturated
+        } else {
+            Status::Normal
+        }
+    }
+
+    ///Starts counting again, e.g. after a recovery heater pulse
+    pub fn reset(&mut self) {
+        self.consecutive = 0;
+    }
+}
+
+impl Default for SaturationMonitor {
+    fn default() -> Self {
+        Self::new(SaturationConfig::default())
+    }
+}
+
+///A measurement with humidity cropped to 0 - 100 %RH and saturation tracking applied
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Reading {
+    ///The measurement as read, before any recovery heater pulse
+    pub measurement: Measurement,
+    ///Relative humidity in thousandths of a %RH, cropped to 0 - 100 %RH
+    pub humidity_mrh: i32,
+    ///True if the raw humidity was outside 0 - 100 %RH
+    pub humidity_clamped: bool,
+    pub status: Status,
+    ///True if a recovery heater pulse ran after this measurement
+    pub heater_triggered: bool,
+    ///True if a recovery heater pulse was due but failed.  Saturation stays set, so the next read
+    ///tries again.
+    pub heater_failed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //~99 %RH
+    const WET_TICKS: u16 = 0xD700;
+    //~50 %RH
+    const DRY_TICKS: u16 = 0x7000;
+
+    fn config(readings: u8) -> SaturationConfig {
+        SaturationConfig {
+            readings,
+            ..SaturationConfig::default()
+        }
+    }
+
+    #[test]
+    fn saturated_after_consecutive_wet_readings() {
+        let mut under_test = SaturationMonitor::new(config(3));
+        let wet = Measurement::from_ticks(0x6666, WET_TICKS);
+
+        assert_eq!(Status::Normal, under_test.update(&wet));
+        assert_eq!(Status::Normal, under_test.update(&wet));
+        assert_eq!(Status::Saturated, under_test.update(&wet));
+        assert_eq!(Status::Saturated, under_test.update(&wet));
+    }
+
+    #[test]
+    fn dry_reading_resets_count() {
+        let mut under_test = SaturationMonitor::new(config(2));
+        let wet = Measurement::from_ticks(0x6666, WET_TICKS);
+        let dry = Measurement::from_ticks(0x6666, DRY_TICKS);
+
+        under_test.update(&wet);
+        under_test.update(&dry);
+
+        assert_eq!(Status::Normal, under_test.update(&wet));
+        assert_eq!(Status::Saturated, under_test.update(&wet));
+    }
+
+    #[test]
+    fn zero_readings_disables_detection() {
+        let mut under_test = SaturationMonitor::new(config(0));
+        let wet = Measurement::from_ticks(0x6666, WET_TICKS);
+        let dry = Measurement::from_ticks(0x6666, DRY_TICKS);
+
+        assert_eq!(Status::Normal, under_test.update(&dry));
+        assert_eq!(Status::Normal, under_test.update(&wet));
+        assert_eq!(Status::Normal, under_test.update(&wet));
+    }
+
+    #[test]
+    fn reset_clears_saturation() {
+        let mut under_test = SaturationMonitor::new(config(2));
+        let wet = Measurement::from_ticks(0x6666, WET_TICKS);
+
+        under_test.update(&wet);
+        assert_eq!(Status::Saturated, under_test.update(&wet));
+
+        under_test.reset();
+
+        assert_eq!(Status::Normal, under_test.update(&wet));
+    }
+}
@@ -0,0 +1,196 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, I2c};
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use sensor_core::scan::Device;
+use sensor_core::sensor::{Error, Sensor};
+
+use crate::sht40::{self, Sht40};
+use crate::types::Precision;
+
+///`Sensor` adapter for an SHT40.  Each read produces temperature and humidity cropped to
+///0 - 100 %RH, with the driver's saturation handling applied.
+pub struct Sht40Sensor<I2C>
+where
+    I2C: I2c,
+{
+    driver: Sht40<I2C>,
+    channel: ChannelId,
+    precision: Precision,
+}
+
+impl<I2C, E> Sht40Sensor<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(driver: Sht40<I2C>, instance: &'static str) -> Sht40Sensor<I2C> {
+        Sht40Sensor {
+            driver,
+            channel: ChannelId::new("sht40", instance),
+            precision: Precision::High,
+        }
+    }
+
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+    }
+
+    pub fn driver(&mut self) -> &mut Sht40<I2C> {
+        &mut self.driver
+    }
+
+    pub fn destroy(self) -> Sht40<I2C> {
+        self.driver
+    }
+}
+
+impl<E> From<sht40::Error<E>> for Error
+where
+    E: i2c::Error,
+{
+    fn from(value: sht40::Error<E>) -> Self {
+        match value {
+            sht40::Error::I2C(error) => Error::I2C(error.kind()),
+            sht40::Error::Crc => Error::Crc,
+            _ => Error::Other,
+        }
+    }
+}
+
+impl<I2C, E> Sensor for Sht40Sensor<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    fn channel(&self) -> ChannelId {
+        self.channel
+    }
+
+    fn quantities(&self) -> &'static [(Quantity, Unit)] {
+        &[
+            (Quantity::Temperature, Unit::MilliCelsius),
+            (Quantity::RelativeHumidity, Unit::MilliPercentRh),
+        ]
+    }
+
+    fn read(
+        &mut self,
+        timestamp_ms: u64,
+        mut delay: &mut dyn DelayNs,
+        sink: &mut dyn FnMut(Reading),
+    ) -> Result<(), Error> {
+        let reading = self.driver.read(self.precision, timestamp_ms, &mut delay)?;
+
+        sink(Reading::new(
+            self.channel,
+            Quantity::Temperature,
+            Unit::MilliCelsius,
+            reading.measurement.temperature_mc(),
+            timestamp_ms,
+        ));
+
+        sink(Reading::new(
+            self.channel,
+            Quantity::RelativeHumidity,
+            Unit::MilliPercentRh,
+            reading.humidity_mrh,
+            timestamp_ms,
+        ));
+
+        Result::Ok(())
+    }
+}
+
+///`sensor_core::scan::Identify` for the SHT4x, by its CRC-checked serial number
+pub fn identify<I2C, D>(i2c: &mut I2C, delay: &mut D, address: u8) -> Option<Device>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    let address = sht40::Address::ALL
+        .into_iter()
+        .find(|candidate| u8::from(*candidate) == address)?;
+
+    let serial_number = Sht40::with_address(&mut *i2c, address)
+        .serial_number(delay)
+        .ok()?;
+
+    Some(Device::Sht4x { serial_number })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use crate::sht40::{Address, DEFAULT_ADDRESS};
+
+    #[test]
+    fn read_produces_temperature_and_humidity() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40Sensor::new(Sht40::new(I2cMock::new(&expectations)), "inside");
+
+        let mut readings = Vec::new();
+
+        under_test
+            .read(100, &mut NoopDelay::new(), &mut |reading| readings.push(reading))
+            .unwrap();
+
+        let channel = ChannelId::new("sht40", "inside");
+
+        assert_eq!(
+            vec![
+                Reading::new(channel, Quantity::Temperature, Unit::MilliCelsius, 25_000, 100),
+                Reading::new(channel, Quantity::RelativeHumidity, Unit::MilliPercentRh, 56_501, 100),
+            ],
+            readings
+        );
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn read_with_bad_crc_reports_crc_error() {
+        let expectations = [
+            I2cTransaction::write(0x45, vec![0xE0]),
+            I2cTransaction::read(0x45, vec![0x66, 0x66, 0x00, 0x80, 0x00, 0xA2]),
+        ];
+
+        let driver = Sht40::with_address(I2cMock::new(&expectations), Address::Bd1b);
+        let mut under_test = Sht40Sensor::new(driver, "outside");
+
+        under_test.set_precision(Precision::Low);
+
+        let result = under_test.read(0, &mut NoopDelay::new(), &mut |_| panic!("no reading expected"));
+
+        assert_eq!(Err(Error::Crc), result);
+
+        under_test.destroy().destroy().done();
+    }
+
+    #[test]
+    fn identify_reads_serial_number() {
+        let expectations = [
+            I2cTransaction::write(0x44, vec![0x89]),
+            I2cTransaction::read(0x44, vec![0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]),
+            I2cTransaction::write(0x45, vec![0x89]),
+            I2cTransaction::read(0x45, vec![0xBE, 0xEF, 0x00, 0x00, 0x00, 0x81]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        assert_eq!(
+            Some(Device::Sht4x { serial_number: 0xBEEF0000 }),
+            identify(&mut i2c, &mut NoopDelay::new(), 0x44)
+        );
+        assert_eq!(None, identify(&mut i2c, &mut NoopDelay::new(), 0x45));
+        assert_eq!(None, identify(&mut i2c, &mut NoopDelay::new(), 0x40));
+
+        i2c.done();
+    }
+}
@@ -0,0 +1,635 @@
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{self, I2c},
+};
+
+use crate::crc;
+use crate::heater::{HeaterDuration, HeaterGuard, HeaterPower};
+use crate::saturation::{Reading, SaturationConfig, SaturationMonitor, Status};
+use crate::types::{Measurement, Precision};
+
+///I2C address, fixed per SHT4x part variant
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Address {
+    ///SHT4x-AD1B
+    Ad1b = 0x44,
+    ///SHT4x-BD1B
+    Bd1b = 0x45,
+    ///SHT4x-CD1B
+    Cd1b = 0x46,
+}
+
+impl Address {
+    pub const ALL: [Address; 3] = [Address::Ad1b, Address::Bd1b, Address::Cd1b];
+}
+
+impl From<Address> for u8 {
+    fn from(value: Address) -> u8 {
+        value as u8
+    }
+}
+
+///SHT40-AD1B address
+pub const DEFAULT_ADDRESS: u8 = Address::Ad1b as u8;
+
+pub struct Sht40<I2C>
+where
+    I2C: I2c,
+{
+    i2c: I2C,
+    address: u8,
+    heater_guard: HeaterGuard,
+    saturation: SaturationMonitor,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error<E>
+where
+    E: i2c::Error,
+{
+    /// A response word failed its CRC check.
+    Crc,
+    /// The heater would exceed its duty cycle.  Holds the earliest allowed start time in ms.
+    HeaterCooldown(u64),
+    /// Failed I2C communication.
+    I2C(E),
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum Command {
+    MeasureHighPrecision = 0xFD,
+    MeasureMediumPrecision = 0xF6,
+    MeasureLowPrecision = 0xE0,
+    ReadSerialNumber = 0x89,
+    SoftReset = 0x94,
+    Heater200mW1s = 0x39,
+    Heater200mW100ms = 0x32,
+    Heater110mW1s = 0x2F,
+    Heater110mW100ms = 0x24,
+    Heater20mW1s = 0x1E,
+    Heater20mW100ms = 0x15,
+}
+
+impl Command {
+    ///Worst case time before the response is available, in microseconds
+    pub(crate) fn duration_us(&self) -> u32 {
+        match self {
+            Command::MeasureHighPrecision => 8_300,
+            Command::MeasureMediumPrecision => 4_500,
+            Command::MeasureLowPrecision => 1_700,
+            Command::ReadSerialNumber => 1_000,
+            Command::SoftReset => 1_000,
+            Command::Heater200mW1s | Command::Heater110mW1s | Command::Heater20mW1s => 1_100_000,
+            Command::Heater200mW100ms | Command::Heater110mW100ms | Command::Heater20mW100ms => 110_000,
+        }
+    }
+
+    pub(crate) fn heater(power: HeaterPower, duration: HeaterDuration) -> Command {
+        match (power, duration) {
+            (HeaterPower::Mw200, HeaterDuration::Ms1000) => Command::Heater200mW1s,
+            (HeaterPower::Mw200, HeaterDuration::Ms100) => Command::Heater200mW100ms,
+            (HeaterPower::Mw110, HeaterDuration::Ms1000) => Command::Heater110mW1s,
+            (HeaterPower::Mw110, HeaterDuration::Ms100) => Command::Heater110mW100ms,
+            (HeaterPower::Mw20, HeaterDuration::Ms1000) => Command::Heater20mW1s,
+            (HeaterPower::Mw20, HeaterDuration::Ms100) => Command::Heater20mW100ms,
+        }
+    }
+}
+
+impl From<Precision> for Command {
+    fn from(value: Precision) -> Self {
+        match value {
+            Precision::High => Command::MeasureHighPrecision,
+            Precision::Medium => Command::MeasureMediumPrecision,
+            Precision::Low => Command::MeasureLowPrecision,
+        }
+    }
+}
+
+impl<I2C, E> Sht40<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(i2c: I2C) -> Sht40<I2C> {
+        Self::with_address(i2c, Address::Ad1b)
+    }
+
+    pub fn with_address(i2c: I2C, address: Address) -> Sht40<I2C> {
+        Sht40 {
+            i2c,
+            address: address.into(),
+            heater_guard: HeaterGuard::new(),
+            saturation: SaturationMonitor::default(),
+        }
+    }
+
+    pub fn destroy(self) -> I2C {
+        self.i2c
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    fn write_command(&mut self, command: Command) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[command as u8])
+            .map_err(Error::I2C)
+    }
+
+    ///Sends a command, waits for it to complete and reads back its 6 byte, CRC-checked response
+    fn command_response<D: DelayNs>(
+        &mut self,
+        command: Command,
+        delay: &mut D,
+    ) -> Result<[u8; 6], Error<E>> {
+        self.write_command(command)?;
+
+        self.read_response(command, delay)
+    }
+
+    fn read_response<D: DelayNs>(
+        &mut self,
+        command: Command,
+        delay: &mut D,
+    ) -> Result<[u8; 6], Error<E>> {
+        delay.delay_us(command.duration_us());
+
+        let mut read_buffer: [u8; 6] = [0x00; 6];
+
+        self.i2c
+            .read(self.address, &mut read_buffer)
+            .map_err(Error::I2C)?;
+
+        if !crc::validate(&read_buffer) {
+            return Result::Err(Error::Crc);
+        }
+
+        Result::Ok(read_buffer)
+    }
+
+    pub fn measure<D: DelayNs>(
+        &mut self,
+        precision: Precision,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<E>> {
+        let response = self.command_response(Command::from(precision), delay)?;
+
+        Result::Ok(measurement_from_response(&response))
+    }
+
+    pub fn serial_number<D: DelayNs>(&mut self, delay: &mut D) -> Result<u32, Error<E>> {
+        let response = self.command_response(Command::ReadSerialNumber, delay)?;
+
+        Result::Ok(u32::from_be_bytes([
+            response[0],
+            response[1],
+            response[3],
+            response[4],
+        ]))
+    }
+
+    ///Runs the heater for `duration` and returns the high precision measurement taken at the end
+    ///of the pulse.  `now_ms` comes from the caller's monotonic clock and is used to keep the heater
+    ///within its duty cycle across calls.
+    pub fn heat<D: DelayNs>(
+        &mut self,
+        power: HeaterPower,
+        duration: HeaterDuration,
+        now_ms: u64,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<E>> {
+        let mut guard = self.heater_guard;
+
+        guard
+            .try_start(duration, now_ms)
+            .map_err(Error::HeaterCooldown)?;
+
+        let command = Command::heater(power, duration);
+
+        self.write_command(command)?;
+
+        //Once the command is accepted the heater is on, whether or not the response reads back
+        self.heater_guard = guard;
+
+        let response = self.read_response(command, delay)?;
+
+        Result::Ok(measurement_from_response(&response))
+    }
+
+    pub fn heater_guard(&self) -> &HeaterGuard {
+        &self.heater_guard
+    }
+
+    pub fn set_saturation_config(&mut self, config: SaturationConfig) {
+        self.saturation = SaturationMonitor::new(config);
+    }
+
+    ///Measures and applies humidity cropping and saturation tracking.  When saturated and a recovery
+    ///heater is configured, runs one heater pulse if the duty cycle allows it.
+    pub fn read<D: DelayNs>(
+        &mut self,
+        precision: Precision,
+        now_ms: u64,
+        delay: &mut D,
+    ) -> Result<Reading, Error<E>> {
+        let measurement = self.measure(precision, delay)?;
+
+        let status = self.saturation.update(&measurement);
+
+        let mut heater_triggered = false;
+        let mut heater_failed = false;
+
+        if status == Status::Saturated {
+            if let Some((power, duration)) = self.saturation.config().recovery_heater {
+                if self.heater_guard.is_allowed(now_ms) {
+                    //The measurement is still good, so a failed pulse is reported rather than returned
+                    if self.heat(power, duration, now_ms, delay).is_ok() {
+                        self.saturation.reset();
+
+                        heater_triggered = true;
+                    } else {
+                        heater_failed = true;
+                    }
+                }
+            }
+        }
+
+        Result::Ok(Reading {
+            measurement,
+            humidity_mrh: measurement.humidity_mrh_clamped(),
+            humidity_clamped: measurement.is_humidity_out_of_range(),
+            status,
+            heater_triggered,
+            heater_failed,
+        })
+    }
+
+    pub fn soft_reset<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        self.write_command(Command::SoftReset)?;
+
+        delay.delay_us(Command::SoftReset.duration_us());
+
+        Result::Ok(())
+    }
+}
+
+///Tries every SHT4x address and reads its serial number.  Entries are in `Address::ALL` order;
+///None where nothing answered or the response failed its CRC.
+pub fn probe<I2C, D>(i2c: &mut I2C, delay: &mut D) -> [(Address, Option<u32>); 3]
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    Address::ALL.map(|address| {
+        let serial_number = Sht40::with_address(&mut *i2c, address)
+            .serial_number(delay)
+            .ok();
+
+        (address, serial_number)
+    })
+}
+
+pub(crate) fn measurement_from_response(response: &[u8; 6]) -> Measurement {
+    Measurement::from_ticks(
+        u16::from_be_bytes([response[0], response[1]]),
+        u16::from_be_bytes([response[3], response[4]]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    #[test]
+    fn measure_high_precision_returns_ticks() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test
+            .measure(Precision::High, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(0x6666, result.temperature_ticks());
+        assert_eq!(0x8000, result.humidity_ticks());
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn measure_low_precision_sends_low_precision_command() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xE0]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x00, 0x00, 0x81, 0x00, 0x00, 0x81]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test
+            .measure(Precision::Low, &mut NoopDelay::new())
+            .unwrap();
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn measure_with_bad_crc_returns_error() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xF6]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x00, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test.measure(Precision::Medium, &mut NoopDelay::new());
+
+        assert!(matches!(result, Err(Error::Crc)));
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn serial_number_joins_both_words() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x89]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test.serial_number(&mut NoopDelay::new()).unwrap();
+
+        assert_eq!(0xBEEF0000, result);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn soft_reset_sends_reset_command() {
+        let expectations = [I2cTransaction::write(DEFAULT_ADDRESS, vec![0x94])];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test.soft_reset(&mut NoopDelay::new()).unwrap();
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn heat_returns_measurement_after_pulse() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x32]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test
+            .heat(HeaterPower::Mw200, HeaterDuration::Ms100, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(0x6666, result.temperature_ticks());
+        assert_eq!(Some(1_000), under_test.heater_guard().next_allowed_ms());
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn heat_within_cooldown_returns_error_without_bus_traffic() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x1E]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test
+            .heat(HeaterPower::Mw20, HeaterDuration::Ms1000, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        let result = under_test.heat(
+            HeaterPower::Mw20,
+            HeaterDuration::Ms1000,
+            9_999,
+            &mut NoopDelay::new(),
+        );
+
+        assert!(matches!(result, Err(Error::HeaterCooldown(10_000))));
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn failed_heat_does_not_start_cooldown() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x2F])
+                .with_error(embedded_hal::i2c::ErrorKind::Other),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test.heat(
+            HeaterPower::Mw110,
+            HeaterDuration::Ms1000,
+            0,
+            &mut NoopDelay::new(),
+        );
+
+        assert!(matches!(result, Err(Error::I2C(_))));
+        assert_eq!(None, under_test.heater_guard().next_allowed_ms());
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_clamps_humidity_above_range() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0xFF, 0xFF, 0xAC]),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        let result = under_test
+            .read(Precision::High, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(100_000, result.humidity_mrh);
+        assert!(result.humidity_clamped);
+        assert_eq!(Status::Normal, result.status);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_when_saturated_runs_recovery_heater() {
+        let wet = vec![0x66, 0x66, 0x93, 0xFF, 0xFF, 0xAC];
+
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, wet.clone()),
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, wet.clone()),
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x32]),
+            I2cTransaction::read(DEFAULT_ADDRESS, wet),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test.set_saturation_config(SaturationConfig {
+            threshold_mrh: 98_000,
+            readings: 2,
+            recovery_heater: Some((HeaterPower::Mw200, HeaterDuration::Ms100)),
+        });
+
+        let first = under_test
+            .read(Precision::High, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(Status::Normal, first.status);
+        assert!(!first.heater_triggered);
+
+        let second = under_test
+            .read(Precision::High, 1_000, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(Status::Saturated, second.status);
+        assert!(second.heater_triggered);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_keeps_measurement_when_recovery_heater_fails() {
+        let wet = vec![0x66, 0x66, 0x93, 0xFF, 0xFF, 0xAC];
+
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, wet),
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x32])
+                .with_error(embedded_hal::i2c::ErrorKind::Other),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test.set_saturation_config(SaturationConfig {
+            readings: 1,
+            recovery_heater: Some((HeaterPower::Mw200, HeaterDuration::Ms100)),
+            ..SaturationConfig::default()
+        });
+
+        let result = under_test
+            .read(Precision::High, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(Status::Saturated, result.status);
+        assert_eq!(100_000, result.humidity_mrh);
+        assert!(!result.heater_triggered);
+        assert!(result.heater_failed);
+        assert_eq!(None, under_test.heater_guard().next_allowed_ms());
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_when_saturated_without_recovery_heater_only_reports() {
+        let wet = vec![0x66, 0x66, 0x93, 0xFF, 0xFF, 0xAC];
+
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, wet),
+        ];
+
+        let mut under_test = Sht40::new(I2cMock::new(&expectations));
+
+        under_test.set_saturation_config(SaturationConfig {
+            readings: 1,
+            ..SaturationConfig::default()
+        });
+
+        let result = under_test
+            .read(Precision::High, 0, &mut NoopDelay::new())
+            .unwrap();
+
+        assert_eq!(Status::Saturated, result.status);
+        assert!(!result.heater_triggered);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn with_address_uses_variant_address() {
+        let expectations = [
+            I2cTransaction::write(0x46, vec![0x94]),
+        ];
+
+        let mut under_test = Sht40::with_address(I2cMock::new(&expectations), Address::Cd1b);
+
+        assert_eq!(0x46, under_test.address());
+
+        under_test.soft_reset(&mut NoopDelay::new()).unwrap();
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn probe_reports_serial_number_for_responding_addresses() {
+        let expectations = [
+            I2cTransaction::write(0x44, vec![0x89])
+                .with_error(embedded_hal::i2c::ErrorKind::NoAcknowledge(
+                    embedded_hal::i2c::NoAcknowledgeSource::Address,
+                )),
+            I2cTransaction::write(0x45, vec![0x89]),
+            I2cTransaction::read(0x45, vec![0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]),
+            I2cTransaction::write(0x46, vec![0x89]),
+            I2cTransaction::read(0x46, vec![0xBE, 0xEF, 0x00, 0x00, 0x00, 0x81]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let result = probe(&mut i2c, &mut NoopDelay::new());
+
+        assert_eq!(
+            [
+                (Address::Ad1b, None),
+                (Address::Bd1b, Some(0xBEEF0000)),
+                (Address::Cd1b, None),
+            ],
+            result
+        );
+
+        i2c.done();
+    }
+
+    #[test]
+    fn two_sensors_share_a_bus() {
+        let expectations = [
+            I2cTransaction::write(0x44, vec![0xFD]),
+            I2cTransaction::read(0x44, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+            I2cTransaction::write(0x45, vec![0xFD]),
+            I2cTransaction::read(0x45, vec![0x00, 0x00, 0x81, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut inside = Sht40::with_address(i2c.clone(), Address::Ad1b);
+        let mut outside = Sht40::with_address(i2c.clone(), Address::Bd1b);
+
+        let inside_result = inside.measure(Precision::High, &mut NoopDelay::new()).unwrap();
+        let outside_result = outside.measure(Precision::High, &mut NoopDelay::new()).unwrap();
+
+        assert_eq!(25_000, inside_result.temperature_mc());
+        assert_eq!(-45_000, outside_result.temperature_mc());
+
+        i2c.done();
+    }
+}
@@ -0,0 +1,315 @@
+use embedded_hal::i2c;
+use embedded_hal_async::{delay::DelayNs, i2c::I2c};
+
+use crate::crc;
+use crate::heater::{HeaterDuration, HeaterGuard, HeaterPower};
+use crate::saturation::{Reading, SaturationConfig, SaturationMonitor, Status};
+use crate::sht40::{measurement_from_response, Address, Command, Error};
+use crate::types::{Measurement, Precision};
+
+///Async variant of `Sht40`.  Conversion and heater waits await the delay rather than blocking,
+///so other tasks on the executor keep running.
+pub struct Sht40Async<I2C>
+where
+    I2C: I2c,
+{
+    i2c: I2C,
+    address: u8,
+    heater_guard: HeaterGuard,
+    saturation: SaturationMonitor,
+}
+
+impl<I2C, E> Sht40Async<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: i2c::Error,
+{
+    pub fn new(i2c: I2C) -> Sht40Async<I2C> {
+        Self::with_address(i2c, Address::Ad1b)
+    }
+
+    pub fn with_address(i2c: I2C, address: Address) -> Sht40Async<I2C> {
+        Sht40Async {
+            i2c,
+            address: address.into(),
+            heater_guard: HeaterGuard::new(),
+            saturation: SaturationMonitor::default(),
+        }
+    }
+
+    pub fn destroy(self) -> I2C {
+        self.i2c
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    async fn write_command(&mut self, command: Command) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[command as u8])
+            .await
+            .map_err(Error::I2C)
+    }
+
+    async fn command_response<D: DelayNs>(
+        &mut self,
+        command: Command,
+        delay: &mut D,
+    ) -> Result<[u8; 6], Error<E>> {
+        self.write_command(command).await?;
+
+        self.read_response(command, delay).await
+    }
+
+    async fn read_response<D: DelayNs>(
+        &mut self,
+        command: Command,
+        delay: &mut D,
+    ) -> Result<[u8; 6], Error<E>> {
+        delay.delay_us(command.duration_us()).await;
+
+        let mut read_buffer: [u8; 6] = [0x00; 6];
+
+        self.i2c
+            .read(self.address, &mut read_buffer)
+            .await
+            .map_err(Error::I2C)?;
+
+        if !crc::validate(&read_buffer) {
+            return Result::Err(Error::Crc);
+        }
+
+        Result::Ok(read_buffer)
+    }
+
+    pub async fn measure<D: DelayNs>(
+        &mut self,
+        precision: Precision,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<E>> {
+        let response = self.command_response(Command::from(precision), delay).await?;
+
+        Result::Ok(measurement_from_response(&response))
+    }
+
+    pub async fn serial_number<D: DelayNs>(&mut self, delay: &mut D) -> Result<u32, Error<E>> {
+        let response = self
+            .command_response(Command::ReadSerialNumber, delay)
+            .await?;
+
+        Result::Ok(u32::from_be_bytes([
+            response[0],
+            response[1],
+            response[3],
+            response[4],
+        ]))
+    }
+
+    ///See `Sht40::heat`
+    pub async fn heat<D: DelayNs>(
+        &mut self,
+        power: HeaterPower,
+        duration: HeaterDuration,
+        now_ms: u64,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<E>> {
+        let mut guard = self.heater_guard;
+
+        guard
+            .try_start(duration, now_ms)
+            .map_err(Error::HeaterCooldown)?;
+
+        let command = Command::heater(power, duration);
+
+        self.write_command(command).await?;
+
+        //Once the command is accepted the heater is on, whether or not the response reads back
+        self.heater_guard = guard;
+
+        let response = self.read_response(command, delay).await?;
+
+        Result::Ok(measurement_from_response(&response))
+    }
+
+    pub fn heater_guard(&self) -> &HeaterGuard {
+        &self.heater_guard
+    }
+
+    pub fn set_saturation_config(&mut self, config: SaturationConfig) {
+        self.saturation = SaturationMonitor::new(config);
+    }
+
+    ///See `Sht40::read`
+    pub async fn read<D: DelayNs>(
+        &mut self,
+        precision: Precision,
+        now_ms: u64,
+        delay: &mut D,
+    ) -> Result<Reading, Error<E>> {
+        let measurement = self.measure(precision, delay).await?;
+
+        let status = self.saturation.update(&measurement);
+
+        let mut heater_triggered = false;
+        let mut heater_failed = false;
+
+        if status == Status::Saturated {
+            if let Some((power, duration)) = self.saturation.config().recovery_heater {
+                if self.heater_guard.is_allowed(now_ms) {
+                    //The measurement is still good, so a failed pulse is reported rather than returned
+                    if self.heat(power, duration, now_ms, delay).await.is_ok() {
+                        self.saturation.reset();
+
+                        heater_triggered = true;
+                    } else {
+                        heater_failed = true;
+                    }
+                }
+            }
+        }
+
+        Result::Ok(Reading {
+            measurement,
+            humidity_mrh: measurement.humidity_mrh_clamped(),
+            humidity_clamped: measurement.is_humidity_out_of_range(),
+            status,
+            heater_triggered,
+            heater_failed,
+        })
+    }
+
+    pub async fn soft_reset<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        self.write_command(Command::SoftReset).await?;
+
+        delay.delay_us(Command::SoftReset.duration_us()).await;
+
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use crate::sht40::DEFAULT_ADDRESS;
+
+    ///Records the total time awaited
+    struct RecordingDelay {
+        total_ns: u64,
+    }
+
+    impl DelayNs for RecordingDelay {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.total_ns += u64::from(ns);
+        }
+    }
+
+    #[test]
+    fn measure_awaits_conversion_delay() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40Async::new(I2cMock::new(&expectations));
+        let mut delay = RecordingDelay { total_ns: 0 };
+
+        let result = block_on(under_test.measure(Precision::High, &mut delay)).unwrap();
+
+        assert_eq!(25_000, result.temperature_mc());
+        assert_eq!(8_300_000, delay.total_ns);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn measure_with_bad_crc_returns_error() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xE0]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x00, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40Async::new(I2cMock::new(&expectations));
+
+        let result = block_on(under_test.measure(Precision::Low, &mut NoopDelay::new()));
+
+        assert!(matches!(result, Err(Error::Crc)));
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn serial_number_uses_configured_address() {
+        let expectations = [
+            I2cTransaction::write(0x45, vec![0x89]),
+            I2cTransaction::read(0x45, vec![0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]),
+        ];
+
+        let mut under_test = Sht40Async::with_address(I2cMock::new(&expectations), Address::Bd1b);
+
+        let result = block_on(under_test.serial_number(&mut NoopDelay::new())).unwrap();
+
+        assert_eq!(0xBEEF0000, result);
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn heat_awaits_pulse_and_respects_cooldown() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x24]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40Async::new(I2cMock::new(&expectations));
+        let mut delay = RecordingDelay { total_ns: 0 };
+
+        block_on(under_test.heat(HeaterPower::Mw110, HeaterDuration::Ms100, 0, &mut delay))
+            .unwrap();
+
+        assert_eq!(110_000_000, delay.total_ns);
+
+        let result = block_on(under_test.heat(
+            HeaterPower::Mw110,
+            HeaterDuration::Ms100,
+            500,
+            &mut delay,
+        ));
+
+        assert!(matches!(result, Err(Error::HeaterCooldown(1_000))));
+
+        under_test.destroy().done();
+    }
+
+    #[test]
+    fn read_triggers_recovery_heater_when_saturated() {
+        let expectations = [
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0xFD]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0xFF, 0xFF, 0xAC]),
+            I2cTransaction::write(DEFAULT_ADDRESS, vec![0x15]),
+            I2cTransaction::read(DEFAULT_ADDRESS, vec![0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]),
+        ];
+
+        let mut under_test = Sht40Async::new(I2cMock::new(&expectations));
+
+        under_test.set_saturation_config(SaturationConfig {
+            readings: 1,
+            recovery_heater: Some((HeaterPower::Mw20, HeaterDuration::Ms100)),
+            ..SaturationConfig::default()
+        });
+
+        let result =
+            block_on(under_test.read(Precision::High, 0, &mut NoopDelay::new())).unwrap();
+
+        assert_eq!(Status::Saturated, result.status);
+        assert_eq!(100_000, result.humidity_mrh);
+        assert!(result.heater_triggered);
+
+        under_test.destroy().done();
+    }
+}
@@ -0,0 +1,157 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Precision {
+    /// Highest repeatability, ~8.2ms conversion
+    High,
+    /// ~4.5ms conversion
+    Medium,
+    /// Lowest repeatability, ~1.7ms conversion
+    Low,
+}
+
+///Raw temperature and humidity ticks as returned by the sensor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Measurement {
+    temperature_ticks: u16,
+    humidity_ticks: u16,
+}
+
+impl Measurement {
+    pub fn from_ticks(temperature_ticks: u16, humidity_ticks: u16) -> Measurement {
+        Measurement {
+            temperature_ticks,
+            humidity_ticks,
+        }
+    }
+
+    pub fn temperature_ticks(&self) -> u16 {
+        self.temperature_ticks
+    }
+
+    pub fn humidity_ticks(&self) -> u16 {
+        self.humidity_ticks
+    }
+
+    ///Temperature in degrees C.  T = -45 + 175 * ticks / 65535
+    pub fn temperature_celsius(&self) -> f32 {
+        -45.0 + 175.0 * f32::from(self.temperature_ticks) / 65535.0
+    }
+
+    ///Relative humidity in %RH, unclamped.  RH = -6 + 125 * ticks / 65535
+    pub fn humidity_percent(&self) -> f32 {
+        -6.0 + 125.0 * f32::from(self.humidity_ticks) / 65535.0
+    }
+
+    ///Temperature in millidegrees C, integer math only
+    pub fn temperature_mc(&self) -> i32 {
+        -45_000 + scale_ticks(self.temperature_ticks, 175_000)
+    }
+
+    ///Relative humidity in thousandths of a %RH, unclamped, integer math only
+    pub fn humidity_mrh(&self) -> i32 {
+        -6_000 + scale_ticks(self.humidity_ticks, 125_000)
+    }
+
+    ///Relative humidity in thousandths of a %RH, cropped to 0 - 100 %RH as the datasheet recommends
+    pub fn humidity_mrh_clamped(&self) -> i32 {
+        self.humidity_mrh().clamp(0, 100_000)
+    }
+
+    ///Relative humidity in %RH, cropped to 0 - 100 %RH
+    pub fn humidity_percent_clamped(&self) -> f32 {
+        self.humidity_percent().clamp(0.0, 100.0)
+    }
+
+    ///True when the raw humidity is outside 0 - 100 %RH, typically near saturation
+    pub fn is_humidity_out_of_range(&self) -> bool {
+        !(0..=100_000).contains(&self.humidity_mrh())
+    }
+}
+
+///ticks * span / 65535, rounded to nearest
+fn scale_ticks(ticks: u16, span: i64) -> i32 {
+    ((i64::from(ticks) * span + 32_767) / 65_535) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimum_ticks_convert_to_range_floor() {
+        let result = Measurement::from_ticks(0, 0);
+
+        assert_eq!(-45.0, result.temperature_celsius());
+        assert_eq!(-6.0, result.humidity_percent());
+    }
+
+    #[test]
+    fn maximum_ticks_convert_to_range_ceiling() {
+        let result = Measurement::from_ticks(u16::MAX, u16::MAX);
+
+        assert_eq!(130.0, result.temperature_celsius());
+        assert_eq!(119.0, result.humidity_percent());
+    }
+
+    #[test]
+    fn mid_scale_ticks_convert() {
+        let result = Measurement::from_ticks(0x6666, 0x8000);
+
+        assert!((result.temperature_celsius() - 25.0).abs() < 0.01);
+        assert!((result.humidity_percent() - 56.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn range_limits_convert_to_milli_units() {
+        let minimum = Measurement::from_ticks(0, 0);
+        let maximum = Measurement::from_ticks(u16::MAX, u16::MAX);
+
+        assert_eq!(-45_000, minimum.temperature_mc());
+        assert_eq!(-6_000, minimum.humidity_mrh());
+        assert_eq!(130_000, maximum.temperature_mc());
+        assert_eq!(119_000, maximum.humidity_mrh());
+    }
+
+    #[test]
+    fn mid_scale_ticks_convert_to_milli_units() {
+        let result = Measurement::from_ticks(0x6666, 0x8000);
+
+        assert_eq!(25_000, result.temperature_mc());
+        assert_eq!(56_501, result.humidity_mrh());
+    }
+
+    #[test]
+    fn humidity_above_range_is_clamped_and_flagged() {
+        let result = Measurement::from_ticks(0x6666, u16::MAX);
+
+        assert_eq!(100_000, result.humidity_mrh_clamped());
+        assert_eq!(100.0, result.humidity_percent_clamped());
+        assert!(result.is_humidity_out_of_range());
+    }
+
+    #[test]
+    fn humidity_below_range_is_clamped_and_flagged() {
+        let result = Measurement::from_ticks(0x6666, 0);
+
+        assert_eq!(0, result.humidity_mrh_clamped());
+        assert_eq!(0.0, result.humidity_percent_clamped());
+        assert!(result.is_humidity_out_of_range());
+    }
+
+    #[test]
+    fn humidity_in_range_is_not_flagged() {
+        let result = Measurement::from_ticks(0x6666, 0x8000);
+
+        assert_eq!(result.humidity_mrh(), result.humidity_mrh_clamped());
+        assert!(!result.is_humidity_out_of_range());
+    }
+
+    #[test]
+    fn milli_units_agree_with_float_conversion() {
+        for ticks in (0..=u16::MAX).step_by(97) {
+            let result = Measurement::from_ticks(ticks, ticks);
+
+            assert!((result.temperature_mc() as f32 - result.temperature_celsius() * 1000.0).abs() <= 1.0);
+            assert!((result.humidity_mrh() as f32 - result.humidity_percent() * 1000.0).abs() <= 1.0);
+        }
+    }
+}
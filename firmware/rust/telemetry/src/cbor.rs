@@ -0,0 +1,121 @@
+//! CBOR (RFC 8949) with the same layout and keys as the JSON encoding
+
+use alloc::vec::Vec;
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::snapshot::Snapshot;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+
+pub fn encode(snapshot: &Snapshot) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    push_header(&mut output, MAJOR_MAP, 3);
+    push_text(&mut output, "id");
+    push_text(&mut output, snapshot.device_id);
+    push_text(&mut output, "ts");
+    push_header(&mut output, MAJOR_UNSIGNED, snapshot.timestamp_ms);
+    push_text(&mut output, "r");
+    push_header(&mut output, MAJOR_ARRAY, snapshot.readings.len() as u64);
+
+    let mut text = String::new();
+
+    for reading in snapshot.readings {
+        push_header(&mut output, MAJOR_MAP, 4);
+
+        push_text(&mut output, "c");
+        text.clear();
+        let _ = write!(text, "{}", reading.channel);
+        push_text(&mut output, &text);
+
+        push_text(&mut output, "k");
+        text.clear();
+        let _ = write!(text, "{}", reading.key());
+        push_text(&mut output, &text);
+
+        push_text(&mut output, "v");
+        push_integer(&mut output, i64::from(reading.value));
+
+        push_text(&mut output, "ts");
+        push_header(&mut output, MAJOR_UNSIGNED, reading.timestamp_ms);
+    }
+
+    output
+}
+
+///Major type and argument, in the shortest form
+fn push_header(output: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+
+    if argument < 24 {
+        output.push(major | argument as u8);
+    } else if argument <= u64::from(u8::MAX) {
+        output.push(major | 24);
+        output.push(argument as u8);
+    } else if argument <= u64::from(u16::MAX) {
+        output.push(major | 25);
+        output.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u64::from(u32::MAX) {
+        output.push(major | 26);
+        output.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        output.push(major | 27);
+        output.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+fn push_integer(output: &mut Vec<u8>, value: i64) {
+    if value >= 0 {
+        push_header(output, MAJOR_UNSIGNED, value as u64);
+    } else {
+        //-1 - n
+        push_header(output, MAJOR_NEGATIVE, !(value as u64));
+    }
+}
+
+fn push_text(output: &mut Vec<u8>, value: &str) {
+    push_header(output, MAJOR_TEXT, value.len() as u64);
+    output.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec;
+
+    fn encoded(value: i64) -> Vec<u8> {
+        let mut output = Vec::new();
+        push_integer(&mut output, value);
+        output
+    }
+
+    #[test]
+    fn integers_use_shortest_form() {
+        //RFC 8949 appendix A
+        assert_eq!(vec![0x00], encoded(0));
+        assert_eq!(vec![0x17], encoded(23));
+        assert_eq!(vec![0x18, 0x18], encoded(24));
+        assert_eq!(vec![0x19, 0x03, 0xE8], encoded(1000));
+        assert_eq!(vec![0x1A, 0x00, 0x0F, 0x42, 0x40], encoded(1_000_000));
+        assert_eq!(vec![0x1B, 0x00, 0x00, 0x00, 0xE8, 0xD4, 0xA5, 0x10, 0x00], encoded(1_000_000_000_000));
+        assert_eq!(vec![0x20], encoded(-1));
+        assert_eq!(vec![0x38, 0x63], encoded(-100));
+        assert_eq!(vec![0x39, 0x03, 0xE7], encoded(-1000));
+    }
+
+    #[test]
+    fn text_has_length_prefix() {
+        let mut output = Vec::new();
+
+        push_text(&mut output, "IETF");
+
+        assert_eq!(vec![0x64, 0x49, 0x45, 0x54, 0x46], output);
+    }
+}
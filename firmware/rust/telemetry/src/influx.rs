@@ -0,0 +1,92 @@
+//! InfluxDB line protocol.  One line per channel and timestamp, measurement named after the part:
+//! `ina237,device=esp123,instance=a voltage_mv=800i,current_ua=3051i 1700000000000000000`
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use sensor_core::reading::Reading;
+
+use crate::snapshot::Snapshot;
+
+pub fn encode(snapshot: &Snapshot) -> String {
+    let mut output = String::new();
+
+    let mut start = 0;
+
+    while start < snapshot.readings.len() {
+        let first = &snapshot.readings[start];
+
+        let end = snapshot.readings[start..]
+            .iter()
+            .position(|reading| !same_line(first, reading))
+            .map_or(snapshot.readings.len(), |offset| start + offset);
+
+        push_line(&mut output, snapshot.device_id, &snapshot.readings[start..end]);
+
+        start = end;
+    }
+
+    output
+}
+
+fn same_line(first: &Reading, reading: &Reading) -> bool {
+    reading.channel == first.channel && reading.timestamp_ms == first.timestamp_ms
+}
+
+fn push_line(output: &mut String, device_id: &str, readings: &[Reading]) {
+    let first = &readings[0];
+
+    push_escaped(output, first.channel.sensor, false);
+    output.push_str(",device=");
+    push_escaped(output, device_id, true);
+    output.push_str(",instance=");
+    push_escaped(output, first.channel.instance, true);
+
+    for (index, reading) in readings.iter().enumerate() {
+        output.push(if index == 0 { ' ' } else { ',' });
+
+        let _ = write!(output, "{}={}i", reading.key(), reading.value);
+    }
+
+    //Line protocol timestamps default to nanoseconds
+    let _ = writeln!(output, " {}", u128::from(first.timestamp_ms) * 1_000_000);
+}
+
+///Measurements escape commas and spaces, tag values also escape equals signs
+fn push_escaped(output: &mut String, value: &str, tag: bool) {
+    for c in value.chars() {
+        if c == ',' || c == ' ' || (tag && c == '=') {
+            output.push('\\');
+        }
+
+        output.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use sensor_core::reading::{ChannelId, Quantity, Unit};
+
+    #[test]
+    fn tags_are_escaped() {
+        let readings = [Reading::new(
+            ChannelId::new("sht 40", "in,side"),
+            Quantity::Temperature,
+            Unit::MilliCelsius,
+            21_000,
+            1,
+        )];
+
+        let result = encode(&Snapshot::new("bench=1", 1, &readings));
+
+        assert_eq!("sht\\ 40,device=bench\\=1,instance=in\\,side temperature_mc=21000i 1000000\n", result);
+    }
+
+    #[test]
+    fn empty_snapshot_has_no_lines() {
+        assert_eq!("", encode(&Snapshot::new("esp123", 0, &[])));
+    }
+}
@@ -0,0 +1,373 @@
+//! Compact JSON:
+//! `{"id":"<device>","ts":<ms>,"r":[{"c":"<channel>","k":"<key>","v":<value>,"ts":<ms>},...]}`
+//!
+//! Also the small JSON reader and string escaper the rest of the firmware shares.  Numbers are read
+//! as f64, which holds every integer the device uses exactly.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::snapshot::Snapshot;
+
+pub fn encode(snapshot: &Snapshot) -> String {
+    let mut output = String::new();
+
+    output.push_str("{\"id\":");
+    push_string(&mut output, snapshot.device_id);
+    let _ = write!(output, ",\"ts\":{},\"r\":[", snapshot.timestamp_ms);
+
+    for (index, reading) in snapshot.readings.iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+
+        output.push_str("{\"c\":\"");
+        let _ = write!(Escaped(&mut output), "{}", reading.channel);
+        let _ = write!(
+            output,
+            "\",\"k\":\"{}\",\"v\":{},\"ts\":{}}}",
+            reading.key(),
+            reading.value,
+            reading.timestamp_ms
+        );
+    }
+
+    output.push_str("]}");
+
+    output
+}
+
+///Appends `value` as a quoted, escaped JSON string.  Shared with the other JSON writers on the
+///device.
+pub fn push_string(output: &mut String, value: &str) {
+    output.push('"');
+    let _ = Escaped(output).write_str(value);
+    output.push('"');
+}
+
+///Escapes quotes, backslashes and control characters as it writes
+struct Escaped<'a>(&'a mut String);
+
+impl Write for Escaped<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            match c {
+                '"' => self.0.push_str("\\\""),
+                '\\' => self.0.push_str("\\\\"),
+                '\n' => self.0.push_str("\\n"),
+                '\r' => self.0.push_str("\\r"),
+                '\t' => self.0.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(self.0, "\\u{:04x}", c as u32)?,
+                c => self.0.push(c),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    ///Members in document order
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    ///Whole numbers only
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_f64()
+            .filter(|value| (-9.0e15..9.0e15).contains(value) && *value as i64 as f64 == *value)
+            .map(|value| value as i64)
+    }
+}
+
+///Nesting allowed before a document is rejected, to bound recursion on the device
+const MAX_DEPTH: usize = 8;
+
+///Parses a complete document, None if it isn't valid JSON
+pub fn parse(text: &str) -> Option<Value> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        position: 0,
+    };
+
+    let value = parser.value(0)?;
+
+    parser.whitespace();
+
+    (parser.position == parser.text.len()).then_some(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.position += 1;
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Option<Value> {
+        self.text[self.position..]
+            .starts_with(literal.as_bytes())
+            .then(|| {
+                self.position += literal.len();
+                value
+            })
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        self.whitespace();
+
+        match self.peek()? {
+            b'n' => self.literal("null", Value::Null),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'"' => self.string().map(Value::String),
+            b'[' => self.array(depth),
+            b'{' => self.object(depth),
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.position;
+
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.position += 1;
+        }
+
+        let text = core::str::from_utf8(&self.text[start..self.position]).ok()?;
+
+        text.parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(Value::Number)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        //Opening quote
+        self.position += 1;
+
+        let mut bytes = Vec::new();
+
+        loop {
+            let byte = self.peek()?;
+            self.position += 1;
+
+            match byte {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let escaped = self.peek()?;
+                    self.position += 1;
+
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let hex = self.text.get(self.position..self.position + 4)?;
+                            self.position += 4;
+
+                            //Surrogate pairs aren't needed for anything the API accepts
+                            char::from_u32(
+                                u32::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()?,
+                            )?
+                        }
+                        _ => return None,
+                    };
+
+                    let mut encoded = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                }
+                byte if byte < 0x20 => return None,
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Option<Value> {
+        self.position += 1;
+
+        let mut items = Vec::new();
+
+        self.whitespace();
+
+        if self.peek()? == b']' {
+            self.position += 1;
+            return Some(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.value(depth + 1)?);
+
+            self.whitespace();
+
+            match self.peek()? {
+                b',' => self.position += 1,
+                b']' => {
+                    self.position += 1;
+                    return Some(Value::Array(items));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Option<Value> {
+        self.position += 1;
+
+        let mut members = Vec::new();
+
+        self.whitespace();
+
+        if self.peek()? == b'}' {
+            self.position += 1;
+            return Some(Value::Object(members));
+        }
+
+        loop {
+            self.whitespace();
+
+            if self.peek()? != b'"' {
+                return None;
+            }
+
+            let name = self.string()?;
+
+            self.whitespace();
+
+            if self.peek()? != b':' {
+                return None;
+            }
+
+            self.position += 1;
+
+            members.push((name, self.value(depth + 1)?));
+
+            self.whitespace();
+
+            match self.peek()? {
+                b',' => self.position += 1,
+                b'}' => {
+                    self.position += 1;
+                    return Some(Value::Object(members));
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn empty_snapshot() {
+        let result = encode(&Snapshot::new("esp123", 5, &[]));
+
+        assert_eq!(r#"{"id":"esp123","ts":5,"r":[]}"#, result);
+    }
+
+    #[test]
+    fn device_id_is_escaped() {
+        let result = encode(&Snapshot::new("a\"b\\c\u{1}", 0, &[]));
+
+        assert_eq!(r#"{"id":"a\"b\\c\u0001","ts":0,"r":[]}"#, result);
+    }
+
+    #[test]
+    fn document_is_parsed() {
+        let value = parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"é\n"}} "#).unwrap();
+
+        assert_eq!(
+            Some(&Value::Array(alloc::vec![
+                Value::Number(1.0),
+                Value::Number(-25.0),
+                Value::Bool(true),
+                Value::Null
+            ])),
+            value.get("a")
+        );
+        assert_eq!(
+            Some("x\"é\n"),
+            value
+                .get("b")
+                .and_then(|b| b.get("c"))
+                .and_then(Value::as_str)
+        );
+        assert_eq!(Some(1), parse("1").and_then(|value| value.as_i64()));
+        assert_eq!(None, parse("1.5").and_then(|value| value.as_i64()));
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        for text in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "{\"a\":1}x",
+            "\"\u{1}\"",
+            "nul",
+            "1e999",
+            "{a:1}",
+        ] {
+            assert_eq!(None, parse(text), "{:?}", text);
+        }
+
+        assert_eq!(None, parse(&"[".repeat(20)));
+    }
+}
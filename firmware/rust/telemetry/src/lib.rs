@@ -0,0 +1,15 @@
+#![deny(unsafe_code)]
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "influx")]
+pub mod influx;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod snapshot;
+
+#[cfg(not(any(feature = "json", feature = "cbor", feature = "influx")))]
+compile_error!("enable at least one of the json, cbor or influx features");
@@ -0,0 +1,71 @@
+use alloc::vec::Vec;
+
+use sensor_core::reading::Reading;
+
+///Every reading taken in one pass over the sensors, ready to encode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snapshot<'a> {
+    pub device_id: &'a str,
+    ///When the snapshot was taken.  Milliseconds since the Unix epoch once the clock is set,
+    ///otherwise since boot.
+    pub timestamp_ms: u64,
+    pub readings: &'a [Reading],
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn new(device_id: &'a str, timestamp_ms: u64, readings: &'a [Reading]) -> Snapshot<'a> {
+        Snapshot {
+            device_id,
+            timestamp_ms,
+            readings,
+        }
+    }
+
+    ///Encodes in the format chosen at build time, see `Format::DEFAULT`
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(Format::DEFAULT)
+    }
+
+    pub fn encode_as(&self, format: Format) -> Vec<u8> {
+        match format {
+            #[cfg(feature = "json")]
+            Format::Json => crate::json::encode(self).into_bytes(),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => crate::cbor::encode(self),
+            #[cfg(feature = "influx")]
+            Format::Influx => crate::influx::encode(self).into_bytes(),
+        }
+    }
+}
+
+///Payload formats built into this crate, one per enabled feature
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "influx")]
+    Influx,
+}
+
+impl Format {
+    ///The first enabled of JSON, CBOR and Influx line protocol
+    #[cfg(feature = "json")]
+    pub const DEFAULT: Format = Format::Json;
+    #[cfg(all(not(feature = "json"), feature = "cbor"))]
+    pub const DEFAULT: Format = Format::Cbor;
+    #[cfg(all(not(feature = "json"), not(feature = "cbor"), feature = "influx"))]
+    pub const DEFAULT: Format = Format::Influx;
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => "application/json",
+            #[cfg(feature = "cbor")]
+            Format::Cbor => "application/cbor",
+            #[cfg(feature = "influx")]
+            Format::Influx => "text/plain",
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Compares each encoding of a fixed snapshot against the files in `tests/golden`.  Regenerate with
+//! `UPDATE_GOLDEN=1 cargo test --all-features` after an intentional format change.
+
+use std::path::PathBuf;
+
+use sensor_core::reading::{ChannelId, Quantity, Reading, Unit};
+use telemetry::snapshot::{Format, Snapshot};
+
+const INA_A: ChannelId = ChannelId::new("ina237", "a");
+const NAU_A: ChannelId = ChannelId::new("nau7802", "a");
+const SHT_INSIDE: ChannelId = ChannelId::new("sht40", "inside");
+
+fn readings() -> Vec<Reading> {
+    let ts = 1_700_000_000_000;
+
+    vec![
+        Reading::new(INA_A, Quantity::Voltage, Unit::Millivolt, 5_012, ts),
+        Reading::new(INA_A, Quantity::ShuntVoltage, Unit::Microvolt, 125, ts),
+        Reading::new(INA_A, Quantity::Current, Unit::Microamp, -250_000, ts),
+        Reading::new(INA_A, Quantity::Temperature, Unit::MilliCelsius, 31_250, ts),
+        Reading::new(NAU_A, Quantity::RawCounts, Unit::Counts, -8_388_608, ts + 3),
+        Reading::new(SHT_INSIDE, Quantity::Temperature, Unit::MilliCelsius, 22_480, ts + 12),
+        Reading::new(SHT_INSIDE, Quantity::RelativeHumidity, Unit::MilliPercentRh, 45_102, ts + 12),
+    ]
+}
+
+fn check(format: Format, file: &str) {
+    let readings = readings();
+    let snapshot = Snapshot::new("esp32-sensor-01", 1_700_000_000_020, &readings);
+
+    let result = snapshot.encode_as(format);
+
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", file].iter().collect();
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &result).unwrap();
+    }
+
+    let expected = std::fs::read(&path).unwrap();
+
+    assert_eq!(expected, result, "{} differs from golden file", file);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_matches_golden() {
+    check(Format::Json, "snapshot.json");
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_matches_golden() {
+    check(Format::Cbor, "snapshot.cbor");
+}
+
+#[cfg(feature = "influx")]
+#[test]
+fn influx_matches_golden() {
+    check(Format::Influx, "snapshot.influx");
+}